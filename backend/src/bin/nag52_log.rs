@@ -0,0 +1,180 @@
+//! Headless record logger, for bench setups without a display (Or over SSH).
+//!
+//! Connects to the TCU, polls one record at a fixed rate and appends every sample to a CSV
+//! file until killed. Each row is written straight to the file, so stopping the logger with
+//! Ctrl+C loses nothing.
+//!
+//! ```text
+//! nag52_log --log <record> --rate <ms> --out <file> [--adapter usb|passthru|socketcan|sim] [--device <name>]
+//! nag52_log --help
+//! ```
+use std::{
+    fs::File,
+    io::Write,
+    path::PathBuf,
+    process::ExitCode,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use backend::{
+    diag::{
+        records::{RecordIdents, LocalRecordData},
+        AdapterHw, AdapterType, Nag52Diag,
+    },
+    ecu_diagnostics::{
+        hardware::{passthru::PassthruScanner, HardwareInfo, HardwareScanner},
+        DiagError,
+    },
+    hw::{sim::SimulatedTcu, usb_scanner::Nag52UsbScanner},
+};
+
+#[cfg(unix)]
+use backend::ecu_diagnostics::hardware::socketcan::SocketCanScanner;
+
+const USAGE: &str = "Usage: nag52_log --log <record> --rate <ms> --out <file> [--adapter usb|passthru|socketcan|sim] [--device <name>]";
+/// The TCU cannot answer much faster than this
+const MIN_RATE_MS: u64 = 20;
+
+struct Args {
+    record: RecordIdents,
+    rate_ms: u64,
+    out: PathBuf,
+    adapter: AdapterType,
+    /// First device found if not given
+    device: Option<String>,
+}
+
+fn parse_record(s: &str) -> Option<RecordIdents> {
+    let id = s.strip_prefix("0x").and_then(|h| u8::from_str_radix(h, 16).ok());
+    match id {
+        Some(id) => RecordIdents::from_id(id),
+        None => RecordIdents::ALL.into_iter().find(|r| format!("{:?}", r).eq_ignore_ascii_case(s)),
+    }
+}
+
+fn parse_adapter(s: &str) -> Option<AdapterType> {
+    match s.to_ascii_lowercase().as_str() {
+        "usb" => Some(AdapterType::USB),
+        "passthru" => Some(AdapterType::Passthru),
+        #[cfg(unix)]
+        "socketcan" => Some(AdapterType::SocketCAN),
+        "sim" => Some(AdapterType::Simulated),
+        _ => None,
+    }
+}
+
+/// None if help was asked for
+fn parse_args() -> Result<Option<Args>, String> {
+    let mut record = None;
+    let mut rate_ms = None;
+    let mut out = None;
+    let mut adapter = AdapterType::USB;
+    let mut device = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        if flag == "--help" || flag == "-h" {
+            return Ok(None);
+        }
+        let value = args.next().ok_or(format!("Missing value for {}", flag))?;
+        match flag.as_str() {
+            "--log" => record = Some(parse_record(&value).ok_or(format!("Unknown record '{}'", value))?),
+            "--rate" => rate_ms = Some(value.parse::<u64>().map_err(|_| format!("Invalid rate '{}'", value))?),
+            "--out" => out = Some(PathBuf::from(value)),
+            "--adapter" => adapter = parse_adapter(&value).ok_or(format!("Unknown adapter '{}'", value))?,
+            "--device" => device = Some(value),
+            _ => return Err(format!("Unknown argument '{}'", flag)),
+        }
+    }
+    Ok(Some(Args {
+        record: record.ok_or("--log is required")?,
+        rate_ms: rate_ms.ok_or("--rate is required")?.max(MIN_RATE_MS),
+        out: out.ok_or("--out is required")?,
+        adapter,
+        device,
+    }))
+}
+
+fn list_devices(ty: AdapterType) -> Vec<HardwareInfo> {
+    match ty {
+        AdapterType::USB => Nag52UsbScanner::new().list_devices(),
+        AdapterType::Passthru => PassthruScanner::new().list_devices(),
+        #[cfg(unix)]
+        AdapterType::SocketCAN => SocketCanScanner::new().list_devices(),
+        AdapterType::Simulated => Vec::new(),
+    }
+}
+
+fn connect(args: &Args) -> Result<Nag52Diag, String> {
+    let hw = if args.adapter == AdapterType::Simulated {
+        AdapterHw::Simulated(SimulatedTcu::new())
+    } else {
+        let devices = list_devices(args.adapter);
+        let info = match &args.device {
+            Some(name) => devices.into_iter().find(|d| &d.name == name),
+            None => devices.into_iter().next(),
+        }
+        .ok_or(format!("No {:?} adapter found", args.adapter))?;
+        eprintln!("Connecting to '{}'", info.name);
+        AdapterHw::try_connect(&info, args.adapter)
+            .map_err(|e| DiagError::from(Arc::new(e)).to_string())?
+    };
+    Nag52Diag::new(hw).map_err(|e| e.to_string())
+}
+
+fn write_row(f: &mut File, elapsed: Duration, data: &LocalRecordData) -> std::io::Result<()> {
+    let values: Vec<String> = data.fields().into_iter().map(|(_, v)| v).collect();
+    writeln!(f, "{:.3},{}", elapsed.as_secs_f64(), values.join(","))
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let nag = connect(&args)?;
+    let mut f = File::create(&args.out).map_err(|e| format!("Cannot create {}: {}", args.out.display(), e))?;
+    eprintln!("Logging {:?} every {}ms to {}", args.record, args.rate_ms, args.out.display());
+    let interval = Duration::from_millis(args.rate_ms);
+    let start = Instant::now();
+    let mut header_written = false;
+    let mut errors = 0u32;
+    loop {
+        let poll_start = Instant::now();
+        match nag.read_record(args.record) {
+            Ok(data) => {
+                if !header_written {
+                    let names: Vec<String> = data.fields().into_iter().map(|(k, _)| k).collect();
+                    writeln!(f, "Time (s),{}", names.join(",")).map_err(|e| e.to_string())?;
+                    header_written = true;
+                }
+                write_row(&mut f, start.elapsed(), &data).map_err(|e| e.to_string())?;
+            },
+            Err(e) => {
+                // Keep going, a long capture should survive the odd dropped frame
+                errors += 1;
+                eprintln!("Read of {:?} failed ({} so far): {}", args.record, errors, e);
+            }
+        }
+        if let Some(wait) = interval.checked_sub(poll_start.elapsed()) {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(Some(a)) => a,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        },
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -18,6 +18,48 @@ pub struct PartitionInfo {
 
 pub const OTA_FORMAT: u8 = 0xF0;
 
+/// Why [Nag52Diag::flash_ota] failed
+#[derive(Debug)]
+pub enum FlashError {
+    /// TCU would not start the transfer
+    Prepare(DiagError),
+    /// Block number `block` (Starting at 0), starting at `address`, could not be sent
+    Write { block: usize, address: u32, err: DiagError },
+    /// TCU responded to block number `block` without acknowledging it
+    BadAck { block: usize, address: u32, response: Vec<u8> },
+    /// Image was written but the TCU rejected it
+    Verify(DiagError),
+    /// The progress callback asked to stop. The TCU keeps running its current firmware
+    Cancelled,
+}
+
+impl std::fmt::Display for FlashError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlashError::Prepare(e) => write!(f, "Failed to prepare for update. {e}"),
+            FlashError::Write { block, address, err } => write!(f, "Failed to write block {block} (Address 0x{address:08X}). {err}"),
+            FlashError::BadAck { block, address, response } => {
+                write!(f, "Block {block} (Address 0x{address:08X}) was not acknowledged by the TCU. Response was {response:02X?}")
+            },
+            FlashError::Verify(e) => write!(f, "Error verification: {e}"),
+            FlashError::Cancelled => write!(f, "Update cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for FlashError {}
+
+/// Stages of [Nag52Diag::flash_ota], reported to its progress callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashProgress {
+    /// TCU is ready to receive the image at `start_addr`, in blocks of `block_size` bytes
+    Started { start_addr: u32, block_size: u16 },
+    /// A block was written. `bytes_done` of `bytes_total` are now on the TCU
+    Written { bytes_done: u32, bytes_total: u32 },
+    /// Whole image was written, TCU is checking it
+    Verifying,
+}
+
 impl Nag52Diag {
     pub fn get_total_flash_size(&self) -> PartitionInfo {
         PartitionInfo {
@@ -53,7 +95,7 @@ impl Nag52Diag {
     pub fn get_running_fw_info(&self) -> DiagServerResult<FirmwareHeader> {
         self.with_kwp(|server| {
             server.kwp_read_custom_local_identifier(0x28).map(|res| {
-                println!("{:02X?}", res);
+                log::debug!("Running firmware info: {:02X?}", res);
                 FirmwareHeader::unpack_from_slice(&res)
                     .map_err(|_| DiagError::InvalidResponseLength)
             })?
@@ -96,11 +138,12 @@ impl Nag52Diag {
         res
     }
 
-    pub fn transfer_data(&self, blk_id: u8, data: &[u8]) -> DiagServerResult<()> {
+    /// Sends one block of a transfer, returning the TCU's raw positive response
+    pub fn transfer_data(&self, blk_id: u8, data: &[u8]) -> DiagServerResult<Vec<u8>> {
         self.with_kwp(|server| {
             let mut req = vec![0x36, blk_id];
             req.extend_from_slice(data);
-            server.send_byte_array_with_response(&req).map(|_| ())
+            server.send_byte_array_with_response(&req)
         })
     }
 
@@ -112,6 +155,57 @@ impl Nag52Diag {
         })
     }
 
+    /// Ends a transfer early (User cancelled). The incomplete image is not
+    /// verified, so the TCU keeps running from its current partition
+    pub fn abort_transfer(&self) -> DiagServerResult<()> {
+        self.with_kwp(|server| {
+            server.send_byte_array_with_response(&[0x37]).map(|_| ())
+        })
+    }
+
+    /// Writes `image` to the next OTA partition and verifies it, calling `on_progress` at every stage.
+    /// Returning false from `on_progress` aborts the transfer, in which case [FlashError::Cancelled]
+    /// is returned and the TCU keeps running its current firmware.
+    ///
+    /// Every block must be acknowledged with a positive response echoing its block counter,
+    /// otherwise the transfer is aborted, as carrying on would leave a corrupt image behind
+    pub fn flash_ota<F: FnMut(FlashProgress) -> bool>(&self, image: &[u8], reboot: bool, mut on_progress: F) -> Result<(), FlashError> {
+        let (start_addr, block_size) = self.begin_ota(image.len() as u32).map_err(FlashError::Prepare)?;
+        let mut keep_going = on_progress(FlashProgress::Started { start_addr, block_size });
+        let mut bytes_done = 0;
+        for (block, data) in image.chunks(block_size.max(1) as usize).enumerate() {
+            if !keep_going {
+                let _ = self.abort_transfer();
+                return Err(FlashError::Cancelled);
+            }
+            let address = start_addr + bytes_done;
+            let blk_id = ((block + 1) & 0xFF) as u8;
+            let response = self.transfer_data(blk_id, data).map_err(|err| {
+                let _ = self.abort_transfer();
+                FlashError::Write { block, address, err }
+            })?;
+            // The block counter is only checked if the TCU echoes it back
+            let acked = match response.as_slice() {
+                [0x76] => true,
+                [0x76, id, ..] => *id == blk_id,
+                _ => false,
+            };
+            if !acked {
+                let _ = self.abort_transfer();
+                return Err(FlashError::BadAck { block, address, response });
+            }
+            bytes_done += data.len() as u32;
+            keep_going = on_progress(FlashProgress::Written { bytes_done, bytes_total: image.len() as u32 });
+        }
+        // Cancelling on the last block must not let the image be verified and booted
+        if !keep_going {
+            let _ = self.abort_transfer();
+            return Err(FlashError::Cancelled);
+        }
+        on_progress(FlashProgress::Verifying);
+        self.end_ota(reboot).map_err(FlashError::Verify)
+    }
+
     pub fn end_ota(&self, reboot: bool) -> DiagServerResult<()> {
         self.with_kwp(|server| {
             server.send_byte_array_with_response(&[0x37])?;
@@ -2,9 +2,11 @@ use std::fmt::Display;
 
 use ecu_diagnostics::{bcd_decode_slice, DiagServerResult};
 
+use serde::Serialize;
+
 use super::Nag52Diag;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub enum EgsMode {
     EGS51,
     EGS52,
@@ -34,7 +36,7 @@ impl Display for EgsMode {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub enum PCBVersion {
     OnePointOne,
     OnePointTwo,
@@ -42,18 +44,44 @@ pub enum PCBVersion {
     Unknown,
 }
 
+/// Hardware build dates (week, year) of each known PCB revision
+const PCB_BUILD_DATES: [((u32, u32), PCBVersion); 3] = [
+    ((49, 21), PCBVersion::OnePointOne),
+    ((27, 22), PCBVersion::OnePointTwo),
+    ((49, 22), PCBVersion::OnePointThree),
+];
+
+/// How many weeks a boards HW date may be away from a known build date.
+/// The closest known dates are 22 weeks apart, so the windows do not overlap:
+///
+/// * V1.1 - Week 39 of 2021 to week 7 of 2022
+/// * V1.2 - Week 17 of 2022 to week 37 of 2022
+/// * V1.3 - Week 39 of 2022 to week 7 of 2023
+const PCB_DATE_TOLERANCE_WEEKS: u32 = 10;
+
 impl PCBVersion {
-    fn from_date(w: u32, y: u32) -> Self {
-        if w == 49 && y == 21 {
-            Self::OnePointOne
-        } else if w == 27 && y == 22 {
-            Self::OnePointTwo
-        } else if w == 49 && y == 22 {
-            Self::OnePointThree
-        } else {
-            Self::Unknown
+    /// Board version from the EFUSE board type byte (Same values as the config app's `BoardType`)
+    fn from_efuse(board_ver: u8) -> Option<Self> {
+        match board_ver {
+            1 => Some(Self::OnePointOne),
+            2 => Some(Self::OnePointTwo),
+            3 => Some(Self::OnePointThree),
+            _ => None,
         }
     }
+
+    /// Fallback for boards without EFUSE data. Picks the revision built closest
+    /// to the HW build date, as long as it is within [PCB_DATE_TOLERANCE_WEEKS]
+    fn from_date(w: u32, y: u32) -> Self {
+        let abs_week = |w: u32, y: u32| y * 52 + w;
+        PCB_BUILD_DATES
+            .iter()
+            .map(|((kw, ky), v)| (abs_week(w, y).abs_diff(abs_week(*kw, *ky)), *v))
+            .filter(|(diff, _)| *diff <= PCB_DATE_TOLERANCE_WEEKS)
+            .min_by_key(|(diff, _)| *diff)
+            .map(|(_, v)| v)
+            .unwrap_or(Self::Unknown)
+    }
 }
 
 impl Display for PCBVersion {
@@ -67,7 +95,7 @@ impl Display for PCBVersion {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct IdentData {
     pub egs_mode: EgsMode,
     pub board_ver: PCBVersion,
@@ -91,12 +119,18 @@ impl Nag52Diag {
     pub fn query_ecu_data(&self) -> DiagServerResult<IdentData> {
         self.with_kwp(|k| {
             let ident = k.kwp_read_daimler_identification()?;
+            // Older firmware or unprogrammed boards have no EFUSE board type
+            let efuse_ver = k
+                .kwp_read_custom_local_identifier(0xFD)
+                .ok()
+                .and_then(|efuse| efuse.first().copied())
+                .and_then(PCBVersion::from_efuse);
             Ok(IdentData {
                 egs_mode: EgsMode::from(ident.diag_info.get_info_id()),
-                board_ver: PCBVersion::from_date(
+                board_ver: efuse_ver.unwrap_or_else(|| PCBVersion::from_date(
                     bcd_decode_to_int(ident.ecu_hw_build_week),
                     bcd_decode_to_int(ident.ecu_hw_build_year),
-                ),
+                )),
                 manf_day: bcd_decode_to_int(ident.ecu_production_day),
                 manf_month: bcd_decode_to_int(ident.ecu_production_month),
                 manf_year: bcd_decode_to_int(ident.ecu_production_year),
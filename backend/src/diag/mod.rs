@@ -1,7 +1,8 @@
 use core::fmt;
 use std::{
-    borrow::{Borrow, BorrowMut},
-    sync::{Arc, Mutex, RwLock, mpsc::{Receiver, self}},
+    borrow::BorrowMut,
+    sync::{Arc, Mutex, RwLock, mpsc::{Receiver, self}, atomic::{AtomicBool, AtomicU32, Ordering}},
+    time::Instant,
 };
 
 use ecu_diagnostics::{hardware::{
@@ -20,6 +21,7 @@ use ecu_diagnostics::{kwp2000::*, DiagServerResult};
 use ecu_diagnostics::hardware::socketcan::{SocketCanDevice, SocketCanScanner};
 
 use crate::hw::{
+    sim::SimulatedTcu,
     usb::{EspLogMessage, Nag52USB},
     usb_scanner::Nag52UsbScanner,
 };
@@ -27,7 +29,9 @@ use crate::hw::{
 pub mod flash;
 pub mod ident;
 pub mod settings;
+pub mod settings_layout;
 pub mod nvs;
+pub mod records;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum AdapterType {
@@ -35,6 +39,8 @@ pub enum AdapterType {
     Passthru,
     #[cfg(unix)]
     SocketCAN,
+    /// Demo mode, no hardware needed
+    Simulated,
 }
 
 #[derive(Debug, Clone)]
@@ -64,6 +70,7 @@ pub enum AdapterHw {
     Passthru(PassthruDevice),
     #[cfg(unix)]
     SocketCAN(SocketCanDevice),
+    Simulated(SimulatedTcu),
 }
 
 impl fmt::Debug for AdapterHw {
@@ -73,6 +80,7 @@ impl fmt::Debug for AdapterHw {
             Self::Passthru(_) => f.debug_tuple("Passthru").finish(),
             #[cfg(unix)]
             Self::SocketCAN(_) => f.debug_tuple("SocketCAN").finish(),
+            Self::Simulated(_) => f.debug_tuple("Simulated").finish(),
         }
     }
 }
@@ -84,6 +92,7 @@ impl AdapterHw {
             AdapterType::Passthru => Self::Passthru(PassthruDevice::try_connect(info)?),
             #[cfg(unix)]
             AdapterType::SocketCAN => Self::SocketCAN(SocketCanDevice::try_connect(info)?),
+            AdapterType::Simulated => Self::Simulated(SimulatedTcu::try_connect(info)?),
         })
     }
 
@@ -93,6 +102,7 @@ impl AdapterHw {
             Self::Passthru(_) => AdapterType::Passthru,
             #[cfg(unix)]
             Self::SocketCAN(_) => AdapterType::SocketCAN,
+            Self::Simulated(_) => AdapterType::Simulated,
         }
     }
 
@@ -102,6 +112,7 @@ impl AdapterHw {
             Self::Passthru(p) => p.create_iso_tp_channel(),
             #[cfg(unix)]
             Self::SocketCAN(s) => s.create_iso_tp_channel(),
+            Self::Simulated(s) => s.create_iso_tp_channel(),
         }
     }
 
@@ -111,6 +122,17 @@ impl AdapterHw {
             Self::Passthru(p) => p.get_info().clone(),
             #[cfg(unix)]
             Self::SocketCAN(s) => s.get_info().clone(),
+            Self::Simulated(s) => s.get_info().clone(),
+        }
+    }
+
+    pub fn get_device_desc(&self) -> String {
+        match self {
+            Self::Usb(u) => u.get_device_desc(),
+            Self::Passthru(p) => p.get_device_desc(),
+            #[cfg(unix)]
+            Self::SocketCAN(s) => s.get_device_desc(),
+            Self::Simulated(s) => s.get_device_desc(),
         }
     }
 
@@ -120,6 +142,17 @@ impl AdapterHw {
             Self::Passthru(p) => p.get_data_rate(),
             #[cfg(unix)]
             Self::SocketCAN(s) => s.get_data_rate(),
+            Self::Simulated(s) => s.get_data_rate(),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        match self {
+            Self::Usb(u) => Nag52Endpoint::is_connected(u),
+            Self::Passthru(p) => Nag52Endpoint::is_connected(p),
+            #[cfg(unix)]
+            Self::SocketCAN(s) => Nag52Endpoint::is_connected(s),
+            Self::Simulated(s) => Nag52Endpoint::is_connected(s),
         }
     }
 
@@ -200,7 +233,10 @@ impl Nag52Endpoint for Nag52USB {
 
 #[derive(Debug, Clone)]
 pub struct NagAppLoggerInner {
-    sender: mpsc::Sender<ServerEvent>
+    sender: mpsc::Sender<ServerEvent>,
+    /// Diagnostic payload bytes sent and received, since the last [NagAppLogger::take_payload_rate]
+    tx_bytes: Arc<AtomicU32>,
+    rx_bytes: Arc<AtomicU32>,
 }
 
 unsafe impl Send for NagAppLoggerInner{}
@@ -211,7 +247,9 @@ impl NagAppLoggerInner {
         let (tx, rx) = mpsc::channel::<ServerEvent>();
         (
             Self {
-                sender: tx
+                sender: tx,
+                tx_bytes: Arc::new(AtomicU32::new(0)),
+                rx_bytes: Arc::new(AtomicU32::new(0)),
             },
             rx
         )
@@ -220,13 +258,25 @@ impl NagAppLoggerInner {
 
 impl DiagServerLogger for NagAppLoggerInner {
     fn on_event(&self, evt: ServerEvent) {
+        match &evt {
+            ServerEvent::BytesSendState(_, b, Ok(_)) => {
+                self.tx_bytes.fetch_add(b.len() as u32, Ordering::Relaxed);
+            },
+            ServerEvent::BytesRecvState(_, Ok(b)) => {
+                self.rx_bytes.fetch_add(b.len() as u32, Ordering::Relaxed);
+            },
+            _ => {}
+        }
         self.sender.send(evt);
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct NagAppLogger {
-    recv: Arc<mpsc::Receiver<ServerEvent>>
+    recv: Arc<mpsc::Receiver<ServerEvent>>,
+    tx_bytes: Arc<AtomicU32>,
+    rx_bytes: Arc<AtomicU32>,
+    last_rate_query: Arc<Mutex<Instant>>,
 }
 
 impl NagAppLogger {
@@ -234,20 +284,134 @@ impl NagAppLogger {
         let (inner, recv) = NagAppLoggerInner::new();
         (
             Self {
-                recv: Arc::new(recv)
+                recv: Arc::new(recv),
+                tx_bytes: inner.tx_bytes.clone(),
+                rx_bytes: inner.rx_bytes.clone(),
+                last_rate_query: Arc::new(Mutex::new(Instant::now())),
             },
             inner
         )
     }
+
+    /// Diagnostic payload throughput (Tx, Rx) in bytes/sec since the last call
+    pub fn take_payload_rate(&self) -> (u32, u32) {
+        let mut last = self.last_rate_query.lock().unwrap();
+        let secs = last.elapsed().as_secs_f32().max(0.001);
+        *last = Instant::now();
+        (
+            (self.tx_bytes.swap(0, Ordering::Relaxed) as f32 / secs) as u32,
+            (self.rx_bytes.swap(0, Ordering::Relaxed) as f32 / secs) as u32,
+        )
+    }
+}
+
+/// Plain language meaning of a KWP2000 negative response code, with the likely cause where there is one
+pub fn nrc_explanation(code: u8) -> Option<&'static str> {
+    Some(match code {
+        0x10 => "generalReject - The TCU rejected the request",
+        0x11 => "serviceNotSupported - This firmware does not support the request. Is the firmware up to date?",
+        0x12 => "subFunctionNotSupported - The request was malformed, or is not supported by this firmware",
+        0x21 => "busyRepeatRequest - The TCU is busy, try again",
+        0x22 => "conditionsNotCorrect - The TCU cannot do this right now. Is the engine running, or the car not in Park?",
+        0x23 => "routineNotComplete - The routine is still running",
+        0x31 => "requestOutOfRange - The TCU does not know this identifier or value. Is the firmware up to date?",
+        0x33 => "securityAccessDenied - The TCU must be unlocked first",
+        0x35 => "invalidKey - Security unlock failed",
+        0x36 => "exceedNumberOfAttempts - Too many failed unlock attempts",
+        0x37 => "requiredTimeDelayNotExpired - Wait before trying to unlock again",
+        0x40 => "downloadNotAccepted - The TCU refused the download",
+        0x42 => "canNotDownloadToSpecifiedAddress - The download address is not valid",
+        0x43 => "canNotDownloadNumberOfBytesRequested - The image does not fit",
+        0x50 => "uploadNotAccepted - The TCU refused the upload",
+        0x71 => "transferSuspended - The transfer was suspended by the TCU",
+        0x72 => "transferAborted - The transfer was aborted by the TCU",
+        0x74 => "illegalAddressInBlockTransfer - The block address is not valid",
+        0x75 => "illegalByteCountInBlockTransfer - The block size is not valid",
+        0x76 => "illegalBlockTransferType - The block type is not valid",
+        0x77 => "blockTransferDataChecksumError - A block was corrupted in transfer",
+        0x79 => "incorrectByteCountDuringBlockTransfer - Not all bytes of the block were received",
+        0x80 => "serviceNotSupportedInActiveDiagnosticSession - Switch to another diagnostic session first",
+        _ => return None,
+    })
+}
+
+/// Replaces the definition of negative responses with [nrc_explanation], so every
+/// `to_string()` of a [Nag52Diag::with_kwp] error tells the user what went wrong
+fn explain_nrc(e: DiagError) -> DiagError {
+    match e {
+        DiagError::ECUError { code, def } => DiagError::ECUError {
+            code,
+            def: nrc_explanation(code).map(|s| s.to_string()).or(def),
+        },
+        e => e,
+    }
+}
+
+/// Negative responses to a write that mean the TCU has most likely timed out of the session the write needs
+const SESSION_LOST_NRCS: [u8; 2] = [
+    0x22, // conditionsNotCorrect
+    0x80, // serviceNotSupportedInActiveDiagnosticSession
+];
+
+/// Sends a write request. If the TCU rejects it because it has dropped out of `session`,
+/// `session` is entered again and the write is retried once before the error is returned
+pub fn send_with_session_retry(server: &DynamicDiagSession, session: KwpSessionTypeByte, req: &[u8]) -> DiagServerResult<Vec<u8>> {
+    match server.send_byte_array_with_response(req) {
+        Err(DiagError::ECUError { code, .. }) if SESSION_LOST_NRCS.contains(&code) => {
+            log::warn!("Write 0x{:02X?} rejected with NRC 0x{:02X}, entering the session again and retrying", &req[..req.len().min(2)], code);
+            server.kwp_set_session(session)?;
+            server.send_byte_array_with_response(req)
+        },
+        res => res,
+    }
+}
+
+/// Delay after the first failed attempt of [Nag52Diag::reconnect_with_backoff]
+const RECONNECT_MIN_DELAY_MS: u32 = 500;
+/// Longest delay between attempts of [Nag52Diag::reconnect_with_backoff]
+const RECONNECT_MAX_DELAY_MS: u32 = 8000;
+
+/// User overrides of the connection parameters.
+/// `None` keeps the adapter specific default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionOverrides {
+    /// ISO-TP block size
+    pub block_size: Option<u8>,
+    /// ISO-TP minimum separation time
+    pub st_min: Option<u8>,
+    /// Delay between diagnostic requests. Some firmware builds reject back-to-back
+    /// requests (E.g. whilst writing settings), a few ms of cooldown avoids this
+    pub command_cooldown_ms: Option<u32>,
+    /// Interval between tester present messages (Default 2000ms). A shorter interval keeps
+    /// a flaky session alive, at the cost of more bus traffic competing with other requests
+    pub tester_present_interval_ms: Option<u32>,
+    /// Wait for the TCU to answer tester present messages (Default true). Turning this off
+    /// avoids tester present responses interfering with long transfers, but a dropped
+    /// session is then only noticed on the next request
+    pub tester_present_require_response: Option<bool>,
+    /// How long to wait for a response before failing (Default 10000ms)
+    pub read_timeout_ms: Option<u32>,
+    /// How long a request may take to send before failing (Default 10000ms)
+    pub write_timeout_ms: Option<u32>,
+}
+
+/// Open connection to the adapter. Shared by every clone of a [Nag52Diag], so a reconnect
+/// through any clone closes the old endpoint and is picked up by all of them
+#[derive(Debug)]
+struct NagConnection {
+    endpoint: Option<AdapterHw>,
+    server: Option<Arc<DynamicDiagSession>>,
+    logger: NagAppLogger,
+    /// Bumped on every successful reconnect
+    generation: u32,
 }
 
 #[derive(Debug, Clone)]
 pub struct Nag52Diag {
     info: HardwareInfo,
-    endpoint: Option<AdapterHw>,
     endpoint_type: AdapterType,
-    server: Option<Arc<DynamicDiagSession>>,
-    logger: NagAppLogger,
+    overrides: ConnectionOverrides,
+    conn: Arc<RwLock<NagConnection>>,
     server_mutex: Arc<Mutex<()>>
 }
 
@@ -255,8 +419,21 @@ unsafe impl Sync for Nag52Diag {}
 unsafe impl Send for Nag52Diag {}
 
 impl Nag52Diag {
-    pub fn new(mut hw: AdapterHw) -> DiagServerResult<Self> {
+    pub fn new(hw: AdapterHw) -> DiagServerResult<Self> {
+        Self::new_with_overrides(hw, ConnectionOverrides::default())
+    }
 
+    pub fn new_with_overrides(hw: AdapterHw, overrides: ConnectionOverrides) -> DiagServerResult<Self> {
+        Ok(Self {
+            info: hw.get_hw_info(),
+            endpoint_type: hw.get_type(),
+            overrides,
+            conn: Arc::new(RwLock::new(Self::open_connection(hw, overrides)?)),
+            server_mutex: Arc::new(Mutex::new(()))
+        })
+    }
+
+    fn open_connection(mut hw: AdapterHw, overrides: ConnectionOverrides) -> DiagServerResult<NagConnection> {
         let mut channel_cfg = IsoTPSettings {
             block_size: 0,
             st_min: 0,
@@ -272,22 +449,29 @@ impl Nag52Diag {
             channel_cfg.st_min = 0x20;
         }
 
+        if let Some(bs) = overrides.block_size {
+            channel_cfg.block_size = bs;
+        }
+        if let Some(st_min) = overrides.st_min {
+            channel_cfg.st_min = st_min;
+        }
+
         let basic_opts = DiagServerBasicOptions {
             send_id: 0x07E1,
             recv_id: 0x07E9,
             timeout_cfg: TimeoutConfig {
-                read_timeout_ms: 10000,
-                write_timeout_ms: 10000,
+                read_timeout_ms: overrides.read_timeout_ms.unwrap_or(10000) as _,
+                write_timeout_ms: overrides.write_timeout_ms.unwrap_or(10000) as _,
             },
         };
 
         let adv_opts = DiagServerAdvancedOptions {
             global_tp_id: 0,
-            tester_present_interval_ms: 2000,
-            tester_present_require_response: true,
+            tester_present_interval_ms: overrides.tester_present_interval_ms.unwrap_or(2000) as _,
+            tester_present_require_response: overrides.tester_present_require_response.unwrap_or(true),
             global_session_control: false,
             tp_ext_id: None,
-            command_cooldown_ms: 0,
+            command_cooldown_ms: overrides.command_cooldown_ms.unwrap_or(0) as _,
         };
 
         let mut protocol = Kwp2000Protocol::default();
@@ -308,57 +492,111 @@ impl Nag52Diag {
             inner_logger
         )?;
 
-        Ok(Self {
-            info: hw.get_hw_info(),
-            endpoint_type: hw.get_type(),
+        Ok(NagConnection {
             endpoint: Some(hw),
             server: Some(Arc::new(kwp)),
             logger,
-            server_mutex: Arc::new(Mutex::new(()))
+            generation: 0,
         })
     }
 
-    pub fn try_reconnect(&mut self) -> DiagServerResult<()> {
-        {
-            let _ = self.server.take();
-            let _ = self.endpoint.take();
-        }
-        // Now try to reconnect
-
-        println!("Trying to find {}", self.info.name);
+    /// Closes the connection and opens it again. Every clone of this handle switches to the new connection
+    pub fn try_reconnect(&self) -> DiagServerResult<()> {
+        let mut conn = self.conn.write().unwrap();
+        // The adapter can only be opened once, so the old connection has to be closed first
+        let _ = conn.server.take();
+        let _ = conn.endpoint.take();
+        log::info!("Reconnecting to {}", self.info.name);
         let dev = AdapterHw::try_connect(&self.info, self.endpoint_type).map_err(|e| DiagError::from(Arc::new(e)))?;
-        *self = Self::new(dev)?;
+        let generation = conn.generation.wrapping_add(1);
+        *conn = Self::open_connection(dev, self.overrides)?;
+        conn.generation = generation;
+        log::info!("Reconnected to {}", self.info.name);
         Ok(())
     }
 
+    /// Changes after every successful reconnect, so users of this handle can tell the
+    /// TCU they queried before might have changed (E.g. a different TCU was plugged in)
+    pub fn connection_generation(&self) -> u32 {
+        self.conn.read().unwrap().generation
+    }
+
+    /// Keeps trying to reconnect until it succeeds or `cancel` is set, waiting a little longer after
+    /// each failed attempt (Up to [RECONNECT_MAX_DELAY_MS]). Returns false if cancelled, in which case
+    /// the connection is left closed, just like after a failed [Self::try_reconnect]
+    pub fn reconnect_with_backoff(&self, cancel: &AtomicBool) -> bool {
+        let mut delay_ms = RECONNECT_MIN_DELAY_MS;
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                log::info!("Reconnect to {} cancelled", self.info.name);
+                return false;
+            }
+            match self.try_reconnect() {
+                Ok(_) => return true,
+                Err(e) => log::warn!("Reconnect failed: {e}. Retrying in {delay_ms}ms"),
+            }
+            let wait_start = Instant::now();
+            while wait_start.elapsed().as_millis() < delay_ms as u128 && !cancel.load(Ordering::Relaxed) {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+            delay_ms = (delay_ms * 2).min(RECONNECT_MAX_DELAY_MS);
+        }
+    }
+
     pub fn with_kwp<F, X>(&self, mut kwp_fn: F) -> DiagServerResult<X>
     where
         F: FnMut(&DynamicDiagSession) -> DiagServerResult<X>,
     {
-        if self.server_mutex.lock().is_ok() {
-            match self.server.borrow() {
+        let res = if self.server_mutex.lock().is_ok() {
+            // Not held whilst the request runs, so a reconnect cannot block on a slow request
+            let server = self.conn.read().unwrap().server.clone();
+            match server {
                 None => Err(DiagError::from(Arc::new(HardwareError::DeviceNotOpen))),
-                Some(s) => kwp_fn(&s),
+                Some(s) => kwp_fn(&s).map_err(explain_nrc),
             }
         } else {
             Err(DiagError::ServerNotRunning)
+        };
+        if let Err(e) = &res {
+            log::warn!("KWP request failed: {e}");
         }
+        res
+    }
+
+    /// Returns false if the adapter has been unplugged or has stopped responding
+    pub fn is_connected(&self) -> bool {
+        self.conn.read().unwrap().endpoint.as_ref().map(|x| x.is_connected()).unwrap_or(false)
     }
 
     pub fn get_data_rate(&self) -> Option<(u32, u32)> {
-        self.endpoint.as_ref().map(|x| x.get_data_rate()).unwrap_or_else(|| None)
+        self.conn.read().unwrap().endpoint.as_ref().map(|x| x.get_data_rate()).unwrap_or_else(|| None)
     }
 
     pub fn read_log_msg(&self) -> Option<EspLogMessage> {
-        self.endpoint.as_ref().map(|x| x.read_log_msg()).flatten()
+        self.conn.read().unwrap().endpoint.as_ref().map(|x| x.read_log_msg()).flatten()
+    }
+
+    pub fn get_adapter_type(&self) -> AdapterType {
+        self.endpoint_type
+    }
+
+    /// Description of the adapter in use. Just the device name whilst reconnecting
+    pub fn get_device_desc(&self) -> String {
+        self.conn.read().unwrap().endpoint.as_ref().map(|x| x.get_device_desc()).unwrap_or_else(|| self.info.name.clone())
     }
 
     pub fn has_logger(&self) -> bool {
         self.endpoint_type == AdapterType::USB
     }
 
+    /// Diagnostic payload throughput (Tx, Rx) in bytes/sec since the last call.
+    /// Unlike [Self::get_data_rate] this works with every adapter, and excludes framing overhead
+    pub fn get_payload_rate(&self) -> (u32, u32) {
+        self.conn.read().unwrap().logger.take_payload_rate()
+    }
+
     pub fn get_server_event(&self) -> Option<ServerEvent> {
-        self.logger.recv.try_recv().ok()
+        self.conn.read().unwrap().logger.recv.try_recv().ok()
     }
 
 }
@@ -376,7 +614,7 @@ pub mod test_diag {
     pub fn test_kwp_reconnect() {
         let scanner = Nag52UsbScanner::new();
         let dev = scanner.open_device_by_name("/dev/ttyUSB0").unwrap();
-        let mut kwp = match Nag52Diag::new(AdapterHw::Usb(dev)) {
+        let kwp = match Nag52Diag::new(AdapterHw::Usb(dev)) {
             Ok(kwp) => kwp,
             Err(e) => {
                 eprintln!("Error starting KWP {e}");
@@ -0,0 +1,621 @@
+//! Records the TCU exposes through ReadDataByLocalIdentifier
+//! Based on diag_data.h in TCM source code
+//!
+//! Only the raw data and the values derived from it live here, so records can be read
+//! without any UI. Rendering them is left to the config app.
+use std::fmt;
+
+use ecu_diagnostics::dynamic_diag::DynamicDiagSession;
+use ecu_diagnostics::{DiagError, DiagServerResult};
+use packed_struct::PackedStructSlice;
+use packed_struct::prelude::{PackedStruct, PrimitiveEnum_u8};
+use serde::Serialize;
+
+use super::Nag52Diag;
+
+#[repr(u8)]
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
+pub enum RecordIdents {
+    GearboxSensors = 0x20,
+    SolenoidStatus = 0x21,
+    CanDataDump = 0x22,
+    SysUsage = 0x23,
+    PressureStatus = 0x25,
+    SSData = 0x27,
+    ClutchSpeeds = 0x30,
+    ClutchVelocities = 0x31,
+}
+
+
+fn read_struct<T>(c: &[u8]) -> DiagServerResult<T>
+where
+    T: PackedStruct,
+{
+    T::unpack_from_slice(&c).map_err(|e| DiagError::InvalidResponseLength)
+}
+
+impl RecordIdents {
+    pub const ALL: [RecordIdents; 8] = [
+        Self::GearboxSensors,
+        Self::SolenoidStatus,
+        Self::CanDataDump,
+        Self::SysUsage,
+        Self::PressureStatus,
+        Self::SSData,
+        Self::ClutchSpeeds,
+        Self::ClutchVelocities,
+    ];
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| *r as u8 == id)
+    }
+
+    /// Size of the record's struct in bytes, which the TCU response must match
+    pub fn expected_len(&self) -> usize {
+        fn len<T: PackedStructSlice>() -> usize {
+            T::packed_bytes_size(None).unwrap_or_default()
+        }
+        match self {
+            Self::GearboxSensors => len::<DataGearboxSensors>(),
+            Self::SolenoidStatus => len::<DataSolenoids>(),
+            Self::CanDataDump => len::<DataCanDump>(),
+            Self::SysUsage => len::<DataSysUsage>(),
+            Self::PressureStatus => len::<DataPressures>(),
+            Self::SSData => len::<DataShiftManager>(),
+            Self::ClutchSpeeds => len::<DataClutchSpeeds>(),
+            Self::ClutchVelocities => len::<DataShiftClutchVelocity>(),
+        }
+    }
+
+    pub fn query_ecu_raw(&self, server: &DynamicDiagSession) -> DiagServerResult<Vec<u8>> {
+        server.kwp_read_custom_local_identifier(*self as u8)
+    }
+
+    pub fn query_ecu(
+        &self,
+        server: &DynamicDiagSession,
+    ) -> DiagServerResult<LocalRecordData> {
+        let resp = self.query_ecu_raw(server)?;
+        self.parse(&resp)
+    }
+
+    pub fn parse(&self, resp: &[u8]) -> DiagServerResult<LocalRecordData> {
+        match self {
+            Self::GearboxSensors => Ok(LocalRecordData::Sensors(read_struct(resp)?)),
+            Self::SolenoidStatus => Ok(LocalRecordData::Solenoids(read_struct(resp)?)),
+            Self::CanDataDump => Ok(LocalRecordData::Canbus(read_struct(resp)?)),
+            Self::SysUsage => Ok(LocalRecordData::SysUsage(read_struct(resp)?)),
+            Self::PressureStatus => Ok(LocalRecordData::Pressures(read_struct(resp)?)),
+            Self::SSData => Ok(LocalRecordData::ShiftMonitorLive(read_struct(resp)?)),
+            Self::ClutchSpeeds => Ok(LocalRecordData::ClutchSpeeds(read_struct(resp)?)),
+            Self::ClutchVelocities => Ok(LocalRecordData::ClutchVelocities(read_struct(resp)?))
+        }
+    }
+}
+
+/// Response of a record that could not be parsed, kept so the user can see what the TCU sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadRecordResponse {
+    pub record: RecordIdents,
+    pub raw: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum LocalRecordData {
+    Sensors(DataGearboxSensors),
+    Solenoids(DataSolenoids),
+    Canbus(DataCanDump),
+    SysUsage(DataSysUsage),
+    Pressures(DataPressures),
+    ShiftMonitorLive(DataShiftManager),
+    ClutchSpeeds(DataClutchSpeeds),
+    ClutchVelocities(DataShiftClutchVelocity),
+}
+
+impl LocalRecordData {
+    /// Raw field names and values of the record, in alphabetical order of the field name.
+    /// Enums are given by their variant name
+    pub fn fields(&self) -> Vec<(String, String)> {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(map)) => map
+                .into_iter()
+                .map(|(k, v)| match v {
+                    serde_json::Value::String(s) => (k, s),
+                    v => (k, v.to_string()),
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Nag52Diag {
+    /// Reads and parses a single record
+    pub fn read_record(&self, record: RecordIdents) -> DiagServerResult<LocalRecordData> {
+        self.with_kwp(|server| record.query_ecu(server))
+    }
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct, Serialize)]
+#[packed_struct(endian="lsb")]
+pub struct DataPressures {
+    pub spc_pwm: u16,
+    pub mpc_pwm: u16,
+    pub tcc_pwm: u16,
+    pub ss_flag: u8,
+    pub spc_sol_pressure: u16,
+    pub mpc_sol_pressure: u16,
+    pub spc_clutch_pressure: u16,
+    pub mpc_clutch_pressure: u16,
+    pub tcc_clutch_pressure: u16,
+    pub line_pressure: u16
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct, Serialize)]
+#[packed_struct(endian="lsb")]
+pub struct DataGearboxSensors {
+    pub n2_rpm: u16,
+    pub n3_rpm: u16,
+    pub calculated_rpm: u16,
+    pub calc_ratio: u16,
+    pub v_batt: u16,
+    pub atf_temp_c: u32,
+    pub parking_lock: u8,
+    pub output_rpm: u16
+}
+
+/// Values the TCU reports as the type's MAX value when the signal is not available
+pub trait Sentinel: Copy + PartialEq {
+    const SNA: Self;
+
+    /// Returns None if the value is the 'signal not available' sentinel
+    fn available(self) -> Option<Self> {
+        if self == Self::SNA { None } else { Some(self) }
+    }
+}
+
+impl Sentinel for u8 { const SNA: Self = u8::MAX; }
+impl Sentinel for u16 { const SNA: Self = u16::MAX; }
+impl Sentinel for u32 { const SNA: Self = u32::MAX; }
+impl Sentinel for i16 { const SNA: Self = i16::MAX; }
+
+/// Forward gear ratios (1 to 5) of the small 722.6 (W5A330/W5A400)
+pub const SMALL_NAG_RATIOS: [f32; 5] = [3.93, 2.41, 1.49, 1.00, 0.83];
+/// Forward gear ratios (1 to 5) of the large 722.6 (W5A580)
+pub const LARGE_NAG_RATIOS: [f32; 5] = [3.59, 2.19, 1.41, 1.00, 0.83];
+
+/// Forward gear ratios of the small and large 722.6
+const FORWARD_RATIOS: [[f32; 5]; 2] = [SMALL_NAG_RATIOS, LARGE_NAG_RATIOS];
+/// How far the calculated ratio may be from a gear's ratio and still be in that gear
+const GEAR_RATIO_TOLERANCE: f32 = 0.05;
+/// Below this battery voltage, the TCU may brown out part way through writing its flash
+pub const MIN_WRITE_VBATT_MV: u16 = 12000;
+
+impl DataGearboxSensors {
+    /// Forward gear (1-5) matching the calculated ratio, for either the small or large 722.6.
+    /// None whilst shifting, or if the ratio does not match any gear
+    pub fn gear(&self) -> Option<u8> {
+        let ratio = self.calc_ratio.available()? as f32 / 100.0;
+        FORWARD_RATIOS.iter().find_map(|ratios| {
+            ratios.iter()
+                .position(|r| (ratio - r).abs() / r < GEAR_RATIO_TOLERANCE)
+                .map(|idx| idx as u8 + 1)
+        })
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct, Serialize)]
+#[packed_struct(endian="lsb")]
+pub struct DataSolenoids {
+    pub spc_pwm: u16,
+    pub mpc_pwm: u16,
+    pub tcc_pwm: u16,
+    pub y3_pwm: u16,
+    pub y4_pwm: u16,
+    pub y5_pwm: u16,
+    pub spc_current: u16,
+    pub mpc_current: u16,
+    pub tcc_current: u16,
+    pub targ_spc_current: u16,
+    pub targ_mpc_current: u16,
+    pub adjustment_spc: u16,
+    pub adjustment_mpc: u16,
+    pub y3_current: u16,
+    pub y4_current: u16,
+    pub y5_current: u16,
+}
+
+impl DataSolenoids {
+    /// MPC PWM trim in % (0 = no adjustment)
+    pub fn mpc_trim_pct(&self) -> f32 {
+        (self.adjustment_mpc as f32 / 10.0) - 100.0
+    }
+
+    /// SPC PWM trim in % (0 = no adjustment)
+    pub fn spc_trim_pct(&self) -> f32 {
+        (self.adjustment_spc as f32 / 10.0) - 100.0
+    }
+
+    pub fn total_current_ma(&self) -> u32 {
+        self.y5_current as u32
+            + self.y4_current as u32
+            + self.y3_current as u32
+            + self.mpc_current as u32
+            + self.spc_current as u32
+            + self.tcc_current as u32
+    }
+
+    /// Name, PWM and current of every solenoid
+    fn channels(&self) -> [(&'static str, u16, u16); 6] {
+        [
+            ("MPC", self.mpc_pwm, self.mpc_current),
+            ("SPC", self.spc_pwm, self.spc_current),
+            ("TCC", self.tcc_pwm, self.tcc_current),
+            ("Y3", self.y3_pwm, self.y3_current),
+            ("Y4", self.y4_pwm, self.y4_current),
+            ("Y5", self.y5_pwm, self.y5_current),
+        ]
+    }
+
+    /// Current readings that do not fit how the solenoids are being driven, which usually
+    /// means a short between circuits or a connector fault. These are hints only, a single
+    /// record can be caught mid-change
+    pub fn fault_hints(&self) -> Vec<SolenoidFaultHint> {
+        let ch = self.channels();
+        let mut hints = Vec::new();
+        for (name, pwm, current_ma) in ch {
+            if pwm <= FAULT_OFF_PWM && current_ma >= FAULT_MIN_CURRENT_MA {
+                hints.push(SolenoidFaultHint::CurrentWhileOff { name, current_ma });
+            }
+        }
+        for (i, (a, a_pwm, a_ma)) in ch.iter().enumerate() {
+            for (b, b_pwm, b_ma) in &ch[i + 1..] {
+                if *a_ma >= FAULT_MIN_CURRENT_MA
+                    && a_ma.abs_diff(*b_ma) <= FAULT_TRACKING_MA
+                    && a_pwm.abs_diff(*b_pwm) >= FAULT_PWM_DIFFERENCE
+                {
+                    hints.push(SolenoidFaultHint::Tracking { a, b, current_ma: *a_ma });
+                }
+            }
+        }
+        hints
+    }
+}
+
+/// PWM (Out of 4096) at or below which a solenoid should draw next to no current
+const FAULT_OFF_PWM: u16 = 80;
+/// Current above which a solenoid is clearly conducting
+const FAULT_MIN_CURRENT_MA: u16 = 300;
+/// Currents within this of each other count as the same
+const FAULT_TRACKING_MA: u16 = 20;
+/// PWM difference (Out of 4096) at which two solenoids should clearly draw different currents
+const FAULT_PWM_DIFFERENCE: u16 = 400;
+
+/// Suspicious pattern in a solenoid record, see [DataSolenoids::fault_hints]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolenoidFaultHint {
+    /// Solenoid draws current whilst it is (Almost) off
+    CurrentWhileOff { name: &'static str, current_ma: u16 },
+    /// Two solenoids draw the same current, despite being driven differently
+    Tracking { a: &'static str, b: &'static str, current_ma: u16 },
+}
+
+impl fmt::Display for SolenoidFaultHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CurrentWhileOff { name, current_ma } => write!(
+                f,
+                "{name} draws {current_ma} mA whilst switched off. Check for a short to another circuit or to +12V"
+            ),
+            Self::Tracking { a, b, current_ma } => write!(
+                f,
+                "{a} and {b} both draw {current_ma} mA, despite different PWM. Check for a short between the two circuits"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PrimitiveEnum_u8, Serialize)]
+pub enum TorqueReqCtrlType {
+    None = 0,
+    NormalSpeed = 1,
+    FastAsPossible = 2
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PrimitiveEnum_u8, Serialize)]
+pub enum TorqueReqBounds {
+    LessThan = 0,
+    MoreThan = 1,
+    Exact = 2
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PrimitiveEnum_u8, Serialize)]
+pub enum PaddlePosition {
+    None = 0,
+    Plus = 1,
+    Minus = 2,
+    PlusAndMinus = 3,
+    SNV = 0xFF,
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PrimitiveEnum_u8, Serialize)]
+pub enum ShifterPosition {
+    Park = 0,
+    ParkReverse = 1,
+    Reverse = 2,
+    ReverseNeutral = 3,
+    Neutral = 4,
+    NeutralDrive = 5,
+    Drive = 6,
+    Plus = 7,
+    Minus = 8,
+    Four = 9,
+    Three = 10,
+    Two = 11,
+    One = 12,
+    SNV = 0xFF,
+}
+
+impl ShifterPosition {
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            ShifterPosition::Park => "P",
+            ShifterPosition::ParkReverse => "P-R",
+            ShifterPosition::Reverse => "R",
+            ShifterPosition::ReverseNeutral => "R-N",
+            ShifterPosition::Neutral => "N",
+            ShifterPosition::NeutralDrive => "N-D",
+            ShifterPosition::Drive => "D",
+            ShifterPosition::Plus => "+",
+            ShifterPosition::Minus => "-",
+            ShifterPosition::Four => "4",
+            ShifterPosition::Three => "3",
+            ShifterPosition::Two => "2",
+            ShifterPosition::One => "1",
+            ShifterPosition::SNV => "SNV",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct, Serialize)]
+#[packed_struct(endian="lsb")]
+pub struct DataCanDump {
+    pub pedal_position: u8,
+    pub min_torque_ms: u16,
+    pub max_torque_ms: u16,
+    pub static_torque: u16,
+    pub driver_torque: u16,
+    pub left_rear_rpm: u16,
+    pub right_rear_rpm: u16,
+    pub shift_profile_pressed: u8,
+    /// Raw value. Which positions are valid depends on the CAN matrix, so it is only
+    /// decoded where the matrix is known (The diagnostics table)
+    pub selector_position: u8,
+    #[packed_field(size_bytes="1", ty="enum")]
+    pub paddle_position: PaddlePosition,
+    pub engine_rpm: u16,
+    pub fuel_flow: u16,
+    pub egs_req_torque: u16,
+    #[packed_field(size_bytes="1", ty="enum")]
+    pub egs_torque_req_ctrl_type: TorqueReqCtrlType,
+    #[packed_field(size_bytes="1", ty="enum")]
+    pub egs_torque_req_bounds: TorqueReqBounds,
+    pub engine_iat_temp: i16,
+    pub engine_oil_temp: i16,
+    pub engine_coolant_temp: i16
+}
+
+/// Below this speed, fuel economy is meaningless
+const MIN_FUEL_ECONOMY_SPEED_KMH: f32 = 5.0;
+/// Maximum fraction the rear wheel speeds may differ by before a sensor fault is suspected
+const MAX_WHEEL_SPEED_MISMATCH: f32 = 0.15;
+/// Wheel speed (RPM) below which a left/right mismatch is not checked
+const MIN_MISMATCH_WHEEL_RPM: f32 = 50.0;
+
+impl DataCanDump {
+    /// Fuel flow in litres per hour
+    pub fn fuel_flow_lph(&self) -> f32 {
+        // ul/s -> L/h
+        self.fuel_flow as f32 * 3600.0 / 1_000_000.0
+    }
+
+    /// Vehicle speed from the average rear wheel speed, in km/h
+    pub fn vehicle_speed_kmh(&self, wheel_circumference_mm: u16) -> Option<f32> {
+        let l = self.left_rear_rpm.available()? as f32 / 2.0;
+        let r = self.right_rear_rpm.available()? as f32 / 2.0;
+        // RPM * mm -> km/h
+        Some((l + r) / 2.0 * wheel_circumference_mm as f32 * 60.0 / 1_000_000.0)
+    }
+
+    /// True if the rear wheels are moving at noticeably different speeds (Above what
+    /// cornering would cause), which points to a faulty wheel speed sensor
+    pub fn wheel_speed_mismatch(&self) -> bool {
+        match (self.left_rear_rpm.available(), self.right_rear_rpm.available()) {
+            (Some(l), Some(r)) => {
+                let (l, r) = (l as f32 / 2.0, r as f32 / 2.0);
+                l.max(r) > MIN_MISMATCH_WHEEL_RPM && (l - r).abs() / l.max(r) > MAX_WHEEL_SPEED_MISMATCH
+            },
+            _ => false,
+        }
+    }
+
+    /// Instantaneous fuel economy in L/100km, if the vehicle is moving
+    pub fn fuel_economy_l100km(&self, wheel_circumference_mm: u16) -> Option<f32> {
+        let speed = self.vehicle_speed_kmh(wheel_circumference_mm)?;
+        if speed < MIN_FUEL_ECONOMY_SPEED_KMH {
+            None
+        } else {
+            Some(self.fuel_flow_lph() / speed * 100.0)
+        }
+    }
+
+    /// Signals that need scaling to be read, in physical units (Name, value). NaN if not available
+    pub fn decoded_signals(&self) -> Vec<(&'static str, f32)> {
+        let trq = |raw: u16| raw.available().map(|v| v as f32 / 4.0 - 500.0).unwrap_or(f32::NAN);
+        vec![
+            ("min_torque_nm", trq(self.min_torque_ms)),
+            ("max_torque_nm", trq(self.max_torque_ms)),
+            ("static_torque_nm", trq(self.static_torque)),
+            ("driver_torque_nm", trq(self.driver_torque)),
+            ("egs_req_torque_nm", trq(self.egs_req_torque)),
+            ("fuel_flow_lph", self.fuel_flow_lph()),
+        ]
+    }
+
+    pub fn vehicle_state(&self) -> VehicleState {
+        VehicleState::new(self.engine_rpm, self.left_rear_rpm, self.right_rear_rpm)
+    }
+}
+
+/// Wheel speed (RPM) above which the car counts as moving
+const MOVING_WHEEL_RPM: f32 = 5.0;
+
+/// Whether the engine is running and the car is moving, for safety interlocks.
+/// Each is None if the TCU does not receive the signal (Such as on a bench)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct VehicleState {
+    pub engine_running: Option<bool>,
+    pub moving: Option<bool>,
+}
+
+impl VehicleState {
+    fn new(engine_rpm: u16, left_rear_rpm: u16, right_rear_rpm: u16) -> Self {
+        // Any engine speed counts, even whilst cranking
+        let engine_running = engine_rpm.available().map(|rpm| rpm > 0);
+        let moving = match (left_rear_rpm.available(), right_rear_rpm.available()) {
+            (None, None) => None,
+            (l, r) => Some(l.max(r).unwrap_or(0) as f32 / 2.0 > MOVING_WHEEL_RPM),
+        };
+        Self { engine_running, moving }
+    }
+
+    /// Why something that is only safe with the car stationary and the engine off cannot be done
+    /// right now. Signals the TCU does not receive do not block, as there is no engine ECU on a bench
+    pub fn interlock_reason(&self) -> Option<&'static str> {
+        if self.moving == Some(true) {
+            Some("The car is moving")
+        } else if self.engine_running == Some(true) {
+            Some("The engine is running")
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct, Serialize)]
+#[packed_struct(endian="lsb")]
+pub struct DataSysUsage {
+    pub core1_usage: u16,
+    pub core2_usage: u16,
+    pub free_ram: u32,
+    pub total_ram: u32,
+    pub free_psram: u32,
+    pub total_psram: u32,
+    pub num_tasks: u32,
+}
+
+pub fn shift_idx_name(idx: u8) -> &'static str {
+    match idx {
+        0 => "None",
+        1 => "1 -> 2",
+        2 => "2 -> 3",
+        3 => "3 -> 4",
+        4 => "4 -> 5",
+        5 => "5 -> 4",
+        6 => "4 -> 3",
+        7 => "3 -> 2",
+        8 => "2 -> 1",
+        _ => "UNKNOWN",
+    }
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct, Serialize)]
+#[packed_struct(endian="lsb")]
+pub struct DataShiftManager {
+    pub spc_pressure_mbar: u16,
+    pub mpc_pressure_mbar: u16,
+    pub tcc_pressure_mbar: u16,
+    pub shift_solenoid_pos: u8,
+    pub input_rpm: u16,
+    pub engine_rpm: u16,
+    pub output_rpm: u16,
+    pub engine_torque: u16,
+    pub req_engine_torque: u16,
+    pub atf_temp: u8,
+    pub shift_idx: u8,
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct, Serialize)]
+#[packed_struct(endian="lsb")]
+pub struct DataClutchSpeeds {
+    pub k1: i16,
+    pub k2: i16,
+    pub k3: i16,
+    pub b1: i16,
+    pub b2: i16,
+    pub b3: i16,
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct, Serialize)]
+#[packed_struct(endian="lsb")]
+pub struct DataShiftClutchVelocity {
+    pub on_vel: i16,
+    pub off_vel: i16
+}
+
+#[cfg(test)]
+pub mod record_tests {
+    use super::{DataGearboxSensors, DataSolenoids, Sentinel, SolenoidFaultHint, VehicleState};
+
+    fn sensors(calculated_rpm: u16, calc_ratio: u16) -> DataGearboxSensors {
+        DataGearboxSensors {
+            n2_rpm: 0,
+            n3_rpm: 0,
+            calculated_rpm,
+            calc_ratio,
+            v_batt: 12000,
+            atf_temp_c: 40,
+            parking_lock: 0,
+            output_rpm: 0,
+        }
+    }
+
+    #[test]
+    pub fn test_ratio_guard() {
+        // Ratio is valid even if the input RPM is not
+        let no_rpm = sensors(u16::MAX, 241);
+        assert_eq!(no_rpm.calc_ratio.available(), Some(241));
+        assert_eq!(no_rpm.gear(), Some(2));
+        // Invalid ratio with a valid input RPM must not turn into a gear
+        let no_ratio = sensors(1500, u16::MAX);
+        assert_eq!(no_ratio.calc_ratio.available(), None);
+        assert_eq!(no_ratio.gear(), None);
+    }
+
+    #[test]
+    pub fn test_solenoid_fault_hints() {
+        let healthy = DataSolenoids { mpc_pwm: 1200, mpc_current: 700, spc_pwm: 2000, spc_current: 1100, ..Default::default() };
+        assert!(healthy.fault_hints().is_empty());
+        let shorted = DataSolenoids { mpc_pwm: 2000, mpc_current: 1100, spc_pwm: 0, spc_current: 1100, ..Default::default() };
+        assert_eq!(
+            shorted.fault_hints(),
+            vec![
+                SolenoidFaultHint::CurrentWhileOff { name: "SPC", current_ma: 1100 },
+                SolenoidFaultHint::Tracking { a: "MPC", b: "SPC", current_ma: 1100 },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_vehicle_state() {
+        let bench = VehicleState::new(u16::MAX, u16::MAX, u16::MAX);
+        assert_eq!((bench.engine_running, bench.moving), (None, None));
+        assert_eq!(bench.interlock_reason(), None);
+        // One wheel sensor missing still tells if the car is moving
+        let driving = VehicleState::new(800, 600, u16::MAX);
+        assert_eq!((driving.engine_running, driving.moving), (Some(true), Some(true)));
+        assert_eq!(driving.interlock_reason(), Some("The car is moving"));
+        assert_eq!(VehicleState::new(700, 0, 0).interlock_reason(), Some("The engine is running"));
+    }
+}
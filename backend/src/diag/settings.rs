@@ -4,12 +4,25 @@ use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
+use super::settings_layout::{settings_layout, FieldKind};
+
 pub type UnpackResult<T> = std::result::Result<T, UnPackError>;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum UnPackError {
     WrongId { wanted: u8, real: u8 },
+    /// The settings block is a different size to what this app expects, so the
+    /// firmware and config app use different settings revisions
     InvalidLen { wanted: usize, len: usize },
+    /// A bool or enum field contains a value this app does not know about
+    InvalidValue { field: String, value: u8 },
+}
+
+impl UnPackError {
+    /// True if the error is most likely caused by the firmware and config app being out of sync
+    pub fn is_revision_mismatch(&self) -> bool {
+        matches!(self, UnPackError::InvalidLen { .. } | UnPackError::InvalidValue { .. })
+    }
 }
 
 impl ToString for UnPackError {
@@ -17,13 +30,14 @@ impl ToString for UnPackError {
         match self {
             UnPackError::WrongId { wanted, real } => format!("Wrong setting ID. Wanted 0x{:02X?}, got 0x{:02X?}", wanted, real),
             UnPackError::InvalidLen { wanted, len } => format!("Wrong response length. Wanted {} bytes, got {} bytes. Maybe a config app/firmware mismatch?", wanted, len),
+            UnPackError::InvalidValue { field, value } => format!("Invalid value 0x{:02X} for '{}'. Maybe a config app/firmware mismatch?", value, field),
         }
     }
 }
 
 pub fn unpack_settings<T>(settings_id: u8, raw: &[u8]) -> UnpackResult<T>
 where
-    T: Copy,
+    T: TcuSettings,
 {
     if settings_id != raw[0] {
         Err(UnPackError::WrongId {
@@ -36,6 +50,19 @@ where
             len: raw.len() - 1,
         })
     } else {
+        // Bools and enums with out of range values cannot be cast to T
+        for field in settings_layout(&T::default()).unwrap_or_default() {
+            let value = raw[1 + field.offset];
+            let valid = match field.kind {
+                FieldKind::Bool => value <= 1,
+                // Setting enums are numbered from 0
+                FieldKind::Enum => T::get_enum_entries(&field.path).map(|e| (value as usize) < e.len()).unwrap_or(false),
+                _ => true,
+            };
+            if !valid {
+                return Err(UnPackError::InvalidValue { field: field.path, value });
+            }
+        }
         let ptr: *const T = raw[1..].as_ptr() as *const T;
         Ok(unsafe { *ptr })
     }
@@ -66,7 +93,7 @@ where T: Serialize + DeserializeOwned {
 }
 
 
-pub trait TcuSettings: Copy + Clone + Serialize + DeserializeOwned
+pub trait TcuSettings: Copy + Clone + Default + Serialize + DeserializeOwned
 where
 {
     fn wiki_url() -> Option<&'static str>;
@@ -79,6 +106,11 @@ where
     fn get_enum_entries(key: &str) -> Option<Vec<String>> {
         None
     }
+    /// Human readable label for an enum entry returned by [TcuSettings::get_enum_entries].
+    /// The raw entry is shown if there is no label
+    fn get_enum_label(key: &str, entry: &str) -> Option<&'static str> {
+        None
+    }
 }
 
 #[derive(Default, Debug, Copy, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
@@ -407,7 +439,20 @@ impl TcuSettings for EtsSettings {
         match key {
             "ewm_selector_type" => Some(enum_to_str_list(EwmSelectorType::iter().collect())),
             "profile_idx_top" => Some(enum_to_str_list(AutoProfile::iter().collect())),
-            "profile_idx_buttom" => Some(enum_to_str_list(AutoProfile::iter().collect())),
+            "profile_idx_bottom" => Some(enum_to_str_list(AutoProfile::iter().collect())),
+            _ => None
+        }
+    }
+
+    fn get_enum_label(key: &str, entry: &str) -> Option<&'static str> {
+        match (key, entry) {
+            ("ewm_selector_type", "None") => Some("None (No profile selector)"),
+            ("ewm_selector_type", "Button") => Some("Button (Single profile toggle button)"),
+            ("ewm_selector_type", "Switch") => Some("Switch (Two position S/C switch)"),
+            ("profile_idx_top" | "profile_idx_bottom", "Sport") => Some("Sport (S)"),
+            ("profile_idx_top" | "profile_idx_bottom", "Comfort") => Some("Comfort (C)"),
+            ("profile_idx_top" | "profile_idx_bottom", "Agility") => Some("Agility (A)"),
+            ("profile_idx_top" | "profile_idx_bottom", "Winter") => Some("Winter (W)"),
             _ => None
         }
     }
@@ -0,0 +1,162 @@
+//! Works out where each field of a settings struct lives in its packed SCN block.
+//!
+//! Settings structs are `#[repr(C, packed)]`, so fields are laid out back to back in
+//! declaration order, which is also the order serde visits them in. Walking the struct
+//! with a serializer that only counts primitive sizes therefore gives the byte layout
+//! that [super::settings::pack_settings] produces.
+use std::fmt::Display;
+
+use serde::{
+    ser::{self, Impossible},
+    Serialize, Serializer,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Bool,
+    Integer,
+    Float,
+    /// `#[repr(u8)]` enum
+    Enum,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldLayout {
+    /// Field path, with nested struct fields separated by '.' (`pressure_multiplier_output_rpm.new_min`)
+    pub path: String,
+    /// Offset of the field within the settings struct (Excluding the SCN ID byte)
+    pub offset: usize,
+    pub size: usize,
+    pub kind: FieldKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutError(String);
+
+impl Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+impl ser::Error for LayoutError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+#[derive(Default)]
+struct LayoutBuilder {
+    offset: usize,
+    path: Vec<&'static str>,
+    fields: Vec<FieldLayout>,
+}
+
+impl LayoutBuilder {
+    fn push(&mut self, size: usize, kind: FieldKind) -> Result<(), LayoutError> {
+        self.fields.push(FieldLayout {
+            path: self.path.join("."),
+            offset: self.offset,
+            size,
+            kind,
+        });
+        self.offset += size;
+        Ok(())
+    }
+
+    fn unsupported<T>(what: &str) -> Result<T, LayoutError> {
+        Err(LayoutError(format!("{what} cannot be part of a packed settings struct")))
+    }
+}
+
+/// Returns the layout of every primitive field of `settings`, in the order they are packed
+pub fn settings_layout<T: Serialize>(settings: &T) -> Result<Vec<FieldLayout>, LayoutError> {
+    let mut builder = LayoutBuilder::default();
+    settings.serialize(&mut builder)?;
+    Ok(builder.fields)
+}
+
+impl<'a> Serializer for &'a mut LayoutBuilder {
+    type Ok = ();
+    type Error = LayoutError;
+    type SerializeSeq = Impossible<(), LayoutError>;
+    type SerializeTuple = Impossible<(), LayoutError>;
+    type SerializeTupleStruct = Impossible<(), LayoutError>;
+    type SerializeTupleVariant = Impossible<(), LayoutError>;
+    type SerializeMap = Impossible<(), LayoutError>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), LayoutError>;
+
+    fn serialize_bool(self, _v: bool) -> Result<(), LayoutError> { self.push(1, FieldKind::Bool) }
+    fn serialize_i8(self, _v: i8) -> Result<(), LayoutError> { self.push(1, FieldKind::Integer) }
+    fn serialize_i16(self, _v: i16) -> Result<(), LayoutError> { self.push(2, FieldKind::Integer) }
+    fn serialize_i32(self, _v: i32) -> Result<(), LayoutError> { self.push(4, FieldKind::Integer) }
+    fn serialize_i64(self, _v: i64) -> Result<(), LayoutError> { self.push(8, FieldKind::Integer) }
+    fn serialize_u8(self, _v: u8) -> Result<(), LayoutError> { self.push(1, FieldKind::Integer) }
+    fn serialize_u16(self, _v: u16) -> Result<(), LayoutError> { self.push(2, FieldKind::Integer) }
+    fn serialize_u32(self, _v: u32) -> Result<(), LayoutError> { self.push(4, FieldKind::Integer) }
+    fn serialize_u64(self, _v: u64) -> Result<(), LayoutError> { self.push(8, FieldKind::Integer) }
+    fn serialize_f32(self, _v: f32) -> Result<(), LayoutError> { self.push(4, FieldKind::Float) }
+    fn serialize_f64(self, _v: f64) -> Result<(), LayoutError> { self.push(8, FieldKind::Float) }
+    fn serialize_char(self, _v: char) -> Result<(), LayoutError> { self.push(4, FieldKind::Integer) }
+
+    // Enums in settings are all #[repr(u8)]
+    fn serialize_unit_variant(self, _name: &'static str, _idx: u32, _variant: &'static str) -> Result<(), LayoutError> {
+        self.push(1, FieldKind::Enum)
+    }
+
+    fn serialize_unit(self) -> Result<(), LayoutError> { Ok(()) }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), LayoutError> { Ok(()) }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<(), LayoutError> {
+        value.serialize(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, LayoutError> {
+        Ok(self)
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<(), LayoutError> { LayoutBuilder::unsupported("String") }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), LayoutError> { LayoutBuilder::unsupported("Byte array") }
+    fn serialize_none(self) -> Result<(), LayoutError> { LayoutBuilder::unsupported("Option") }
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<(), LayoutError> { LayoutBuilder::unsupported("Option") }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, _idx: u32, _variant: &'static str, _value: &T) -> Result<(), LayoutError> {
+        LayoutBuilder::unsupported("Enum with data")
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, LayoutError> { LayoutBuilder::unsupported("Sequence") }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, LayoutError> { LayoutBuilder::unsupported("Tuple") }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, LayoutError> {
+        LayoutBuilder::unsupported("Tuple struct")
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _idx: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant, LayoutError> {
+        LayoutBuilder::unsupported("Enum with data")
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, LayoutError> { LayoutBuilder::unsupported("Map") }
+
+    fn serialize_struct_variant(self, _name: &'static str, _idx: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant, LayoutError> {
+        LayoutBuilder::unsupported("Enum with data")
+    }
+}
+
+impl<'a> ser::SerializeStruct for &'a mut LayoutBuilder {
+    type Ok = ();
+    type Error = LayoutError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), LayoutError> {
+        self.path.push(key);
+        let res = value.serialize(&mut **self);
+        self.path.pop();
+        res
+    }
+
+    fn end(self) -> Result<(), LayoutError> {
+        Ok(())
+    }
+}
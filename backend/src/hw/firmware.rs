@@ -1,6 +1,7 @@
 use std::{fs::File, io::Read};
 
 use chrono::{DateTime, TimeZone, NaiveDateTime};
+use curl::easy::{Easy, List};
 use packed_struct::{prelude::PackedStruct, PackedStructSlice};
 use static_assertions::assert_eq_size;
 
@@ -64,18 +65,88 @@ impl FirmwareHeader {
         let str = format!("{} {}", self.get_date(), self.get_time().split("+").next().unwrap());
         NaiveDateTime::parse_from_str(&str, "%d %b %Y %H:%M:%S").ok()
     }
+
+    /// Names of the text fields that are not readable (Not UTF-8, or containing control characters).
+    /// An image with unreadable fields is most likely not NAG52 firmware, or is corrupt
+    pub fn invalid_fields(&self) -> Vec<&'static str> {
+        fn is_valid(raw: &[u8]) -> bool {
+            match std::str::from_utf8(raw) {
+                Ok(s) => {
+                    let s = s.trim_matches(char::from(0));
+                    !s.is_empty() && !s.chars().any(|c| c.is_control())
+                },
+                Err(_) => false,
+            }
+        }
+        [
+            ("Name", &self.project_name[..]),
+            ("Version", &self.version[..]),
+            ("Build date", &self.date[..]),
+            ("Build time", &self.time[..]),
+            ("ESP IDF version", &self.idf_ver[..]),
+        ]
+        .into_iter()
+        .filter(|(_, raw)| !is_valid(raw))
+        .map(|(name, _)| name)
+        .collect()
+    }
+
+    /// Anti-rollback counter. The bootloader refuses to boot firmware with a lower value than the running one
+    pub fn get_secure_version(&self) -> u32 {
+        self.secure_version
+    }
+
+    /// True if this firmware was built before `current`. False if either build time is unknown
+    pub fn is_downgrade_from(&self, current: &FirmwareHeader) -> bool {
+        match (self.get_build_timestamp(), current.get_build_timestamp()) {
+            (Some(new), Some(old)) => new < old,
+            _ => false,
+        }
+    }
+
+    /// True if flashing this firmware over `current` violates the anti-rollback counter
+    pub fn violates_anti_rollback(&self, current: &FirmwareHeader) -> bool {
+        self.secure_version < current.secure_version
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Firmware {
     pub raw: Vec<u8>,
     pub header: FirmwareHeader,
+    /// Where in the image the header was found
+    pub header_offset: usize,
+}
+
+impl Firmware {
+    /// Describes why the image does not look like an ESP app partition image,
+    /// despite a header having been found in it
+    pub fn layout_warning(&self) -> Option<String> {
+        if self.raw.first() != Some(&ESP_IMAGE_MAGIC) {
+            Some(format!("Image does not start with the ESP image magic (0x{:02X})", ESP_IMAGE_MAGIC))
+        } else if self.header_offset != APP_DESC_OFFSET {
+            Some(format!(
+                "Firmware header found at offset {}, a valid app image has it at offset {}",
+                self.header_offset, APP_DESC_OFFSET
+            ))
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum FirmwareLoadError {
     NotValid(String),
     IoError(std::io::Error),
+    /// Download failed, or the server did not return the file
+    Network(String),
+}
+
+impl From<curl::Error> for FirmwareLoadError {
+    fn from(e: curl::Error) -> Self {
+        Self::Network(e.to_string())
+    }
 }
 
 impl From<std::io::Error> for FirmwareLoadError {
@@ -84,32 +155,37 @@ impl From<std::io::Error> for FirmwareLoadError {
     }
 }
 
+/// How far into the image the header magic may start
+const HEADER_SEARCH_LEN: usize = 50;
+/// First byte of every ESP image
+const ESP_IMAGE_MAGIC: u8 = 0xE9;
+/// The app description follows the 24 byte image header and the 8 byte header of the first segment
+const APP_DESC_OFFSET: usize = 24 + 8;
+
 pub fn load_binary(buf: Vec<u8>) -> FirwmareLoadResult<Firmware> {
-    // Todo find a nicer way to do this!
-    let mut header_start_idx = 0;
-    loop {
-        let tmp = &buf[header_start_idx..];
-        if tmp.len() < HEADER_MAGIC.len() || header_start_idx > 50 {
-            return Err(FirmwareLoadError::NotValid(
-                "Could not find header magic".into(),
-            ));
-        }
-        if tmp[..HEADER_MAGIC.len()] == HEADER_MAGIC {
-            break; // Found!
-        }
-        header_start_idx += 1;
-    }
+    let at_expected = buf.get(APP_DESC_OFFSET..APP_DESC_OFFSET + HEADER_MAGIC.len()) == Some(&HEADER_MAGIC[..]);
+    let header_start_idx = if at_expected {
+        APP_DESC_OFFSET
+    } else {
+        // Not where ESP-IDF puts it, so scan for it. The caller is warned via [Firmware::layout_warning]
+        let search_end = buf.len().min(HEADER_SEARCH_LEN + HEADER_MAGIC.len());
+        buf[..search_end]
+            .windows(HEADER_MAGIC.len())
+            .position(|w| w == HEADER_MAGIC)
+            .ok_or_else(|| FirmwareLoadError::NotValid("Could not find header magic".into()))?
+    };
 
-    if buf[header_start_idx..].len() < HEADER_SIZE {
-        return Err(FirmwareLoadError::NotValid(
-            "Could not find header magic".into(),
-        ));
-    }
     // Ok, read the header
-    let header: FirmwareHeader =
-        FirmwareHeader::unpack_from_slice(&buf[header_start_idx..header_start_idx + HEADER_SIZE])
-            .unwrap();
-    Ok(Firmware { raw: buf, header })
+    let header_bytes = buf
+        .get(header_start_idx..header_start_idx + HEADER_SIZE)
+        .ok_or_else(|| FirmwareLoadError::NotValid(format!(
+            "File is too short to contain a firmware header ({} bytes after the header magic, need {})",
+            buf.len() - header_start_idx,
+            HEADER_SIZE
+        )))?;
+    let header = FirmwareHeader::unpack_from_slice(header_bytes)
+        .map_err(|e| FirmwareLoadError::NotValid(format!("Could not read firmware header: {e:?}")))?;
+    Ok(Firmware { raw: buf, header, header_offset: header_start_idx })
 }
 
 pub fn load_binary_from_path(path: String) -> FirwmareLoadResult<Firmware> {
@@ -118,3 +194,62 @@ pub fn load_binary_from_path(path: String) -> FirwmareLoadResult<Firmware> {
     f.read_to_end(&mut buf)?;
     load_binary(buf)
 }
+
+/// Downloads a firmware image over HTTPS, then loads it the same as [load_binary].
+/// The image is only held in memory, nothing is written to disk
+pub fn load_binary_from_url(url: &str) -> FirwmareLoadResult<Firmware> {
+    if !url.starts_with("https://") {
+        return Err(FirmwareLoadError::Network("Only https:// URLs are supported".into()));
+    }
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut easy = Easy::new();
+    let mut list = List::new();
+    // Needed for GitHub release assets, otherwise the API returns the asset's JSON description
+    list.append("Accept: application/octet-stream")?;
+    easy.http_headers(list)?;
+    easy.useragent("request")?;
+    easy.follow_location(true)?;
+    easy.url(url)?;
+    {
+        let mut transfer = easy.transfer();
+        transfer.write_function(|data| {
+            buffer.extend_from_slice(data);
+            Ok(data.len())
+        })?;
+        transfer.perform()?;
+    }
+    match easy.response_code()? {
+        200 => load_binary(buffer),
+        code => Err(FirmwareLoadError::Network(format!("Server responded with HTTP {code}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(magic_offset: usize) -> Vec<u8> {
+        let mut buf = vec![0; 512];
+        buf[0] = ESP_IMAGE_MAGIC;
+        buf[magic_offset..magic_offset + HEADER_MAGIC.len()].copy_from_slice(&HEADER_MAGIC);
+        buf
+    }
+
+    #[test]
+    fn header_at_app_desc_offset() {
+        let fw = load_binary(image(APP_DESC_OFFSET)).unwrap();
+        assert_eq!(fw.header_offset, APP_DESC_OFFSET);
+        assert!(fw.layout_warning().is_none());
+    }
+
+    #[test]
+    fn warns_on_misplaced_header() {
+        let fw = load_binary(image(10)).unwrap();
+        assert_eq!(fw.header_offset, 10);
+        assert!(fw.layout_warning().is_some());
+
+        let mut buf = image(APP_DESC_OFFSET);
+        buf[0] = 0;
+        assert!(load_binary(buf).unwrap().layout_warning().is_some());
+    }
+}
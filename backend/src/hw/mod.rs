@@ -1,3 +1,4 @@
 pub mod firmware;
+pub mod sim;
 pub mod usb;
 pub mod usb_scanner;
@@ -0,0 +1,382 @@
+//! Simulated TCU, so the UI can be developed and demonstrated without any hardware.
+//!
+//! Answers KWP session control, tester present and the live data local identifiers
+//! (0x20-0x31) with plausible, slowly varying values. Every other request gets a
+//! negative response, so pages that need a real TCU fail the same way they would
+//! with unsupported firmware.
+use ecu_diagnostics::{
+    channel::{CanChannel, ChannelError, ChannelResult, IsoTPChannel, IsoTPSettings, PayloadChannel},
+    hardware::{HardwareCapabilities, HardwareError, HardwareInfo, HardwareResult},
+};
+use std::{
+    f32::consts::PI,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::diag::Nag52Endpoint;
+
+/// Gear ratios of the large 722.6, used for the simulated drive cycle
+const SIM_RATIOS: [f32; 5] = [3.59, 2.19, 1.41, 1.00, 0.83];
+/// Time spent in each gear of the simulated drive cycle
+const SIM_GEAR_TIME_S: f32 = 8.0;
+
+// KWP negative response codes
+const NRC_SERVICE_NOT_SUPPORTED: u8 = 0x11;
+const NRC_REQUEST_OUT_OF_RANGE: u8 = 0x31;
+
+/// Little endian record builder, matching the TCU's packed diag structs
+#[derive(Default)]
+struct Record(Vec<u8>);
+
+impl Record {
+    fn u8(mut self, v: u8) -> Self {
+        self.0.push(v);
+        self
+    }
+
+    fn u16(mut self, v: u16) -> Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn i16(mut self, v: i16) -> Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn u32(mut self, v: u32) -> Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+}
+
+/// Simulated vehicle state at a point in time
+struct SimState {
+    t: f32,
+    gear_idx: usize,
+    /// 0..1 progress through the current gear
+    gear_progress: f32,
+    input_rpm: f32,
+    output_rpm: f32,
+    atf_temp: f32,
+    /// True whilst the simulated car is shifting
+    shifting: bool,
+}
+
+impl SimState {
+    fn at(t: f32) -> Self {
+        let cycle = t / SIM_GEAR_TIME_S;
+        let gear_idx = (cycle as usize) % SIM_RATIOS.len();
+        let gear_progress = cycle.fract();
+        // Revs rise through each gear, then drop at the shift
+        let input_rpm = 1400.0 + 2000.0 * gear_progress + 50.0 * (t * 2.0 * PI).sin();
+        // Warm up from 30*C to 90*C over 5 minutes
+        let atf_temp = 30.0 + 60.0 * (t / 300.0).min(1.0);
+        Self {
+            t,
+            gear_idx,
+            gear_progress,
+            input_rpm,
+            output_rpm: input_rpm / SIM_RATIOS[gear_idx],
+            atf_temp,
+            shifting: gear_progress > 0.9,
+        }
+    }
+
+    fn gearbox_sensors(&self) -> Vec<u8> {
+        Record::default()
+            .u16(self.input_rpm as u16)
+            .u16(self.input_rpm as u16)
+            .u16(self.input_rpm as u16)
+            .u16((SIM_RATIOS[self.gear_idx] * 100.0) as u16)
+            .u16((13800.0 + 100.0 * (self.t / 3.0).sin()) as u16)
+            .u32(self.atf_temp as u32)
+            .u8(0)
+            .u16(self.output_rpm as u16)
+            .0
+    }
+
+    fn solenoids(&self) -> Vec<u8> {
+        let spc = if self.shifting { 2200.0 } else { 400.0 };
+        let mpc = 800.0 + 300.0 * (self.t / 2.0).sin();
+        let tcc = if self.gear_idx >= 2 { 1800.0 } else { 0.0 };
+        let shift_sol = if self.shifting { 1000 } else { 0 };
+        Record::default()
+            .u16((spc / 3.0) as u16) // PWM
+            .u16((mpc / 3.0) as u16)
+            .u16((tcc / 3.0) as u16)
+            .u16(if self.shifting { 4096 } else { 0 })
+            .u16(0)
+            .u16(0)
+            .u16(spc as u16) // Current
+            .u16(mpc as u16)
+            .u16(tcc as u16)
+            .u16(spc as u16) // Target current
+            .u16(mpc as u16)
+            .u16(1000) // Adjustment
+            .u16(1000)
+            .u16(shift_sol)
+            .u16(0)
+            .u16(0)
+            .0
+    }
+
+    fn can_dump(&self) -> Vec<u8> {
+        let pedal = (100.0 + 80.0 * (self.t / 5.0).sin()) as u8;
+        let wheel = (self.output_rpm / 3.27 * 2.0) as u16;
+        let torque = |nm: f32| ((nm + 500.0) * 4.0) as u16;
+        Record::default()
+            .u8(pedal)
+            .u16(torque(-40.0)) // Min torque
+            .u16(torque(400.0)) // Max torque
+            .u16(torque(100.0 + 150.0 * self.gear_progress))
+            .u16(torque(120.0 + 150.0 * self.gear_progress))
+            .u16(wheel)
+            .u16(wheel)
+            .u8(0)
+            .u8(6) // Selector in D
+            .u8(0) // No paddles pressed
+            .u16((self.input_rpm + 20.0) as u16)
+            .u16(1500)
+            .u16(u16::MAX)
+            .u8(0) // No torque request
+            .u8(0)
+            .i16(25)
+            .i16(self.atf_temp as i16 + 5)
+            .i16(self.atf_temp as i16 - 5)
+            .0
+    }
+
+    fn sys_usage(&self) -> Vec<u8> {
+        Record::default()
+            .u16((2500.0 + 500.0 * (self.t / 7.0).sin()) as u16)
+            .u16((1500.0 + 300.0 * (self.t / 11.0).sin()) as u16)
+            .u32(160_000)
+            .u32(320_000)
+            .u32(3_900_000)
+            .u32(4_000_000)
+            .u32(24)
+            .0
+    }
+
+    fn pressures(&self) -> Vec<u8> {
+        let ss_flag = if self.shifting { 1 << (self.gear_idx.min(2)) } else { 0 };
+        let mpc = (5000.0 + 1000.0 * (self.t / 2.0).sin()) as u16;
+        let spc: u16 = if self.shifting { 7000 } else { 0 };
+        Record::default()
+            .u16(spc / 3)
+            .u16(mpc / 3)
+            .u16(if self.gear_idx >= 2 { 600 } else { 0 })
+            .u8(ss_flag)
+            .u16(spc)
+            .u16(mpc)
+            .u16(spc)
+            .u16(mpc)
+            .u16(if self.gear_idx >= 2 { 1800 } else { 0 })
+            .u16(mpc + 1500)
+            .0
+    }
+
+    fn shift_data(&self) -> Vec<u8> {
+        Record::default()
+            .u16(if self.shifting { 7000 } else { 0 })
+            .u16(5000)
+            .u16(0)
+            .u8(if self.shifting { 1 } else { 0 })
+            .u16(self.input_rpm as u16)
+            .u16((self.input_rpm + 20.0) as u16)
+            .u16(self.output_rpm as u16)
+            .u16(((200.0 + 500.0) * 4.0) as u16)
+            .u16(((200.0 + 500.0) * 4.0) as u16)
+            .u8(self.atf_temp as u8)
+            .u8(if self.shifting { self.gear_idx as u8 + 1 } else { 0 })
+            .0
+    }
+
+    fn clutch_speeds(&self) -> Vec<u8> {
+        let slip = if self.shifting { (self.input_rpm * (1.0 - self.gear_progress) * 0.5) as i16 } else { 0 };
+        Record::default().i16(slip).i16(0).i16(0).i16(0).i16(-slip).i16(0).0
+    }
+
+    fn clutch_velocities(&self) -> Vec<u8> {
+        let v = if self.shifting { 150 } else { 0 };
+        Record::default().i16(v).i16(-v).0
+    }
+}
+
+#[derive(Clone)]
+pub struct SimulatedTcu {
+    info: HardwareInfo,
+    start: Instant,
+    tx: Sender<Vec<u8>>,
+    rx: Arc<Mutex<Receiver<Vec<u8>>>>,
+}
+
+unsafe impl Sync for SimulatedTcu {}
+unsafe impl Send for SimulatedTcu {}
+
+impl SimulatedTcu {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            info: HardwareInfo {
+                name: "Simulated TCU".to_string(),
+                vendor: Some("Ultimate-NAG52 demo mode".to_string()),
+                device_fw_version: None,
+                api_version: None,
+                library_version: None,
+                library_location: None,
+                capabilities: HardwareCapabilities {
+                    iso_tp: true,
+                    can: false,
+                    kline: false,
+                    kline_kwp: false,
+                    sae_j1850: false,
+                    sci: false,
+                    ip: false,
+                },
+            },
+            start: Instant::now(),
+            tx,
+            rx: Arc::new(Mutex::new(rx)),
+        }
+    }
+
+    fn respond(&self, req: &[u8]) -> Vec<u8> {
+        let sid = match req.first() {
+            Some(s) => *s,
+            None => return vec![0x7F, 0x00, NRC_SERVICE_NOT_SUPPORTED],
+        };
+        match (sid, req.get(1)) {
+            // Session control
+            (0x10, Some(mode)) => vec![0x50, *mode],
+            // Tester present
+            (0x3E, _) => vec![0x7E],
+            // Read data by local identifier
+            (0x21, Some(id)) => {
+                let state = SimState::at(self.start.elapsed().as_secs_f32());
+                let data = match id {
+                    0x20 => state.gearbox_sensors(),
+                    0x21 => state.solenoids(),
+                    0x22 => state.can_dump(),
+                    0x23 => state.sys_usage(),
+                    0x25 => state.pressures(),
+                    0x27 => state.shift_data(),
+                    0x30 => state.clutch_speeds(),
+                    0x31 => state.clutch_velocities(),
+                    _ => return vec![0x7F, sid, NRC_REQUEST_OUT_OF_RANGE],
+                };
+                let mut resp = vec![0x61, *id];
+                resp.extend_from_slice(&data);
+                resp
+            }
+            _ => vec![0x7F, sid, NRC_SERVICE_NOT_SUPPORTED],
+        }
+    }
+}
+
+impl Nag52Endpoint for SimulatedTcu {
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn try_connect(_info: &HardwareInfo) -> HardwareResult<Self> {
+        Ok(Self::new())
+    }
+
+    fn get_device_desc(&self) -> String {
+        self.info.name.clone()
+    }
+}
+
+impl ecu_diagnostics::hardware::Hardware for SimulatedTcu {
+    fn create_iso_tp_channel(&mut self) -> HardwareResult<Box<dyn IsoTPChannel>> {
+        Ok(Box::new(self.clone()))
+    }
+
+    fn create_can_channel(&mut self) -> HardwareResult<Box<dyn CanChannel>> {
+        Err(HardwareError::ChannelNotSupported)
+    }
+
+    fn is_iso_tp_channel_open(&self) -> bool {
+        true
+    }
+
+    fn is_can_channel_open(&self) -> bool {
+        false
+    }
+
+    fn read_battery_voltage(&mut self) -> Option<f32> {
+        None
+    }
+
+    fn read_ignition_voltage(&mut self) -> Option<f32> {
+        None
+    }
+
+    fn get_info(&self) -> &HardwareInfo {
+        &self.info
+    }
+
+    fn is_connected(&self) -> bool {
+        true
+    }
+}
+
+impl PayloadChannel for SimulatedTcu {
+    fn open(&mut self) -> ChannelResult<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> ChannelResult<()> {
+        Ok(())
+    }
+
+    fn set_ids(&mut self, _send: u32, _recv: u32) -> ChannelResult<()> {
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, timeout_ms: u32) -> ChannelResult<Vec<u8>> {
+        self.rx
+            .lock()
+            .unwrap()
+            .recv_timeout(Duration::from_millis(timeout_ms as u64))
+            .map_err(|_| ChannelError::BufferEmpty)
+    }
+
+    fn write_bytes(&mut self, _addr: u32, _ext_id: Option<u8>, buffer: &[u8], _timeout_ms: u32) -> ChannelResult<()> {
+        let resp = self.respond(buffer);
+        self.tx.send(resp).map_err(|_| ChannelError::InterfaceNotOpen)
+    }
+
+    fn clear_rx_buffer(&mut self) -> ChannelResult<()> {
+        while self.rx.lock().unwrap().try_recv().is_ok() {}
+        Ok(())
+    }
+
+    fn clear_tx_buffer(&mut self) -> ChannelResult<()> {
+        Ok(())
+    }
+
+    fn read_write_bytes(
+        &mut self,
+        addr: u32,
+        ext_id: Option<u8>,
+        buffer: &[u8],
+        write_timeout_ms: u32,
+        read_timeout_ms: u32,
+    ) -> ChannelResult<Vec<u8>> {
+        self.write_bytes(addr, ext_id, buffer, write_timeout_ms)?;
+        self.read_bytes(read_timeout_ms)
+    }
+}
+
+impl IsoTPChannel for SimulatedTcu {
+    fn set_iso_tp_cfg(&mut self, _cfg: IsoTPSettings) -> ChannelResult<()> {
+        Ok(())
+    }
+}
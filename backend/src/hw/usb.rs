@@ -16,14 +16,30 @@ use std::{
 
 use super::usb_scanner::Nag52UsbScanner;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EspLogLevel {
+    Verbose,
     Debug,
     Info,
     Warn,
     Error,
 }
 
+impl EspLogLevel {
+    pub const ALL: [EspLogLevel; 5] = [Self::Error, Self::Warn, Self::Info, Self::Debug, Self::Verbose];
+
+    /// Letter ESP-IDF prefixes log lines of this level with
+    pub fn letter(&self) -> char {
+        match self {
+            EspLogLevel::Verbose => 'V',
+            EspLogLevel::Debug => 'D',
+            EspLogLevel::Info => 'I',
+            EspLogLevel::Warn => 'W',
+            EspLogLevel::Error => 'E',
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct EspLogMessage {
     pub lvl: EspLogLevel,
@@ -48,6 +64,25 @@ pub struct Nag52USB {
 unsafe impl Sync for Nag52USB {}
 unsafe impl Send for Nag52USB {}
 
+/// Removes ANSI colour codes, which ESP-IDF wraps log lines in when colour output is enabled
+fn strip_ansi(line: &str) -> String {
+    let mut res = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip up to and including the final letter of the escape sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
 fn between<'a>(source: &'a str, start: &'a str, end: &'a str) -> &'a str {
     let start_position = source.find(start);
 
@@ -120,12 +155,11 @@ impl Nag52USB {
                         }
                     } else {
                         println!("{}", line);
-                        let lvl = match line.chars().next().unwrap_or(' ') {
-                            'I' => EspLogLevel::Info,
-                            'W' => EspLogLevel::Warn,
-                            'E' => EspLogLevel::Error,
-                            'D' => EspLogLevel::Debug,
-                            _ => {
+                        let line = strip_ansi(&line);
+                        let first = line.chars().next().unwrap_or(' ');
+                        let lvl = match EspLogLevel::ALL.into_iter().find(|l| l.letter() == first) {
+                            Some(l) => l,
+                            None => {
                                 println!("Malformed log line {line}");
                                 continue
                             }
@@ -157,7 +191,15 @@ impl Nag52USB {
             let mut read_buf = Vec::new();
             while is_running_r.load(Ordering::Relaxed) {
                 // read again in case value changed
-                let btr = port_clone.bytes_to_read().unwrap_or_default();
+                let btr = match port_clone.bytes_to_read() {
+                    Ok(btr) => btr,
+                    Err(e) => {
+                        // Port has gone away (USB unplugged)
+                        log::warn!("Serial port error: {e}. Assuming device was disconnected");
+                        is_running_r.store(false, Ordering::Relaxed);
+                        break;
+                    }
+                };
                 let mut r = vec![0x00; btr];
                 let actual_read = port_clone.read(&mut r[..btr]).unwrap_or_default();
                 rx_bytes_t.fetch_add(actual_read as u32, Ordering::Relaxed);
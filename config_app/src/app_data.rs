@@ -0,0 +1,27 @@
+//! Locations of files that the config app keeps on the users machine
+use std::path::{Path, PathBuf};
+
+/// Root directory for all config app data
+pub fn app_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ultimate-nag52-config-app")
+}
+
+/// Returns a directory within the app data directory, creating it if it does not exist yet
+pub fn app_data_subdir(name: &str) -> std::io::Result<PathBuf> {
+    let p = app_data_dir().join(name);
+    std::fs::create_dir_all(&p)?;
+    Ok(p)
+}
+
+/// Opens a directory in the system file manager
+pub fn open_in_file_manager(path: &Path) -> std::io::Result<()> {
+    #[cfg(windows)]
+    let cmd = "explorer";
+    #[cfg(target_os = "macos")]
+    let cmd = "open";
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let cmd = "xdg-open";
+    std::process::Command::new(cmd).arg(path).spawn().map(|_| ())
+}
@@ -0,0 +1,146 @@
+//! Logger that writes to stderr (Like env_logger) and to a size rotated
+//! log file in the app data directory, so users can attach it to bug reports.
+//!
+//! The TCU's own log stream can be captured the same way, to its own set of files.
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::app_data::app_data_subdir;
+
+/// Size at which the current log file is rotated
+const MAX_LOG_SIZE: u64 = 2 * 1024 * 1024;
+/// Number of old log files kept (app.1.log ... app.N.log)
+const MAX_OLD_LOGS: usize = 3;
+/// Base name of the app's own log files
+const APP_LOG_NAME: &str = "app";
+/// Base name of the captured TCU log files
+const ESP_LOG_NAME: &str = "esp";
+
+pub struct RotatingFile {
+    dir: PathBuf,
+    name: &'static str,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn log_path(&self, idx: usize) -> PathBuf {
+        match idx {
+            0 => self.dir.join(format!("{}.log", self.name)),
+            n => self.dir.join(format!("{}.{n}.log", self.name)),
+        }
+    }
+
+    fn open(dir: PathBuf, name: &'static str) -> std::io::Result<Self> {
+        let path = dir.join(format!("{name}.log"));
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { dir, name, file, size })
+    }
+
+    /// Path of the file currently being written to
+    pub fn path(&self) -> PathBuf {
+        self.log_path(0)
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        for idx in (0..MAX_OLD_LOGS).rev() {
+            let from = self.log_path(idx);
+            if from.exists() {
+                std::fs::rename(from, self.log_path(idx + 1))?;
+            }
+        }
+        self.file = File::create(self.log_path(0))?;
+        self.size = 0;
+        Ok(())
+    }
+
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.size + line.len() as u64 > MAX_LOG_SIZE {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+struct AppLogger {
+    stderr: env_logger::Logger,
+    file: Option<Mutex<RotatingFile>>,
+}
+
+/// Everything at info or above goes to the log file, regardless of RUST_LOG
+const FILE_LEVEL: LevelFilter = LevelFilter::Info;
+
+impl Log for AppLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata) || metadata.level() <= FILE_LEVEL
+    }
+
+    fn log(&self, record: &Record) {
+        if self.stderr.matches(record) {
+            self.stderr.log(record);
+        }
+        if record.level() <= FILE_LEVEL {
+            if let Some(f) = &self.file {
+                let line = format!(
+                    "{} {:<5} [{}] {}\n",
+                    host_timestamp(),
+                    record.level(),
+                    record.target(),
+                    record.args()
+                );
+                if let Ok(mut f) = f.lock() {
+                    let _ = f.write_line(&line);
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(f) = &self.file {
+            if let Ok(mut f) = f.lock() {
+                let _ = f.file.flush();
+            }
+        }
+    }
+}
+
+/// Directory containing the log files
+pub fn log_dir() -> std::io::Result<PathBuf> {
+    app_data_subdir("logs")
+}
+
+/// Local time, as written at the start of each log line
+pub fn host_timestamp() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string()
+}
+
+/// Opens the size rotated file that the TCU's log stream is captured to
+pub fn open_esp_log() -> std::io::Result<RotatingFile> {
+    RotatingFile::open(log_dir()?, ESP_LOG_NAME)
+}
+
+/// Installs the logger. Replaces `env_logger::init()`
+pub fn init() {
+    let stderr = env_logger::Builder::from_default_env().build();
+    let file = match log_dir().and_then(|d| RotatingFile::open(d, APP_LOG_NAME)) {
+        Ok(f) => Some(Mutex::new(f)),
+        Err(e) => {
+            eprintln!("Could not open log file, logging to stderr only: {e}");
+            None
+        }
+    };
+    let max_level = stderr.filter().max(FILE_LEVEL);
+    if log::set_boxed_logger(Box::new(AppLogger { stderr, file })).is_ok() {
+        log::set_max_level(max_level);
+    }
+    log::info!("Config app {} (Build {}) started", env!("CARGO_PKG_VERSION"), env!("GIT_BUILD"));
+}
@@ -1,4 +1,5 @@
 
+use chrono::{DateTime, NaiveDateTime};
 use ehttp::*;
 use serde_json::Value;
 
@@ -17,12 +18,61 @@ impl From<serde_json::Error> for ApiQueryError {
     }
 }
 
+impl std::fmt::Display for ApiQueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ConfigAppRelease {
-    date: String,
-    osx_url: Option<String>,
-    unix_url: Option<String>,
-    windows_url: Option<String>,
-    name: String
+    pub date: Option<NaiveDateTime>,
+    pub osx_url: Option<String>,
+    pub unix_url: Option<String>,
+    pub windows_url: Option<String>,
+    pub name: String,
+    /// Release page on GitHub
+    pub html_url: String,
+}
+
+impl ConfigAppRelease {
+    fn from_json(v: &Value) -> Option<Self> {
+        let obj = v.as_object()?;
+        let mut res = Self {
+            date: obj.get("published_at")
+                .and_then(|d| d.as_str())
+                .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+                .map(|d| d.naive_utc()),
+            osx_url: None,
+            unix_url: None,
+            windows_url: None,
+            name: obj.get("name").and_then(|n| n.as_str()).unwrap_or_default().to_string(),
+            html_url: obj.get("html_url")?.as_str()?.to_string(),
+        };
+        for asset in obj.get("assets").and_then(|a| a.as_array()).unwrap_or(&Vec::new()) {
+            let name = asset.get("name").and_then(|n| n.as_str()).unwrap_or_default().to_lowercase();
+            let url = asset.get("browser_download_url").and_then(|u| u.as_str()).map(|u| u.to_string());
+            if name.contains("win") || name.ends_with(".exe") {
+                res.windows_url = url;
+            } else if name.contains("mac") || name.contains("osx") || name.contains("darwin") {
+                res.osx_url = url;
+            } else if name.contains("linux") || name.contains("unix") {
+                res.unix_url = url;
+            }
+        }
+        Some(res)
+    }
+
+    /// Download for the OS this app is running on
+    pub fn download_url(&self) -> Option<&str> {
+        if cfg!(windows) {
+            self.windows_url.as_deref()
+        } else if cfg!(target_os = "macos") {
+            self.osx_url.as_deref()
+        } else {
+            self.unix_url.as_deref()
+        }
+    }
 }
 
 pub type ApiQueryResult<T> = std::result::Result<T, ApiQueryError>;
@@ -50,8 +100,23 @@ pub fn query_firmware_releases(branch: &str) -> ApiQueryResult<Vec<Value>> {
     Ok(res)
 }
 
-pub fn query_config_app_releases(branch: &str) -> ApiQueryResult<Value> {
-    query_gh_api("https://api.github.com/repos/rnd-ash/ultimate-nag52-config-app/releases")
+pub fn query_config_app_releases(branch: &str) -> ApiQueryResult<Vec<ConfigAppRelease>> {
+    let v = query_gh_api("https://api.github.com/repos/rnd-ash/ultimate-nag52-config-app/releases")?;
+    Ok(v.as_array()
+        .unwrap_or(&Vec::new())
+        .iter()
+        .filter_map(ConfigAppRelease::from_json)
+        .filter(|r| r.html_url.contains(&format!("{branch}-")))
+        .collect())
+}
+
+/// Config app release that goes with firmware built at `fw_build`. This is the first release
+/// published after the firmware was built, or the newest release if there is none after it
+pub fn matching_config_app_release(releases: &[ConfigAppRelease], fw_build: NaiveDateTime) -> Option<&ConfigAppRelease> {
+    releases.iter()
+        .filter(|r| r.date.map(|d| d >= fw_build).unwrap_or(false))
+        .min_by_key(|r| r.date)
+        .or_else(|| releases.iter().max_by_key(|r| r.date))
 }
 
 #[cfg(test)]
@@ -0,0 +1,120 @@
+//! Simple translation layer for user facing UI text.
+//!
+//! Each language is a flat `key: text` YML map in `res/lang`. Text can contain
+//! `{name}` placeholders which are filled in with [tr_fmt]. Missing keys fall back
+//! to English, and then to the key itself, so an incomplete translation never
+//! breaks the UI.
+use std::{collections::HashMap, fmt::Display, sync::RwLock};
+
+use backend::serde_yaml;
+
+use crate::app_data::app_data_subdir;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    German,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::German];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+        }
+    }
+
+    /// Name of the language, in that language
+    pub fn native_name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|l| l.code() == code.trim())
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Language::English => include_str!("../res/lang/en.yml"),
+            Language::German => include_str!("../res/lang/de.yml"),
+        }
+    }
+}
+
+type StringTable = HashMap<String, String>;
+
+struct Translations {
+    lang: Language,
+    strings: StringTable,
+    fallback: StringTable,
+}
+
+static TRANSLATIONS: RwLock<Option<Translations>> = RwLock::new(None);
+
+const LANGUAGE_FILE: &str = "language.txt";
+
+fn load_table(lang: Language) -> StringTable {
+    serde_yaml::from_str(lang.source()).unwrap_or_else(|e| {
+        eprintln!("Language file for '{}' is invalid: {e}", lang.code());
+        StringTable::new()
+    })
+}
+
+fn load_saved_language() -> Language {
+    app_data_subdir("")
+        .and_then(|p| std::fs::read_to_string(p.join(LANGUAGE_FILE)))
+        .ok()
+        .and_then(|s| Language::from_code(&s))
+        .unwrap_or(Language::English)
+}
+
+fn make_translations(lang: Language) -> Translations {
+    Translations {
+        lang,
+        strings: load_table(lang),
+        fallback: load_table(Language::English),
+    }
+}
+
+pub fn current_language() -> Language {
+    if let Some(t) = TRANSLATIONS.read().unwrap().as_ref() {
+        return t.lang;
+    }
+    load_saved_language()
+}
+
+/// Switches the UI language, and saves it for the next launch
+pub fn set_language(lang: Language) {
+    *TRANSLATIONS.write().unwrap() = Some(make_translations(lang));
+    if let Err(e) = app_data_subdir("").and_then(|p| std::fs::write(p.join(LANGUAGE_FILE), lang.code())) {
+        eprintln!("Could not save language setting: {e}");
+    }
+}
+
+/// Returns the text for `key` in the current language
+pub fn tr(key: &str) -> String {
+    if TRANSLATIONS.read().unwrap().is_none() {
+        *TRANSLATIONS.write().unwrap() = Some(make_translations(load_saved_language()));
+    }
+    let lock = TRANSLATIONS.read().unwrap();
+    let t = lock.as_ref().unwrap();
+    t.strings
+        .get(key)
+        .or_else(|| t.fallback.get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+/// Like [tr], but replaces `{name}` placeholders with the given arguments
+pub fn tr_fmt(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut s = tr(key);
+    for (name, value) in args {
+        s = s.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    s
+}
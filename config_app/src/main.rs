@@ -4,7 +4,12 @@ use ui::launcher::Launcher;
 #[cfg(windows)]
 use eframe::Renderer;
 
+mod app_data;
+mod file_log;
+mod i18n;
 mod plot_backend;
+mod repaint;
+mod vehicle_state;
 mod ui;
 mod window;
 mod ghapi;
@@ -14,7 +19,7 @@ mod ghapi;
 compile_error!("Windows can ONLY be built using the i686-pc-windows-msvc target!");
 
 fn main() {
-    env_logger::init();
+    file_log::init();
 
     let icon = image::load_from_memory(include_bytes!("../icon.png"))
         .unwrap()
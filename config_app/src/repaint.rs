@@ -0,0 +1,71 @@
+//! When live pages repaint the window.
+//!
+//! Live pages normally repaint at [crate::ui::diagnostics::rli::RLI_PLOT_INTERVAL], so charts
+//! scroll smoothly. That keeps the CPU and GPU busy even whilst no new data arrives, so
+//! repainting can instead be limited to when a page receives a new sample from the TCU.
+//! The choice is saved for the next launch.
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use eframe::egui::Id;
+
+use crate::window::get_context;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepaintMode {
+    /// Repaint every plot interval
+    Continuous,
+    /// Repaint only when new data arrives
+    OnData,
+}
+
+/// Key of the saved mode in egui memory, which eframe stores between launches
+const REPAINT_ID: &str = "repaint_on_data";
+
+/// 0 until the saved mode has been loaded, then 1 + the mode
+static MODE: AtomicU8 = AtomicU8::new(0);
+
+fn load_saved_mode() -> RepaintMode {
+    let on_data = get_context().data_mut(|d| d.get_persisted::<bool>(Id::new(REPAINT_ID))).unwrap_or(false);
+    if on_data {
+        RepaintMode::OnData
+    } else {
+        RepaintMode::Continuous
+    }
+}
+
+fn store(mode: RepaintMode) {
+    MODE.store(if mode == RepaintMode::OnData { 2 } else { 1 }, Ordering::Relaxed);
+}
+
+pub fn mode() -> RepaintMode {
+    match MODE.load(Ordering::Relaxed) {
+        1 => RepaintMode::Continuous,
+        2 => RepaintMode::OnData,
+        _ => {
+            let mode = load_saved_mode();
+            store(mode);
+            mode
+        }
+    }
+}
+
+/// Switches the repaint mode, and saves it for the next launch
+pub fn set_mode(mode: RepaintMode) {
+    store(mode);
+    get_context().data_mut(|d| d.insert_persisted(Id::new(REPAINT_ID), mode == RepaintMode::OnData));
+}
+
+/// Called by the plot timer of a live page. Repaints unless repainting is data driven
+pub fn plot_tick() {
+    if mode() == RepaintMode::Continuous {
+        get_context().request_repaint();
+    }
+}
+
+/// Called by a live page when it receives a new sample. Repaints if repainting is data driven,
+/// otherwise the plot timer already takes care of it
+pub fn new_data() {
+    if mode() == RepaintMode::OnData {
+        get_context().request_repaint();
+    }
+}
@@ -1,8 +1,14 @@
 use std::fmt::Display;
 
 use packed_struct::prelude::{PackedStruct, PrimitiveEnum_u8};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, PackedStruct)]
+// The config structs (De)serialize with field and variant names, so exported files stay
+// readable and editable. Renaming a field or variant breaks existing files.
+
+pub use backend::diag::records::{LARGE_NAG_RATIOS, SMALL_NAG_RATIOS};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, PackedStruct, Serialize, Deserialize)]
 #[packed_struct(endian="lsb")]
 pub struct TcmCoreConfig {
     pub is_large_nag: u8,
@@ -37,7 +43,7 @@ pub struct TcmCoreConfig {
     pub engine_drag_torque: u16
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8, Serialize, Deserialize)]
 pub enum EgsCanType {
     UNKNOWN = 0,
     EGS51 = 1,
@@ -45,28 +51,28 @@ pub enum EgsCanType {
     EGS53 = 3,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8, Serialize, Deserialize)]
 pub enum ShifterStyle {
     EWM_CAN = 0,
     TRRS = 1,
     SLR_MCLAREN = 2, // NEEDS TESTING (Need to work out how this works)
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8, Serialize, Deserialize)]
 pub enum IOPinConfig {
     NotConnected = 0,
     Input = 1,
     Output = 2,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8, Serialize, Deserialize)]
 pub enum MosfetPurpose {
     NotConnected = 0,
     TorqueCutTrigger = 1,
     B3BrakeSolenoid = 2,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, PackedStruct)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, PackedStruct, Serialize, Deserialize)]
 pub struct TcmEfuseConfig {
     #[packed_field(size_bytes="1", ty="enum")]
     pub board_ver: BoardType,
@@ -76,7 +82,7 @@ pub struct TcmEfuseConfig {
     pub manf_year: u8,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8, Serialize, Deserialize)]
 pub enum DefaultProfile {
     Standard = 0,
     Comfort = 1,
@@ -85,13 +91,13 @@ pub enum DefaultProfile {
     Manual = 4,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8, Serialize, Deserialize)]
 pub enum EngineType {
     Diesel,
     Petrol,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, PrimitiveEnum_u8, Serialize, Deserialize)]
 pub enum BoardType {
     Unknown = 0,
     V11 = 1,
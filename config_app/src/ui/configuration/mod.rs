@@ -1,11 +1,12 @@
 use std::{
     borrow::BorrowMut,
-    sync::{Arc, Mutex}, ops::RemAssign,
+    sync::{Arc, Mutex}, ops::{RemAssign, RangeInclusive},
+    time::Duration,
 };
 
-use crate::window::PageAction;
+use crate::{ghapi::{matching_config_app_release, query_config_app_releases, ConfigAppRelease}, i18n::{tr, tr_fmt}, ui::widgets::battery_guard::BatteryGuard, window::PageAction};
 use backend::{
-    diag::Nag52Diag, ecu_diagnostics::kwp2000::{ResetType, KwpSessionType},
+    diag::{send_with_session_retry, Nag52Diag}, ecu_diagnostics::kwp2000::{ResetType, KwpSessionType},
 };
 use chrono::{Datelike, Weekday};
 use config_app_macros::include_base64;
@@ -17,20 +18,165 @@ use packed_struct::PackedStructSlice;
 
 use self::cfg_structs::{
     BoardType, DefaultProfile, EgsCanType, EngineType, IOPinConfig, MosfetPurpose, ShifterStyle,
-    TcmCoreConfig, TcmEfuseConfig,
+    TcmCoreConfig, TcmEfuseConfig, LARGE_NAG_RATIOS, SMALL_NAG_RATIOS,
 };
 
 use super::{StatusText};
 
 pub mod cfg_structs;
 
+/// Plausible ratio ranges. Anything outside of these is most likely a typo
+/// or a ratio entered in the wrong units
+const DIFF_RATIO_RANGE: RangeInclusive<f32> = 2.0..=5.0;
+const TC_HIGH_RATIO_RANGE: RangeInclusive<f32> = 0.5..=1.5;
+const TC_LOW_RATIO_RANGE: RangeInclusive<f32> = 1.0..=3.5;
+/// Ratios that fit in their u16 (x1000) field at all
+const RATIO_EDIT_RANGE: RangeInclusive<f32> = 0.0..=65.0;
+
+/// Shows a warning next to a ratio that is out of range. Returns true if the ratio is OK
+fn check_ratio(ui: &mut Ui, raw: u16, range: RangeInclusive<f32>) -> bool {
+    let ratio = raw as f32 / 1000.0;
+    if range.contains(&ratio) {
+        true
+    } else {
+        ui.add(StatusText::Err(tr_fmt(
+            "config.ratio_range",
+            &[
+                ("ratio", &format!("{:.2}", ratio)),
+                ("min", &format!("{:.2}", range.start())),
+                ("max", &format!("{:.2}", range.end())),
+            ]
+        )));
+        false
+    }
+}
+
+/// Text entry for a number, shown with `decimals` decimal places (0 for whole numbers).
+/// The text is kept whilst the field is being edited, and the value only changes whilst it
+/// is a valid number within `range`. Otherwise the last good value is kept, and the problem
+/// is shown next to the field
+fn num_edit(ui: &mut Ui, id: &str, value: f32, decimals: usize, range: RangeInclusive<f32>) -> f32 {
+    let id = ui.id().with(("num_edit", id));
+    let mut buf = ui.data_mut(|d| d.get_temp::<String>(id)).unwrap_or_else(|| format!("{:.*}", decimals, value));
+    let parsed = match buf.trim().parse::<f32>() {
+        Ok(x) if decimals == 0 && x.fract() != 0.0 => Err(tr("config.num_whole")),
+        Ok(x) if range.contains(&x) => Ok(x),
+        Ok(_) => Err(tr_fmt(
+            "config.num_range",
+            &[
+                ("min", &format!("{:.*}", decimals, range.start())),
+                ("max", &format!("{:.*}", decimals, range.end())),
+            ]
+        )),
+        Err(_) => Err(tr("config.num_invalid")),
+    };
+    let mut ret = value;
+    ui.horizontal(|row| {
+        let mut edit = TextEdit::singleline(&mut buf);
+        if parsed.is_err() {
+            edit = edit.text_color(Color32::RED);
+        }
+        let resp = row.add(edit);
+        match &parsed {
+            Ok(x) => {
+                if resp.changed() {
+                    ret = *x;
+                }
+            },
+            Err(e) => {
+                row.label(RichText::new(tr_fmt("config.num_kept", &[("error", e), ("value", &format!("{:.*}", decimals, value))])).color(Color32::RED));
+            }
+        }
+        if resp.has_focus() {
+            row.data_mut(|d| d.insert_temp(id, buf.clone()));
+        } else {
+            row.data_mut(|d| d.remove::<String>(id));
+        }
+    });
+    ret
+}
+
+/// Config app release that matches the firmware on the TCU
+struct VersionHint {
+    fw_version: String,
+    release: Option<ConfigAppRelease>,
+}
+
+/// Works out which config app release goes with the TCU's firmware, for when the
+/// config structs cannot be read (Most likely due to a version mismatch)
+fn find_matching_app(nag: &Nag52Diag) -> Result<VersionHint, String> {
+    let fw = nag.get_running_fw_info().map_err(|e| e.to_string())?;
+    let fw_version = fw.get_version();
+    let build = fw.get_build_timestamp().ok_or_else(|| tr_fmt("config.fw_no_date", &[("version", &fw_version)]))?;
+    let branch = if fw_version.contains("main") { "main" } else { "dev" };
+    let releases = query_config_app_releases(branch).map_err(|e| e.to_string())?;
+    Ok(VersionHint {
+        release: matching_config_app_release(&releases, build).cloned(),
+        fw_version,
+    })
+}
+
+/// The TCU reboots after an EFUSE write, so the read back is retried until it is back up
+const EFUSE_VERIFY_ATTEMPTS: u32 = 10;
+const EFUSE_VERIFY_RETRY_MS: u64 = 500;
+
+/// Reads the EFUSE config back after it was written and the TCU was reset
+fn read_back_efuse(nag: &Nag52Diag) -> Result<TcmEfuseConfig, String> {
+    let mut last_err = String::new();
+    for _ in 0..EFUSE_VERIFY_ATTEMPTS {
+        std::thread::sleep(Duration::from_millis(EFUSE_VERIFY_RETRY_MS));
+        let res = nag.with_kwp(|server| {
+            // The reset dropped the TCU out of the reprogramming session
+            server.kwp_set_session(KwpSessionType::Normal.into())?;
+            server.kwp_read_custom_local_identifier(0xFD)
+        });
+        match res {
+            Ok(res) => return TcmEfuseConfig::unpack_from_slice(&res).map_err(|_| tr("config.err_efuse_size")),
+            Err(e) => last_err = e.to_string(),
+        }
+    }
+    Err(last_err)
+}
+
+/// Translation key of a short description of what a drive profile does
+fn profile_desc_key(profile: DefaultProfile) -> &'static str {
+    match profile {
+        DefaultProfile::Standard => "config.profile_desc_standard",
+        DefaultProfile::Comfort => "config.profile_desc_comfort",
+        DefaultProfile::Winter => "config.profile_desc_winter",
+        DefaultProfile::Agility => "config.profile_desc_agility",
+        DefaultProfile::Manual => "config.profile_desc_manual",
+    }
+}
+
+/// CAN layers that work on a board
+fn can_layers_for_board(board: BoardType) -> Vec<EgsCanType> {
+    match board {
+        BoardType::Unknown | BoardType::V11 => {
+            vec![EgsCanType::UNKNOWN, EgsCanType::EGS52, EgsCanType::EGS53]
+        }
+        _ => vec![
+            EgsCanType::UNKNOWN,
+            EgsCanType::EGS51,
+            EgsCanType::EGS52,
+            EgsCanType::EGS53,
+        ],
+    }
+}
+
 pub struct ConfigPage {
     nag: Nag52Diag,
+    /// CAN layer the TCU reported when the config was last read
+    reported_can_type: Option<EgsCanType>,
+    version_hint: Option<Arc<Mutex<Option<Result<VersionHint, String>>>>>,
     status: StatusText,
     scn: Option<TcmCoreConfig>,
     efuse: Option<TcmEfuseConfig>,
     show_efuse: bool,
     show_final_warning: bool,
+    /// Board variant that was written to EFUSE, and the config read back afterwards (None until read)
+    efuse_verify: Option<(BoardType, Arc<Mutex<Option<Result<TcmEfuseConfig, String>>>>)>,
+    battery: BatteryGuard,
     pcb_11_img: RetainedImage,
     pcb_12_img: RetainedImage,
     pcb_13_img: RetainedImage,
@@ -68,12 +214,16 @@ impl ConfigPage {
         let pcb_12_img = load_image(blk_img, "V12-PCB");
         let pcb_13_img = load_image(bet_img, "V13-PCB");
         Self {
+            battery: BatteryGuard::new(nag.clone()).block_when_moving(),
             nag,
+            version_hint: None,
+            reported_can_type: None,
             status: StatusText::Ok("".into()),
             scn: None,
             efuse: None,
             show_efuse: false,
             show_final_warning: false,
+            efuse_verify: None,
             pcb_11_img,
             pcb_12_img,
             pcb_13_img,
@@ -83,23 +233,28 @@ impl ConfigPage {
 
 impl crate::window::InterfacePage for ConfigPage {
     fn make_ui(&mut self, ui: &mut Ui, frame: &eframe::Frame) -> PageAction {
-        ui.heading("TCM Configuration");
+        ui.heading(tr("config.title"));
 
-        if ui.button("Read Configuration").clicked() {
+        if ui.button(tr("config.read")).clicked() {
+            let mut mismatch = false;
             let _ = self.nag.with_kwp(|server| {
                 match server.kwp_read_custom_local_identifier(0xFE) {
                     Ok(res) => {
                         match TcmCoreConfig::unpack_from_slice(&res) {
                             Ok(res) => {
-                                self.status = StatusText::Ok(format!("Read OK!"));
+                                self.status = StatusText::Ok(tr("config.read_ok"));
+                                self.reported_can_type = Some(res.egs_can_type);
                                 self.scn = Some(res)
                             },
-                            Err(_) => self.status = StatusText::Err(format!("TCM Config size is invalid. Maybe you have mismatched TCU firmware and config app version?"))
+                            Err(_) => {
+                                self.status = StatusText::Err(tr("config.err_size"));
+                                mismatch = true;
+                            }
                         }
                     }
                     Err(e) => {
                         self.status =
-                            StatusText::Err(format!("Error reading TCM configuration: {}", e))
+                            StatusText::Err(tr_fmt("config.err_read", &[("error", &e)]))
                     }
                 }
                 match server.kwp_read_custom_local_identifier(0xFD) {
@@ -111,16 +266,28 @@ impl crate::window::InterfacePage for ConfigPage {
                                 }
                                 self.efuse = Some(tmp);
                             },
-                            Err(_) => self.status = StatusText::Err(format!("TCM EFUSE size is invalid. Maybe you have mismatched TCU firmware and config app version?"))
+                            Err(_) => {
+                                self.status = StatusText::Err(tr("config.err_efuse_size"));
+                                mismatch = true;
+                            }
                         }
                     }
                     Err(e) => {
                         self.status =
-                            StatusText::Err(format!("Error reading TCM EFUSE configuration: {}", e))
+                            StatusText::Err(tr_fmt("config.err_efuse_read", &[("error", &e)]))
                     }
                 }
                 Ok(())
             });
+            if mismatch && self.version_hint.is_none() {
+                let hint = Arc::new(Mutex::new(None));
+                let hint_t = hint.clone();
+                let nag = self.nag.clone();
+                std::thread::spawn(move || {
+                    *hint_t.lock().unwrap() = Some(find_matching_app(&nag));
+                });
+                self.version_hint = Some(hint);
+            }
         }
 
         let board_ver = self
@@ -128,20 +295,33 @@ impl crate::window::InterfacePage for ConfigPage {
             .clone()
             .map(|x| x.board_ver)
             .unwrap_or(BoardType::Unknown);
+        let low_batt = if self.scn.is_some() || self.efuse.is_some() {
+            self.battery.make_ui(ui)
+        } else {
+            false
+        };
         if let Some(scn) = self.scn.borrow_mut() {
+            let mut ratios_ok = true;
 
-            ui.hyperlink_to("See getting started for more info", include_base64!("aHR0cDovL2RvY3MudWx0aW1hdGUtbmFnNTIubmV0L2VuL2dldHRpbmdzdGFydGVkI2l2ZS1yZWNlaXZlZC1hbi1hc3NlbWJsZWQtdGN1"));
-            ui.hyperlink_to("See Mercedes VIN lookup table for your car configuration", include_base64!("aHR0cDovL2RvY3MudWx0aW1hdGUtbmFnNTIubmV0L2VuL2dldHRpbmdzdGFydGVkL2NvbmZpZ3VyYXRpb24vVklOTGlzdA"));
+            ui.hyperlink_to(tr("config.getting_started"), include_base64!("aHR0cDovL2RvY3MudWx0aW1hdGUtbmFnNTIubmV0L2VuL2dldHRpbmdzdGFydGVkI2l2ZS1yZWNlaXZlZC1hbi1hc3NlbWJsZWQtdGN1"));
+            ui.hyperlink_to(tr("config.vin_lookup"), include_base64!("aHR0cDovL2RvY3MudWx0aW1hdGUtbmFnNTIubmV0L2VuL2dldHRpbmdzdGFydGVkL2NvbmZpZ3VyYXRpb24vVklOTGlzdA"));
 
             egui::Grid::new("DGS").striped(true).show(ui, |ui| {
                 let mut x = scn.is_large_nag == 1;
-                ui.label("Using large 722.6");
+                ui.label(tr("config.large_nag"));
                 ui.checkbox(&mut x, "");
                 scn.is_large_nag = x as u8;
                 ui.end_row();
 
+                // Read only, so it is clear what the gearbox variant changes
+                let ratios = if x { LARGE_NAG_RATIOS } else { SMALL_NAG_RATIOS };
+                ui.label(tr("config.gear_ratios"));
+                ui.label(ratios.iter().enumerate().map(|(i, r)| format!("{}: {:.2}", i + 1, r)).collect::<Vec<_>>().join("  "))
+                    .on_hover_text(tr("config.gear_ratios_hint"));
+                ui.end_row();
+
                 let mut curr_profile = scn.default_profile;
-                ui.label("Default drive profile");
+                ui.label(tr("config.default_profile"));
                 egui::ComboBox::from_id_source("profile")
                     .width(100.0)
                     .selected_text(format!("{:?}", curr_profile))
@@ -163,25 +343,22 @@ impl crate::window::InterfacePage for ConfigPage {
                         scn.default_profile = curr_profile
                     });
                 ui.end_row();
+                ui.label("");
+                ui.label(RichText::new(tr(profile_desc_key(scn.default_profile))).italics());
+                ui.end_row();
 
-                let mut buffer = format!("{:.2}", scn.diff_ratio as f32 / 1000.0);
-                ui.label("Differential ratio");
-                ui.text_edit_singleline(&mut buffer);
-                if let Ok(new_ratio) = buffer.parse::<f32>() {
-                    scn.diff_ratio = (new_ratio * 1000.0) as u16;
-                }
+                ui.label(tr("config.diff_ratio"));
+                let ratio = num_edit(ui, "diff_ratio", scn.diff_ratio as f32 / 1000.0, 2, RATIO_EDIT_RANGE);
+                scn.diff_ratio = (ratio * 1000.0).round() as u16;
+                ratios_ok &= check_ratio(ui, scn.diff_ratio, DIFF_RATIO_RANGE);
                 ui.end_row();
 
-                let mut buffer = format!("{}", scn.wheel_circumference);
-                ui.label("Wheel circumferance (mm)");
-                ui.text_edit_singleline(&mut buffer);
-                if let Ok(new_ratio) = buffer.parse::<u16>() {
-                    scn.wheel_circumference = new_ratio;
-                }
+                ui.label(tr("config.wheel_circ"));
+                scn.wheel_circumference = num_edit(ui, "wheel_circ", scn.wheel_circumference as f32, 0, 1.0..=u16::MAX as f32) as u16;
                 ui.end_row();
 
                 let mut engine = scn.engine_type;
-                ui.label("Engine type");
+                ui.label(tr("config.engine_type"));
                 egui::ComboBox::from_id_source("engine_type")
                     .width(100.0)
                     .selected_text(format!("{:?}", engine))
@@ -194,82 +371,69 @@ impl crate::window::InterfacePage for ConfigPage {
                     });
                 ui.end_row();
 
-                let mut buffer = match scn.engine_type {
-                    EngineType::Diesel => format!("{}", scn.red_line_dieselrpm),
-                    EngineType::Petrol => format!("{}", scn.red_line_petrolrpm),
+                let redline = match scn.engine_type {
+                    EngineType::Diesel => &mut scn.red_line_dieselrpm,
+                    EngineType::Petrol => &mut scn.red_line_petrolrpm,
                 };
-                ui.label("Engine redline RPM");
-                ui.text_edit_singleline(&mut buffer);
-                if let Ok(rpm) = buffer.parse::<u16>() {
-                    match scn.engine_type {
-                        EngineType::Diesel => scn.red_line_dieselrpm = rpm,
-                        EngineType::Petrol => scn.red_line_petrolrpm = rpm,
-                    }
-                }
+                ui.label(tr("config.redline"));
+                *redline = num_edit(ui, "redline", *redline as f32, 0, 0.0..=u16::MAX as f32) as u16;
                 ui.end_row();
 
                 let mut x = scn.is_four_matic == 1;
-                ui.label("Four matic");
+                ui.label(tr("config.four_matic"));
                 ui.checkbox(&mut x, "");
                 scn.is_four_matic = (x as u8);
                 ui.end_row();
 
                 if scn.is_four_matic == 1 {
-                    let mut buffer =
-                        format!("{:.2}", scn.transfer_case_high_ratio as f32 / 1000.0);
-                    ui.label("Transfer case high ratio");
-                    ui.text_edit_singleline(&mut buffer);
-                    if let Ok(new_ratio) = buffer.parse::<f32>() {
-                        scn.transfer_case_high_ratio = (new_ratio * 1000.0) as u16;
-                    }
+                    ui.label(tr("config.tc_high"));
+                    let ratio = num_edit(ui, "tc_high", scn.transfer_case_high_ratio as f32 / 1000.0, 2, RATIO_EDIT_RANGE);
+                    scn.transfer_case_high_ratio = (ratio * 1000.0).round() as u16;
+                    ratios_ok &= check_ratio(ui, scn.transfer_case_high_ratio, TC_HIGH_RATIO_RANGE);
                     ui.end_row();
 
-                    let mut buffer =
-                        format!("{:.2}", scn.transfer_case_low_ratio as f32 / 1000.0);
-                    ui.label("Transfer case low ratio");
-                    ui.text_edit_singleline(&mut buffer);
-                    if let Ok(new_ratio) = buffer.parse::<f32>() {
-                        scn.transfer_case_low_ratio = (new_ratio * 1000.0) as u16;
-                    }
+                    ui.label(tr("config.tc_low"));
+                    let ratio = num_edit(ui, "tc_low", scn.transfer_case_low_ratio as f32 / 1000.0, 2, RATIO_EDIT_RANGE);
+                    scn.transfer_case_low_ratio = (ratio * 1000.0).round() as u16;
+                    ratios_ok &= check_ratio(ui, scn.transfer_case_low_ratio, TC_LOW_RATIO_RANGE);
                     ui.end_row();
                 }
 
-                let mut buffer =
-                        format!("{:.1}", scn.engine_drag_torque as f32 / 10.0);
-                    ui.label("Engine drag torque");
-                    ui.text_edit_singleline(&mut buffer);
-                    if let Ok(drg) = buffer.parse::<f32>() {
-                        scn.engine_drag_torque = (drg * 10.0) as u16;
-                    }
-                    ui.end_row();
+                ui.label(tr("config.drag_torque"));
+                let drag = num_edit(ui, "drag_torque", scn.engine_drag_torque as f32 / 10.0, 1, 0.0..=u16::MAX as f32 / 10.0);
+                scn.engine_drag_torque = (drag * 10.0).round() as u16;
+                ui.end_row();
 
-                ui.label("EGS CAN Layer: ");
+                ui.label(tr("config.can_layer"));
                 let mut can = scn.egs_can_type;
+                let board_layers = can_layers_for_board(board_ver);
                 egui::ComboBox::from_id_source("can_layer")
                     .width(100.0)
                     .selected_text(format!("{:?}", can))
                     .show_ui(ui, |cb_ui| {
-                        let layers = match board_ver {
-                            BoardType::Unknown | BoardType::V11 => {
-                                vec![EgsCanType::UNKNOWN, EgsCanType::EGS52, EgsCanType::EGS53]
-                            }
-                            _ => vec![
-                                EgsCanType::UNKNOWN,
-                                EgsCanType::EGS51,
-                                EgsCanType::EGS52,
-                                EgsCanType::EGS53,
-                            ],
-                        };
+                        let mut layers = board_layers.clone();
+                        // Whatever the TCU reported stays selectable, so an unsupported value can be changed back to
+                        if let Some(reported) = self.reported_can_type.filter(|l| !layers.contains(l)) {
+                            layers.push(reported);
+                        }
                         for layer in layers {
                             cb_ui.selectable_value(&mut can, layer.clone(), format!("{:?}", layer));
                         }
                         scn.egs_can_type = can
                     });
                 ui.end_row();
+                if !board_layers.contains(&scn.egs_can_type) {
+                    ui.label("");
+                    ui.add(StatusText::Err(tr_fmt(
+                        "config.can_layer_unusual",
+                        &[("layer", &format!("{:?}", scn.egs_can_type)), ("board", &board_ver)]
+                    )));
+                    ui.end_row();
+                }
 
                 if board_ver == BoardType::V12 || board_ver == BoardType::V13 {
                     // 1.2 or 1.3 config
-                    ui.label("Shifter style: ");
+                    ui.label(tr("config.shifter_style"));
                     let mut ss = scn.shifter_style;
                     egui::ComboBox::from_id_source("shifter_style")
                         .width(200.0)
@@ -290,7 +454,7 @@ impl crate::window::InterfacePage for ConfigPage {
 
                 if board_ver == BoardType::V13 {
                     // Only v1.3 config
-                    ui.label("GPIO usage: ");
+                    ui.label(tr("config.gpio_usage"));
                     let mut ss = scn.io_0_usage;
                     egui::ComboBox::from_id_source("gpio_usage")
                         .width(200.0)
@@ -309,23 +473,15 @@ impl crate::window::InterfacePage for ConfigPage {
                     ui.end_row();
 
                     if scn.io_0_usage == IOPinConfig::Input {
-                        let mut t = format!("{}", scn.input_sensor_pulses_per_rev);
-                        ui.label("Input sensor pulses/rev");
-                        ui.text_edit_singleline(&mut t);
-                        if let Ok(prev) = t.parse::<u8>() {
-                            scn.input_sensor_pulses_per_rev = prev;
-                        }
+                        ui.label(tr("config.input_pulses"));
+                        scn.input_sensor_pulses_per_rev = num_edit(ui, "input_pulses", scn.input_sensor_pulses_per_rev as f32, 0, 0.0..=u8::MAX as f32) as u8;
                         ui.end_row();
                     } else if scn.io_0_usage == IOPinConfig::Output {
-                        let mut t = format!("{}", scn.output_pulse_width_per_kmh);
-                        ui.label("Pulse width (us) per kmh");
-                        ui.text_edit_singleline(&mut t);
-                        if let Ok(prev) = t.parse::<u8>() {
-                            scn.output_pulse_width_per_kmh = prev;
-                        }
+                        ui.label(tr("config.pulse_width"));
+                        scn.output_pulse_width_per_kmh = num_edit(ui, "pulse_width", scn.output_pulse_width_per_kmh as f32, 0, 0.0..=u8::MAX as f32) as u8;
                         ui.end_row();
                     }
-                    ui.label("General MOSFET usage: ");
+                    ui.label(tr("config.mosfet_usage"));
                     let mut ss = scn.mosfet_purpose;
                     egui::ComboBox::from_id_source("mosfet_purpose")
                         .width(200.0)
@@ -345,13 +501,16 @@ impl crate::window::InterfacePage for ConfigPage {
                 }
             });
 
-            if ui.button("Write SCN configuration").clicked() {
+            if !ratios_ok {
+                ui.add(StatusText::Err(tr("config.fix_ratios")));
+            }
+            if ui.add_enabled(ratios_ok && !low_batt, Button::new(tr("config.write_scn"))).clicked() {
                 let res = {
                     let mut x: Vec<u8> = vec![0x3B, 0xFE];
                     x.extend_from_slice(&scn.clone().pack_to_vec().unwrap());
                     self.nag.with_kwp(|server| {
                         server.kwp_set_session(KwpSessionType::Reprogramming.into())?;
-                        server.send_byte_array_with_response(&x)?;
+                        send_with_session_retry(server, KwpSessionType::Reprogramming.into(), &x)?;
                         server.kwp_reset_ecu(ResetType::PowerOnReset.into())?;
                         Ok(())
                     })
@@ -361,12 +520,12 @@ impl crate::window::InterfacePage for ConfigPage {
 
         if let Some(efuse) = self.efuse.borrow_mut() {
             if self.show_efuse {
-                ui.heading("EFUSE CONFIG");
-                ui.label("IMPORTANT! This can only be set once! Be careful!");
+                ui.heading(tr("config.efuse_heading"));
+                ui.label(tr("config.efuse_warning"));
                 ui.spacing();
                 ui.horizontal(|row| {
                     row.vertical(|col| {
-                        col.label("V1.1 - Red PCB (12/12/21)");
+                        col.label(tr("config.pcb_v11"));
                         col.image(
                             self.pcb_11_img.texture_id(col.ctx()),
                             Vec2::from((200.0, 150.0)),
@@ -374,7 +533,7 @@ impl crate::window::InterfacePage for ConfigPage {
                     });
                     row.separator();
                     row.vertical(|col| {
-                        col.label("V1.2 - Black PCB (07/07/22) with TRRS support");
+                        col.label(tr("config.pcb_v12"));
                         col.image(
                             self.pcb_12_img.texture_id(col.ctx()),
                             Vec2::from((200.0, 150.0)),
@@ -382,7 +541,7 @@ impl crate::window::InterfacePage for ConfigPage {
                     });
                     row.separator();
                     row.vertical(|col| {
-                        col.label("V1.3 - Black PCB (12/12/22) - Latest PCB");
+                        col.label(tr("config.pcb_v13"));
                         col.image(
                             self.pcb_13_img.texture_id(col.ctx()),
                             Vec2::from((230.0, 150.0)),
@@ -390,7 +549,7 @@ impl crate::window::InterfacePage for ConfigPage {
                     });
                 });
                 let mut ver = efuse.board_ver;
-                ui.label("Choose board variant: ");
+                ui.label(tr("config.choose_board"));
                 egui::ComboBox::from_id_source("board_ver")
                     .width(100.0)
                     .selected_text(format!("{:?}", efuse.board_ver))
@@ -403,29 +562,33 @@ impl crate::window::InterfacePage for ConfigPage {
                     });
             }
             if self.show_efuse && efuse.board_ver != BoardType::Unknown {
-                if ui.button("Write EFUSE configuration").clicked() {
+                if ui.add_enabled(!low_batt, Button::new(tr("config.write_efuse"))).clicked() {
                     self.show_final_warning = true;
                 }
             }
+            if !self.show_efuse {
+                // Already written, so the section above is hidden for good
+                let date = format!("{:02}/{:02}/{:02}", efuse.manf_day, efuse.manf_month, efuse.manf_year);
+                ui.label(tr_fmt("config.efuse_locked", &[("board", &efuse.board_ver), ("date", &date)]))
+                    .on_hover_text(tr("config.efuse_locked_hint"));
+            }
         }
 
         let mut tmp = self.show_final_warning;
 
         let ss = ui.ctx().input(|x| x.screen_rect());
         let mut reload = false;
-        egui::Window::new("ARE YOU SURE?")
+        egui::Window::new(tr("config.are_you_sure"))
             .open(&mut self.show_final_warning)
             .fixed_pos(Pos2::new(ss.size().x / 2.0, ss.size().y / 2.0))
             .show(ui.ctx(), |win| {
-                win.label("EFUSE CONFIGURATION CANNOT BE UN-DONE");
-                win.label(
-                    "Please double check and ensure you have selected the right board variant!",
-                );
+                win.label(tr("config.efuse_cannot_undo"));
+                win.label(tr("config.efuse_double_check"));
                 win.horizontal(|row| {
-                    if row.button("Take me back").clicked() {
+                    if row.button(tr("config.take_me_back")).clicked() {
                         tmp = false;
                     }
-                    if row.button("Yes, I am sure!").clicked() {
+                    if row.button(tr("config.yes_sure")).clicked() {
                         let mut efuse = self.efuse.clone().unwrap();
                         let date = chrono::Utc::now().date_naive();
                         efuse.manf_day = date.day() as u8;
@@ -436,12 +599,26 @@ impl crate::window::InterfacePage for ConfigPage {
 
                         let mut x = vec![0x3Bu8, 0xFD];
                         x.extend_from_slice(&efuse.pack_to_vec().unwrap());
-                        self.nag.with_kwp(|server| {
+                        let res = self.nag.with_kwp(|server| {
                             server.kwp_set_session(KwpSessionType::Reprogramming.into())?;
                             server.send_byte_array_with_response(&x)?;
                             server.kwp_reset_ecu(ResetType::PowerOnReset.into())?;
                             Ok(())
                         });
+                        match res {
+                            Ok(()) => {
+                                let read_back = Arc::new(Mutex::new(None));
+                                let read_back_t = read_back.clone();
+                                let nag = self.nag.clone();
+                                std::thread::spawn(move || {
+                                    *read_back_t.lock().unwrap() = Some(read_back_efuse(&nag));
+                                });
+                                self.efuse_verify = Some((efuse.board_ver, read_back));
+                            },
+                            Err(e) => {
+                                self.status = StatusText::Err(tr_fmt("config.efuse_write_err", &[("error", &e)]));
+                            }
+                        }
                         tmp = false;
                     }
                 })
@@ -451,10 +628,64 @@ impl crate::window::InterfacePage for ConfigPage {
         }
         self.show_final_warning = tmp;
 
+        if let Some((written, read_back)) = &self.efuse_verify {
+            let res = read_back.lock().unwrap().clone();
+            match res {
+                None => {
+                    ui.label(tr("config.efuse_verifying"));
+                },
+                Some(Ok(efuse)) if efuse.board_ver == *written => {
+                    ui.add(StatusText::Ok(tr_fmt("config.efuse_verify_ok", &[("board", written)])));
+                    self.show_efuse = false;
+                    self.efuse = Some(efuse);
+                },
+                Some(res) => {
+                    let got = match res {
+                        Ok(efuse) => efuse.board_ver.to_string(),
+                        Err(e) => e,
+                    };
+                    ui.label(
+                        RichText::new(tr_fmt("config.efuse_verify_failed", &[("board", written), ("got", &got)]))
+                            .color(Color32::RED)
+                            .strong()
+                            .size(18.0),
+                    );
+                }
+            }
+        }
+
         ui.add(self.status.clone());
+        if let Some(hint) = &self.version_hint {
+            match &*hint.lock().unwrap() {
+                None => {
+                    ui.label(tr("config.version_checking"));
+                },
+                Some(Err(e)) => {
+                    ui.add(StatusText::Err(tr_fmt("config.version_check_err", &[("error", e)])));
+                },
+                Some(Ok(h)) => match &h.release {
+                    Some(r) => {
+                        ui.label(tr_fmt("config.version_match", &[("fw", &h.fw_version), ("app", &r.name)]));
+                        match r.download_url() {
+                            Some(url) => ui.hyperlink_to(tr_fmt("config.version_download", &[("app", &r.name)]), url),
+                            None => ui.hyperlink_to(tr("config.version_release_page"), &r.html_url),
+                        };
+                    },
+                    None => {
+                        ui.label(tr_fmt("config.version_no_match", &[("fw", &h.fw_version)]));
+                    }
+                },
+            }
+        }
         PageAction::None
     }
 
+    fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
+        if let Some(n) = nag {
+            self.nag = (*n).clone();
+        }
+    }
+
     fn get_title(&self) -> &'static str {
         "Configuration"
     }
@@ -462,4 +693,8 @@ impl crate::window::InterfacePage for ConfigPage {
     fn should_show_statusbar(&self) -> bool {
         true
     }
+
+    fn is_dangerous(&self) -> bool {
+        true
+    }
 }
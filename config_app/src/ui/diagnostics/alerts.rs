@@ -0,0 +1,161 @@
+//! Threshold alerts for live values
+//!
+//! Each threshold fires once when its value rises above the limit, and is only
+//! re-armed once the value drops back below the limit (Minus some hysteresis),
+//! so a value hovering around the limit does not spam notifications.
+use std::{
+    collections::VecDeque,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use eframe::egui::{self, DragValue, Ui};
+
+use super::rli::LocalRecordData;
+
+/// Fraction of the limit the value must drop below the limit before the alert re-arms
+const ALERT_HYSTERESIS: f32 = 0.05;
+/// Minimum time between two alerts from the same threshold
+const ALERT_COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertSource {
+    /// ATF temperature in *C (Gearbox sensors)
+    AtfTemp,
+    /// Total solenoid current in A (Solenoid status)
+    SolenoidCurrent,
+    /// Number of OS tasks on the TCU (System usage)
+    TaskCount,
+    /// Increase in OS tasks since the previous sample (System usage)
+    TaskCountJump,
+}
+
+impl AlertSource {
+    fn value_of(&self, data: &LocalRecordData, task_jump: Option<u32>) -> Option<f32> {
+        match (self, data) {
+            // ATF temperature cannot be read with the parking lock engaged
+            (AlertSource::AtfTemp, LocalRecordData::Sensors(s)) if s.parking_lock == 0 => Some(s.atf_temp_c as i32 as f32),
+            (AlertSource::SolenoidCurrent, LocalRecordData::Solenoids(s)) => Some(s.total_current_ma() as f32 / 1000.0),
+            (AlertSource::TaskCount, LocalRecordData::SysUsage(s)) => Some(s.num_tasks as f32),
+            (AlertSource::TaskCountJump, LocalRecordData::SysUsage(_)) => task_jump.map(|j| j as f32),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertThreshold {
+    pub name: &'static str,
+    pub unit: &'static str,
+    pub source: AlertSource,
+    pub enabled: bool,
+    pub limit: f32,
+    tripped: bool,
+    last_fired: Option<Instant>,
+}
+
+impl AlertThreshold {
+    fn new(name: &'static str, unit: &'static str, source: AlertSource, limit: f32) -> Self {
+        Self {
+            name,
+            unit,
+            source,
+            enabled: false,
+            limit,
+            tripped: false,
+            last_fired: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertMonitor {
+    pub thresholds: Vec<AlertThreshold>,
+    /// Ring the terminal bell when an alert fires
+    pub beep: bool,
+    pending: VecDeque<String>,
+    /// Task count of the last system usage sample
+    last_task_count: Option<u32>,
+    /// Last system usage sample was above one of the task count limits, even if that alert is disabled
+    task_anomaly: bool,
+}
+
+impl AlertMonitor {
+    pub fn new() -> Self {
+        Self {
+            thresholds: vec![
+                AlertThreshold::new("ATF temperature", "*C", AlertSource::AtfTemp, 120.0),
+                AlertThreshold::new("Total solenoid current", "A", AlertSource::SolenoidCurrent, 6.0),
+                // Depends on the firmware version, so the limits have to be adjusted to match
+                AlertThreshold::new("OS task count", " tasks", AlertSource::TaskCount, 40.0),
+                AlertThreshold::new("OS task count increase", " tasks", AlertSource::TaskCountJump, 5.0),
+            ],
+            beep: false,
+            pending: VecDeque::new(),
+            last_task_count: None,
+            task_anomaly: false,
+        }
+    }
+
+    /// True if an enabled threshold needs the gearbox sensor record
+    pub fn needs_sensors(&self) -> bool {
+        self.thresholds.iter().any(|t| t.enabled && t.source == AlertSource::AtfTemp)
+    }
+
+    /// True if the last system usage sample had too many tasks, or too many new tasks
+    pub fn task_anomaly(&self) -> bool {
+        self.task_anomaly
+    }
+
+    /// Checks a newly polled record against all enabled thresholds
+    pub fn on_sample(&mut self, data: &LocalRecordData) {
+        let mut task_jump = None;
+        if let LocalRecordData::SysUsage(s) = data {
+            task_jump = self.last_task_count.map(|last| s.num_tasks.saturating_sub(last));
+            self.last_task_count = Some(s.num_tasks);
+            self.task_anomaly = self.thresholds.iter()
+                .filter(|t| matches!(t.source, AlertSource::TaskCount | AlertSource::TaskCountJump))
+                .any(|t| t.source.value_of(data, task_jump).map(|v| v > t.limit).unwrap_or(false));
+        }
+        for t in self.thresholds.iter_mut().filter(|t| t.enabled) {
+            let value = match t.source.value_of(data, task_jump) {
+                Some(v) => v,
+                None => continue,
+            };
+            if value > t.limit {
+                let cooled_down = t.last_fired.map(|i| i.elapsed() > ALERT_COOLDOWN).unwrap_or(true);
+                if !t.tripped && cooled_down {
+                    self.pending.push_back(format!("{} is {:.1}{} (Limit {:.1}{})", t.name, value, t.unit, t.limit, t.unit));
+                    t.last_fired = Some(Instant::now());
+                    if self.beep {
+                        let mut out = std::io::stdout();
+                        let _ = out.write_all(b"\x07");
+                        let _ = out.flush();
+                    }
+                }
+                t.tripped = true;
+            } else if value < t.limit - (t.limit.abs() * ALERT_HYSTERESIS) {
+                t.tripped = false;
+            }
+        }
+    }
+
+    pub fn pop_alert(&mut self) -> Option<String> {
+        self.pending.pop_front()
+    }
+
+    pub fn make_ui(&mut self, ui: &mut Ui) {
+        egui::Grid::new("alert-thresholds").striped(true).show(ui, |ui| {
+            for t in self.thresholds.iter_mut() {
+                ui.checkbox(&mut t.enabled, t.name);
+                ui.label("above");
+                ui.add(DragValue::new(&mut t.limit).speed(0.5).suffix(t.unit));
+                if t.tripped {
+                    ui.label(egui::RichText::new("Exceeded").color(egui::Color32::RED));
+                }
+                ui.end_row();
+            }
+        });
+        ui.checkbox(&mut self.beep, "Beep when an alert fires");
+    }
+}
@@ -0,0 +1,82 @@
+//! Rolling ATF temperature history, with warm-up tracking
+use std::collections::VecDeque;
+
+use eframe::egui::{
+    plot::{HLine, Legend, Line, Plot, PlotPoints},
+    Color32, Ui,
+};
+
+use super::rli::{split_at_gaps, DataGearboxSensors};
+
+/// How long samples are kept for
+pub const ATF_HISTORY_TIME_MS: u128 = 10 * 60 * 1000;
+/// Typical ATF operating band of the 722.6 in *C
+pub const ATF_OPERATING_BAND: (f32, f32) = (80.0, 110.0);
+
+#[derive(Debug, Clone, Default)]
+pub struct AtfHistory {
+    /// (Timestamp ms, temperature). None whilst the temperature cannot be read (Parking lock engaged)
+    samples: VecDeque<(u128, Option<f32>)>,
+    /// Timestamp of the first sample
+    start_ms: Option<u128>,
+    /// Timestamp the gearbox first reached the operating band
+    warm_ms: Option<u128>,
+}
+
+impl AtfHistory {
+    pub fn on_sample(&mut self, timestamp_ms: u128, s: &DataGearboxSensors) {
+        let temp = if s.parking_lock == 0 { Some(s.atf_temp_c as i32 as f32) } else { None };
+        if self.start_ms.is_none() {
+            self.start_ms = Some(timestamp_ms);
+        }
+        if self.warm_ms.is_none() && temp.map(|t| t >= ATF_OPERATING_BAND.0).unwrap_or(false) {
+            self.warm_ms = Some(timestamp_ms);
+        }
+        self.samples.push_back((timestamp_ms, temp));
+        while let Some((ts, _)) = self.samples.front() {
+            if timestamp_ms - ts > ATF_HISTORY_TIME_MS {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Contiguous runs of readable samples, so unreadable periods show up as gaps
+    fn segments(&self) -> Vec<Vec<[f64; 2]>> {
+        split_at_gaps(self.samples.iter().map(|(ts, temp)| [*ts as f64 / 1000.0, temp.map(|t| t as f64).unwrap_or(f64::NAN)]))
+    }
+
+    pub fn make_ui(&self, ui: &mut Ui, now_ms: u128) {
+        match (self.start_ms, self.warm_ms) {
+            (None, _) => {
+                ui.label("Waiting for ATF temperature data");
+            },
+            (Some(start), Some(warm)) => {
+                let secs = (warm - start) / 1000;
+                ui.label(format!("Reached operating temperature after {:02}:{:02}", secs / 60, secs % 60));
+            },
+            (Some(start), None) => {
+                let secs = (now_ms - start) / 1000;
+                ui.label(format!("Warming up for {:02}:{:02}", secs / 60, secs % 60));
+            },
+        }
+        Plot::new("atf-history")
+            .height(200.0)
+            .allow_drag(false)
+            .legend(Legend::default())
+            .include_x((now_ms.saturating_sub(ATF_HISTORY_TIME_MS)) as f64 / 1000.0)
+            .include_x(now_ms as f64 / 1000.0)
+            .include_y(0.0)
+            .include_y(ATF_OPERATING_BAND.1 as f64 + 20.0)
+            .x_axis_formatter(|f, _r| format!("{:02.0}:{:02.0}", (f / 60.0).floor(), f % 60.0))
+            .y_axis_formatter(|f, _r| format!("{f}*C"))
+            .show(ui, |p| {
+                p.hline(HLine::new(ATF_OPERATING_BAND.0).color(Color32::from_rgb(0, 160, 0)).name("Operating band"));
+                p.hline(HLine::new(ATF_OPERATING_BAND.1).color(Color32::from_rgb(0, 160, 0)).name("Operating band"));
+                for seg in self.segments() {
+                    p.line(Line::new(PlotPoints::new(seg)).color(Color32::from_rgb(255, 128, 0)).name("ATF temperature"));
+                }
+            });
+    }
+}
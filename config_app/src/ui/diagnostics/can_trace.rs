@@ -0,0 +1,67 @@
+//! CAN data trace logger
+//!
+//! Writes every polled CAN data sample to a CSV file, with the raw signals as the TCU
+//! reports them followed by the scaled ones. Each row is labelled with the CAN layer
+//! the TCU is configured for, so traces of different cars can be told apart.
+use std::{
+    fs::File,
+    io::Write,
+    path::PathBuf,
+};
+
+use crate::ui::configuration::cfg_structs::EgsCanType;
+
+use super::rli::{DataCanDump, LocalRecordData};
+
+pub struct CanTraceLogger {
+    path: PathBuf,
+    file: File,
+    /// CAN layer name, or "Unknown" if the TCU configuration could not be read
+    can_layer: String,
+    /// Timestamp of the first sample, which the time column counts from
+    start_ms: Option<u128>,
+    sample_count: usize,
+}
+
+impl CanTraceLogger {
+    pub fn new(path: PathBuf, can_type: Option<EgsCanType>) -> std::io::Result<Self> {
+        let file = File::create(&path)?;
+        Ok(Self {
+            path,
+            file,
+            can_layer: can_type.map(|t| format!("{:?}", t)).unwrap_or("Unknown".into()),
+            start_ms: None,
+            sample_count: 0,
+        })
+    }
+
+    pub fn get_path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn get_sample_count(&self) -> usize {
+        self.sample_count
+    }
+
+    /// Appends a row for the sample, writing the header first if this is the first sample
+    pub fn on_sample(&mut self, timestamp_ms: u128, sample: &DataCanDump) -> std::io::Result<()> {
+        let raw = LocalRecordData::Canbus(sample.clone()).fields();
+        let decoded = sample.decoded_signals();
+        let start_ms = match self.start_ms {
+            Some(ms) => ms,
+            None => {
+                let names: Vec<&str> = raw.iter().map(|(k, _)| k.as_str()).chain(decoded.iter().map(|(k, _)| *k)).collect();
+                writeln!(self.file, "Time (s),CAN layer,{}", names.join(","))?;
+                self.start_ms = Some(timestamp_ms);
+                timestamp_ms
+            }
+        };
+        let values: Vec<String> = raw.into_iter()
+            .map(|(_, v)| v)
+            .chain(decoded.into_iter().map(|(_, v)| if v.is_nan() { String::new() } else { format!("{:.2}", v) }))
+            .collect();
+        writeln!(self.file, "{:.3},{},{}", (timestamp_ms - start_ms) as f64 / 1000.0, self.can_layer, values.join(","))?;
+        self.sample_count += 1;
+        Ok(())
+    }
+}
@@ -1,7 +1,8 @@
-use crate::window::{PageAction, StatusBar, get_context};
+use crate::{repaint, vehicle_state};
+use crate::window::{PageAction, StatusBar};
 use backend::diag::Nag52Diag;
 use backend::ecu_diagnostics::kwp2000::{KwpSessionTypeByte, KwpSessionType};
-use eframe::egui::plot::{Legend, Line, Plot};
+use eframe::egui::plot::{Legend, Line, Plot, PlotBounds, Polygon};
 use eframe::egui::{Color32, RichText, Ui, Context};
 use eframe::epaint::Stroke;
 use std::borrow::Borrow;
@@ -13,14 +14,40 @@ use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread;
 use std::time::{Instant, Duration};
 
+pub mod alerts;
+pub mod atf_history;
+pub mod can_trace;
 pub mod data;
+pub mod resample;
 pub mod rli;
+pub mod scope;
+pub mod shift_log;
+pub mod shift_overlay;
+pub mod shift_stats;
+pub mod snapshot;
 pub mod solenoids;
-use crate::ui::diagnostics::rli::{LocalRecordData, RecordIdents};
+pub mod watch;
+use crate::ui::diagnostics::rli::{bad_record_ui, BadRecordResponse, DataGearboxSensors, LocalRecordData, RecordIdents, RecordView, Sentinel, TableContext};
 
-use self::rli::{ChartData, RLI_QUERY_INTERVAL, RLI_PLOT_INTERVAL};
+use crate::ui::configuration::cfg_structs::TcmCoreConfig;
+use packed_struct::PackedStructSlice;
 
-const RLI_CHART_DISPLAY_TIME: u128 = 10000;
+use self::alerts::AlertMonitor;
+use self::atf_history::AtfHistory;
+use self::can_trace::CanTraceLogger;
+use self::scope::Scope;
+use self::shift_log::ShiftLogger;
+use self::shift_overlay::ShiftOverlay;
+use self::shift_stats::ShiftStats;
+use self::snapshot::Snapshot;
+use self::watch::{Watch, PINS_ID};
+
+use self::rli::{ChartData, RLI_QUERY_INTERVAL, RLI_PLOT_INTERVAL, split_at_gaps};
+
+/// Default length of the chart time window
+const RLI_CHART_DISPLAY_TIME: u128 = 30000;
+/// Selectable chart time windows (ms, label)
+const RLI_CHART_WINDOWS: [(u64, &str); 4] = [(10000, "10s"), (30000, "30s"), (60000, "60s"), (300000, "5min")];
 
 pub enum CommandStatus {
     Ok(String),
@@ -36,11 +63,60 @@ pub struct DiagnosticsPage {
     chart_idx: u128,
     read_error: Arc<RwLock<Option<String>>>,
     rli_start_time: Arc<AtomicU64>,
-    launch_time: Instant
+    /// Samples of the selected record read since it was selected
+    sample_count: Arc<AtomicU64>,
+    launch_time: Instant,
+    shift_logger: Arc<RwLock<Option<ShiftLogger>>>,
+    can_trace: Arc<RwLock<Option<CanTraceLogger>>>,
+    /// Shifts seen whilst charting the solenoid pressures
+    shift_overlay: Arc<RwLock<ShiftOverlay>>,
+    shift_stats: Arc<RwLock<ShiftStats>>,
+    /// Keep polling the shift data in the background for the shift statistics
+    track_shifts: Arc<AtomicBool>,
+    disconnected: Arc<AtomicBool>,
+    reconnect_req: Arc<AtomicBool>,
+    /// Stops a running reconnect attempt
+    reconnect_cancel: Arc<AtomicBool>,
+    /// Last gearbox sensor record, needed by other records' tables
+    last_sensors: Arc<RwLock<Option<DataGearboxSensors>>>,
+    /// TCM core config, needed to decode some CAN values
+    tcm_cfg: Arc<RwLock<Option<TcmCoreConfig>>>,
+    alerts: Arc<RwLock<AlertMonitor>>,
+    atf_history: Arc<RwLock<AtfHistory>>,
+    /// Keep polling the gearbox sensors in the background for the ATF history
+    track_atf: Arc<AtomicBool>,
+    scope: Arc<RwLock<Scope>>,
+    watch: Arc<RwLock<Watch>>,
+    /// Length of the chart time window in ms. Older samples are dropped
+    chart_window: Arc<AtomicU64>,
+    /// Snapshot shown in the table whilst frozen. Polling and logging carry on underneath
+    frozen: Option<LocalRecordData>,
+    nag: Nag52Diag,
+    /// Last response of the selected record that failed to parse
+    bad_response: Arc<RwLock<Option<BadRecordResponse>>>,
+    /// Last selected record and chart window have been restored from egui memory
+    view_restored: bool,
+    /// One-off capture of all records
+    snapshot: Option<Snapshot>,
+    /// Snapshot being captured in the background, filled in once every record has been read
+    snapshot_capture: Option<Arc<RwLock<Option<Snapshot>>>>,
 }
 
 impl DiagnosticsPage {
+    fn table_context(&self) -> TableContext {
+        let cfg = self.tcm_cfg.read().unwrap().clone();
+        let sensors = self.last_sensors.read().unwrap().clone();
+        TableContext {
+            v_batt: sensors.as_ref().and_then(|s| s.v_batt.available()),
+            gear: sensors.as_ref().and_then(|s| s.gear()),
+            can_matrix: cfg.as_ref().map(|c| c.egs_can_type),
+            wheel_circumference_mm: cfg.as_ref().map(|c| c.wheel_circumference),
+            task_anomaly: self.alerts.read().unwrap().task_anomaly(),
+        }
+    }
+
     pub fn new(nag: Nag52Diag) -> Self {
+        let nag_c = nag.clone();
         
         let run = Arc::new(AtomicBool::new(true));
         let run_t = run.clone();
@@ -61,6 +137,8 @@ impl DiagnosticsPage {
         let launch_time_t = launch_time.clone();
 
         let rli_start_time = Arc::new(AtomicU64::new(0));
+        let sample_count = Arc::new(AtomicU64::new(0));
+        let sample_count_t = sample_count.clone();
 
         let charting_data = Arc::new(RwLock::new(VecDeque::new()));
         let charting_data_t = charting_data.clone();
@@ -68,21 +146,171 @@ impl DiagnosticsPage {
         let err_text = Arc::new(RwLock::new(None));
         let err_text_t = err_text.clone();
 
+        let shift_logger: Arc<RwLock<Option<ShiftLogger>>> = Arc::new(RwLock::new(None));
+        let shift_logger_t = shift_logger.clone();
+        let can_trace: Arc<RwLock<Option<CanTraceLogger>>> = Arc::new(RwLock::new(None));
+        let can_trace_t = can_trace.clone();
+        let shift_overlay = Arc::new(RwLock::new(ShiftOverlay::default()));
+        let shift_overlay_t = shift_overlay.clone();
+        let shift_stats = Arc::new(RwLock::new(ShiftStats::default()));
+        let shift_stats_t = shift_stats.clone();
+        let track_shifts = Arc::new(AtomicBool::new(false));
+        let track_shifts_t = track_shifts.clone();
+
+        let disconnected = Arc::new(AtomicBool::new(false));
+        let disconnected_t = disconnected.clone();
+        let reconnect_req = Arc::new(AtomicBool::new(false));
+        let reconnect_req_t = reconnect_req.clone();
+        let reconnect_cancel = Arc::new(AtomicBool::new(false));
+        let reconnect_cancel_t = reconnect_cancel.clone();
+
+        let last_sensors: Arc<RwLock<Option<DataGearboxSensors>>> = Arc::new(RwLock::new(None));
+        let last_sensors_t = last_sensors.clone();
+
+        let tcm_cfg: Arc<RwLock<Option<TcmCoreConfig>>> = Arc::new(RwLock::new(None));
+        let tcm_cfg_t = tcm_cfg.clone();
+
+        let alerts = Arc::new(RwLock::new(AlertMonitor::new()));
+        let alerts_t = alerts.clone();
+
+        let atf_history = Arc::new(RwLock::new(AtfHistory::default()));
+        let atf_history_t = atf_history.clone();
+        let track_atf = Arc::new(AtomicBool::new(false));
+        let track_atf_t = track_atf.clone();
+
+        let scope = Arc::new(RwLock::new(Scope::default()));
+        let scope_t = scope.clone();
+        let watch = Arc::new(RwLock::new(Watch::default()));
+        let watch_t = watch.clone();
+
+        let chart_window = Arc::new(AtomicU64::new(RLI_CHART_DISPLAY_TIME as u64));
+        let chart_window_t = chart_window.clone();
+
+        let bad_response: Arc<RwLock<Option<BadRecordResponse>>> = Arc::new(RwLock::new(None));
+        let bad_response_t = bad_response.clone();
+
         let _ = thread::spawn(move || {
             nag.with_kwp(|server| {
                 server.kwp_set_session(KwpSessionTypeByte::Standard(KwpSessionType::Normal))
             });
+            // Needed to decode CAN values whose meaning depends on the CAN matrix or the vehicle
+            if let Ok(cfg) = nag.with_kwp(|server| server.kwp_read_custom_local_identifier(0xFE)) {
+                *tcm_cfg_t.write().unwrap() = TcmCoreConfig::unpack_from_slice(&cfg).ok();
+            }
+            let mut last_v_batt_query = Instant::now();
             while run_t.load(Ordering::Relaxed) {
                 let start = Instant::now();
+                if disconnected_t.load(Ordering::Relaxed) {
+                    // Polling is paused until the user asks us to reconnect
+                    if reconnect_req_t.load(Ordering::Relaxed) {
+                        reconnect_cancel_t.store(false, Ordering::Relaxed);
+                        if nag.reconnect_with_backoff(&reconnect_cancel_t) {
+                            let _ = nag.with_kwp(|server| {
+                                server.kwp_set_session(KwpSessionTypeByte::Standard(KwpSessionType::Normal))
+                            });
+                            *err_text_t.write().unwrap() = None;
+                            disconnected_t.store(false, Ordering::Relaxed);
+                        } else {
+                            *err_text_t.write().unwrap() = Some("Reconnect cancelled".into());
+                        }
+                        reconnect_req_t.store(false, Ordering::Relaxed);
+                        repaint::new_data();
+                    }
+                    std::thread::sleep(Duration::from_millis(RLI_QUERY_INTERVAL));
+                    continue;
+                }
+                // Scope records are polled one per cycle, alongside the selected record
+                let scope_record = scope_t.write().unwrap().next_record();
+                if let Some(rec) = scope_record {
+                    if let Ok(r) = nag.with_kwp(|server| rec.query_ecu(server)) {
+                        alerts_t.write().unwrap().on_sample(&r);
+                        scope_t.write().unwrap().on_sample(launch_time_t.elapsed().as_millis(), rec, &r);
+                        watch_t.write().unwrap().on_sample(rec, &r);
+                    }
+                }
+                // Same for records with pinned fields, unless the scope just read it
+                let watch_record = watch_t.write().unwrap().next_record();
+                if let Some(rec) = watch_record.filter(|r| scope_record != Some(*r)) {
+                    if let Ok(r) = nag.with_kwp(|server| rec.query_ecu(server)) {
+                        alerts_t.write().unwrap().on_sample(&r);
+                        watch_t.write().unwrap().on_sample(rec, &r);
+                    }
+                }
                 if let Some(to_query) = to_query_t.read().unwrap().clone() {
-                    match nag.with_kwp(|server| to_query.query_ecu(server)) {
+                    // Solenoid power needs battery voltage and ATF temperature alerts need the ATF temperature,
+                    // both of which live in the gearbox sensor record
+                    let needs_sensors = to_query == RecordIdents::SolenoidStatus
+                        || alerts_t.read().unwrap().needs_sensors()
+                        || track_atf_t.load(Ordering::Relaxed);
+                    if to_query != RecordIdents::GearboxSensors && needs_sensors && last_v_batt_query.elapsed().as_millis() > 1000 {
+                        if let Ok(r) = nag.with_kwp(|server| RecordIdents::GearboxSensors.query_ecu(server)) {
+                            if let LocalRecordData::Sensors(s) = &r {
+                                *last_sensors_t.write().unwrap() = Some(s.clone());
+                                atf_history_t.write().unwrap().on_sample(launch_time_t.elapsed().as_millis(), s);
+                            }
+                            alerts_t.write().unwrap().on_sample(&r);
+                        }
+                        last_v_batt_query = Instant::now();
+                    }
+                    // The pressure chart is overlaid with shifts, which live in the shift data record
+                    let needs_shifts = to_query == RecordIdents::PressureStatus || track_shifts_t.load(Ordering::Relaxed);
+                    if to_query != RecordIdents::SSData && needs_shifts {
+                        if let Ok(LocalRecordData::ShiftMonitorLive(s)) = nag.with_kwp(|server| RecordIdents::SSData.query_ecu(server)) {
+                            let now = launch_time_t.elapsed().as_millis();
+                            shift_stats_t.write().unwrap().on_sample(now, &s);
+                            if to_query == RecordIdents::PressureStatus {
+                                let mut overlay = shift_overlay_t.write().unwrap();
+                                overlay.on_sample(now, &s);
+                                overlay.trim(now.saturating_sub(chart_window_t.load(Ordering::Relaxed) as u128));
+                            }
+                        }
+                    }
+                    let res = nag.with_kwp(|server| to_query.query_ecu_raw(server)).and_then(|raw| {
+                        to_query.parse(&raw).map_err(|e| {
+                            *bad_response_t.write().unwrap() = Some(BadRecordResponse { record: to_query, raw });
+                            e
+                        })
+                    });
+                    match res {
                         Ok(r) => {
-                            let cd = r.get_chart_data();
+                            sample_count_t.fetch_add(1, Ordering::Relaxed);
+                            alerts_t.write().unwrap().on_sample(&r);
+                            if let LocalRecordData::Sensors(s) = &r {
+                                *last_sensors_t.write().unwrap() = Some(s.clone());
+                                atf_history_t.write().unwrap().on_sample(launch_time_t.elapsed().as_millis(), s);
+                            }
+                            if let LocalRecordData::Canbus(c) = &r {
+                                vehicle_state::update(c);
+                                let mut trace = can_trace_t.write().unwrap();
+                                if let Some(t) = trace.as_mut() {
+                                    if let Err(e) = t.on_sample(launch_time_t.elapsed().as_millis(), c) {
+                                        *err_text_t.write().unwrap() = Some(format!("Could not write CAN trace: {e}"));
+                                        *trace = None;
+                                    }
+                                }
+                            }
+                            if let LocalRecordData::ShiftMonitorLive(s) = &r {
+                                shift_stats_t.write().unwrap().on_sample(launch_time_t.elapsed().as_millis(), s);
+                                let mut logger = shift_logger_t.write().unwrap();
+                                if let Some(l) = logger.as_mut() {
+                                    if let Err(e) = l.on_sample(launch_time_t.elapsed().as_millis(), s) {
+                                        *err_text_t.write().unwrap() = Some(format!("Could not write shift log: {e}"));
+                                        *logger = None;
+                                    }
+                                }
+                            }
+                            if scope_record != Some(to_query) {
+                                scope_t.write().unwrap().on_sample(launch_time_t.elapsed().as_millis(), to_query, &r);
+                            }
+                            watch_t.write().unwrap().on_sample(to_query, &r);
+                            let cd = r.to_chart_data();
                             *store_old_t.write().unwrap() = store_t.read().unwrap().clone();
                             *store_t.write().unwrap() = Some(r);
                             let mut m = charting_data_t.write().unwrap();
-                            m.push_back((launch_time_t.elapsed().as_millis(), cd));
-                            if launch_time_t.elapsed().as_millis() - m[0].0 > 20000 {
+                            let now = launch_time_t.elapsed().as_millis();
+                            m.push_back((now, cd));
+                            let window = chart_window_t.load(Ordering::Relaxed) as u128;
+                            while m.front().map(|(ts, _)| now - ts > window).unwrap_or(false) {
                                 m.pop_front();
                             }
                             drop(m);
@@ -90,11 +318,15 @@ impl DiagnosticsPage {
                                 launch_time_t.elapsed().as_millis() as u64,
                                 Ordering::Relaxed,
                             );
-
+                            repaint::new_data();
                         },
                         Err(e) => {
                             *err_text_t.write().unwrap() = Some(e.to_string());
                             eprintln!("Could not query {}", e);
+                            repaint::new_data();
+                            if !nag.is_connected() {
+                                disconnected_t.store(true, Ordering::Relaxed);
+                            }
                         }
                     }
                 }
@@ -108,7 +340,7 @@ impl DiagnosticsPage {
         let _ = thread::spawn(move || {
             while run_tt.load(Ordering::Relaxed) {
                 let start = Instant::now();
-                get_context().request_repaint();
+                repaint::plot_tick();
                 let taken = start.elapsed().as_millis() as u64;
                 if taken < RLI_PLOT_INTERVAL {
                     std::thread::sleep(Duration::from_millis(RLI_PLOT_INTERVAL - taken));
@@ -125,7 +357,30 @@ impl DiagnosticsPage {
             chart_idx: 0,
             read_error: err_text,
             rli_start_time,
-            launch_time
+            sample_count,
+            launch_time,
+            shift_logger,
+            can_trace,
+            shift_overlay,
+            shift_stats,
+            track_shifts,
+            disconnected,
+            reconnect_req,
+            reconnect_cancel,
+            last_sensors,
+            tcm_cfg,
+            alerts,
+            atf_history,
+            track_atf,
+            scope,
+            watch,
+            chart_window,
+            frozen: None,
+            nag: nag_c,
+            bad_response,
+            view_restored: false,
+            snapshot: None,
+            snapshot_capture: None,
         }
     }
 }
@@ -133,7 +388,36 @@ impl DiagnosticsPage {
 impl crate::window::InterfacePage for DiagnosticsPage {
     fn make_ui(&mut self, ui: &mut Ui, _frame: &eframe::Frame) -> PageAction {
         ui.heading("This is experimental, use with MOST up-to-date firmware");
+        if self.disconnected.load(Ordering::Relaxed) {
+            ui.horizontal(|row| {
+                row.label(RichText::new("Device disconnected").color(Color32::RED).strong());
+                if self.reconnect_req.load(Ordering::Relaxed) {
+                    row.spinner();
+                    row.label("Reconnecting...");
+                    if row.button("Cancel").clicked() {
+                        self.reconnect_cancel.store(true, Ordering::Relaxed);
+                    }
+                } else if row.button("Reconnect").clicked() {
+                    self.reconnect_req.store(true, Ordering::Relaxed);
+                }
+            });
+        }
         ui.add_space(5.0);
+        let record_id = eframe::egui::Id::new("diag-last-record");
+        let window_id = eframe::egui::Id::new("diag-chart-window");
+        if !self.view_restored {
+            self.view_restored = true;
+            let (record, window) = ui.data_mut(|d| (d.get_persisted::<u8>(record_id), d.get_persisted::<u64>(window_id)));
+            if let Some(r) = record.and_then(RecordIdents::from_id) {
+                *self.record_to_query.write().unwrap() = Some(r);
+            }
+            if let Some(w) = window {
+                self.chart_window.store(w, Ordering::Relaxed);
+            }
+            if let Some(pins) = ui.data_mut(|d| d.get_persisted::<Vec<(u8, String, String)>>(eframe::egui::Id::new(PINS_ID))) {
+                self.watch.write().unwrap().load_persisted(pins);
+            }
+        }
         let ui_height = ui.available_height() - 20.0;
         let current_val = self.curr_values.try_read().unwrap().clone();
         let chart_data = self.charting_data.read().unwrap().clone();
@@ -174,48 +458,262 @@ impl crate::window::InterfacePage for DiagnosticsPage {
                 }
 
                 if rli_reset {
+                    if let Some(r) = *self.record_to_query.read().unwrap() {
+                        ui.data_mut(|d| d.insert_persisted(record_id, r as u8));
+                    }
+                    self.frozen = None;
                     self.chart_idx = 0;
                     self.charting_data.write().unwrap().clear();
+                    self.shift_overlay.write().unwrap().clear();
                     *self.curr_values.write().unwrap() = None;
                     *self.prev_values.write().unwrap() = None;
                     self.rli_start_time.store(self.launch_time.elapsed().as_millis() as u64, Ordering::Relaxed);
+                    self.sample_count.store(0, Ordering::Relaxed);
+                }
+
+                if self.record_to_query.read().unwrap().is_some() {
+                    let elapsed_ms = (self.launch_time.elapsed().as_millis() as u64).saturating_sub(self.rli_start_time.load(Ordering::Relaxed));
+                    let samples = self.sample_count.load(Ordering::Relaxed);
+                    let secs = elapsed_ms / 1000;
+                    let rate = if elapsed_ms > 0 { samples as f64 * 1000.0 / elapsed_ms as f64 } else { 0.0 };
+                    ui.label(format!("Capturing for {:02}:{:02}, {} samples ({:.1}/s)", secs / 60, secs % 60, samples, rate))
+                        .on_hover_text(format!("The poll loop aims for {:.1} samples/s", 1000.0 / RLI_QUERY_INTERVAL as f64));
+                }
+
+                if *self.record_to_query.read().unwrap() == Some(RecordIdents::SSData) {
+                    // Only lock whilst reading or swapping the logger, not whilst the file dialog is open,
+                    // as the poll thread writes to it with every sample
+                    let status = self.shift_logger.read().unwrap().as_ref().map(|l| (l.get_path().display().to_string(), l.get_shift_count()));
+                    if let Some((path, count)) = status {
+                        ui.label(format!("Logging shifts to {} ({} shifts recorded)", path, count));
+                        if ui.button("Stop shift log").clicked() {
+                            *self.shift_logger.write().unwrap() = None;
+                        }
+                    } else if ui.button("Start shift log").clicked() {
+                        if let Some(p) = rfd::FileDialog::new().add_filter("JSON lines", &["jsonl"]).save_file() {
+                            match ShiftLogger::new(p) {
+                                Ok(l) => *self.shift_logger.write().unwrap() = Some(l),
+                                Err(e) => *self.read_error.write().unwrap() = Some(format!("Could not create shift log: {e}")),
+                            }
+                        }
+                    }
                 }
 
+                if *self.record_to_query.read().unwrap() == Some(RecordIdents::CanDataDump) {
+                    let status = self.can_trace.read().unwrap().as_ref().map(|t| (t.get_path().display().to_string(), t.get_sample_count()));
+                    if let Some((path, count)) = status {
+                        ui.label(format!("Tracing CAN data to {} ({} samples)", path, count));
+                        if ui.button("Stop CAN trace").clicked() {
+                            *self.can_trace.write().unwrap() = None;
+                        }
+                    } else if ui.button("Start CAN trace")
+                        .on_hover_text("Writes every CAN data sample to a CSV file, for comparing against the expected EGS frames")
+                        .clicked()
+                    {
+                        if let Some(p) = rfd::FileDialog::new().add_filter("csv", &["csv"]).set_file_name("can_trace.csv").save_file() {
+                            let can_type = self.tcm_cfg.read().unwrap().as_ref().map(|c| c.egs_can_type);
+                            match CanTraceLogger::new(p, can_type) {
+                                Ok(t) => *self.can_trace.write().unwrap() = Some(t),
+                                Err(e) => *self.read_error.write().unwrap() = Some(format!("Could not create CAN trace: {e}")),
+                            }
+                        }
+                    }
+                }
+
+                ui.collapsing("Watch panel", |ui| {
+                    let selected = self.record_to_query.read().unwrap().zip(current_val.as_ref());
+                    let mut watch = self.watch.write().unwrap();
+                    if watch.make_ui(ui, selected) {
+                        let pins = watch.to_persisted();
+                        ui.data_mut(|d| d.insert_persisted(eframe::egui::Id::new(PINS_ID), pins));
+                    }
+                });
+                ui.collapsing("Snapshot of all records", |ui| {
+                    let captured = self.snapshot_capture.as_ref().and_then(|c| c.write().unwrap().take());
+                    if captured.is_some() {
+                        self.snapshot = captured;
+                        self.snapshot_capture = None;
+                    }
+                    ui.horizontal(|row| {
+                        if row.add_enabled(self.snapshot_capture.is_none(), eframe::egui::Button::new("Take snapshot"))
+                            .on_hover_text("Reads every record once, back to back")
+                            .clicked()
+                        {
+                            let capture = Arc::new(RwLock::new(None));
+                            let capture_t = capture.clone();
+                            let nag = self.nag.clone();
+                            thread::spawn(move || {
+                                *capture_t.write().unwrap() = Some(Snapshot::capture(&nag));
+                                get_context().request_repaint();
+                            });
+                            self.snapshot_capture = Some(capture);
+                        }
+                        if self.snapshot_capture.is_some() {
+                            row.spinner();
+                            row.label("Reading records...");
+                        }
+                        if let Some(snapshot) = &self.snapshot {
+                            if row.button("Export").clicked() {
+                                if let Some(p) = rfd::FileDialog::new().add_filter("Text", &["txt"]).save_file() {
+                                    if let Err(e) = snapshot.export(&p) {
+                                        *self.read_error.write().unwrap() = Some(format!("Could not export snapshot: {e}"));
+                                    }
+                                }
+                            }
+                            if row.button("Export JSON")
+                                .on_hover_text("Field names and raw values of every record, with the TCU identification")
+                                .clicked()
+                            {
+                                if let Some(p) = rfd::FileDialog::new().add_filter("JSON", &["json"]).save_file() {
+                                    if let Err(e) = snapshot.export_json(&p) {
+                                        *self.read_error.write().unwrap() = Some(format!("Could not export snapshot: {e}"));
+                                    }
+                                }
+                            }
+                        }
+                    });
+                    if let Some(snapshot) = &self.snapshot {
+                        snapshot.make_ui(ui, &self.table_context());
+                    }
+                });
+                ui.collapsing("Alerts", |ui| {
+                    self.alerts.write().unwrap().make_ui(ui);
+                });
+                let atf_resp = ui.collapsing("ATF temperature history", |ui| {
+                    self.atf_history.read().unwrap().make_ui(ui, self.launch_time.elapsed().as_millis());
+                });
+                // Only keep reading the sensors in the background whilst the chart is open
+                self.track_atf.store(atf_resp.body_returned.is_some(), Ordering::Relaxed);
+                let stats_resp = ui.collapsing("Shift time statistics", |ui| {
+                    self.shift_stats.write().unwrap().make_ui(ui);
+                });
+                self.track_shifts.store(stats_resp.body_returned.is_some(), Ordering::Relaxed);
+                let mut window = self.chart_window.load(Ordering::Relaxed);
+                ui.horizontal(|ui| {
+                    ui.label("Chart time window:");
+                    for (ms, label) in RLI_CHART_WINDOWS {
+                        ui.selectable_value(&mut window, ms, label);
+                    }
+                });
+                self.chart_window.store(window, Ordering::Relaxed);
+                ui.data_mut(|d| d.insert_persisted(window_id, window));
+                ui.collapsing("Multi-channel scope", |ui| {
+                    let mut scope = self.scope.write().unwrap();
+                    scope.set_window(window as u128);
+                    scope.make_ui(ui, self.launch_time.elapsed().as_millis());
+                });
+
                 if let Some(e) = self.read_error.read().unwrap().clone() {
                     ui.label(RichText::new(format!("Error querying ECU: {e}")).color(Color32::RED));
                 }
-                if let Some(data) = current_val.clone() {
-                    data.to_table(ui);
+                let bad_response = self.bad_response.read().unwrap().clone();
+                if let Some(bad) = bad_response {
+                    ui.collapsing("Debug: Last unreadable response", |ui| {
+                        bad_record_ui(&bad, ui);
+                        if ui.button("Clear").clicked() {
+                            *self.bad_response.write().unwrap() = None;
+                        }
+                    });
+                }
+                match &self.frozen {
+                    Some(_) => {
+                        if ui.button("Unfreeze table").clicked() {
+                            self.frozen = None;
+                        }
+                    },
+                    None => {
+                        if ui.add_enabled(current_val.is_some(), eframe::egui::Button::new("Freeze table"))
+                            .on_hover_text("Holds the current values in the table. Charts and logging keep updating")
+                            .clicked()
+                        {
+                            self.frozen = current_val.clone();
+                        }
+                    }
+                }
+                if let Some(data) = self.frozen.clone().or_else(|| current_val.clone()) {
+                    data.to_table(ui, &self.table_context());
                 }
             });
             if let Some(data) = current_val {
                 ui.vertical(|col| {
                     let start_time = self.rli_start_time.load(Ordering::Relaxed);
                     let legend = Legend::default().position(eframe::egui::plot::Corner::LeftTop);
-                    let space_per_chart = (ui_height / data.get_chart_data().len() as f32) - (10.0 * data.get_chart_data().len() as f32);
+                    let space_per_chart = (ui_height / data.to_chart_data().len() as f32) - (10.0 * data.to_chart_data().len() as f32);
                     
-                    for (idx, d) in data.get_chart_data().iter().enumerate() {
+                    for (idx, d) in data.to_chart_data().iter().enumerate() {
                         let mut lines = Vec::new();
-                        col.heading(d.group_name.clone());
+                        let mut extent: Option<(f64, f64)> = None;
                         let mut unit: Option<&'static str> =  d.data[0].2.clone();
                         for (i, (key, _, _)) in d.data.iter().enumerate() {
                             let mut points: Vec<[f64; 2]> = Vec::new();
                             for (timestamp, point) in chart_data.iter() {
-                                points.push([*timestamp as f64 - start_time as f64, point[idx].data[i].1 as f64])
+                                let y = point[idx].data[i].1 as f64;
+                                if !y.is_nan() {
+                                    extent = Some(extent.map_or((y, y), |(lo, hi)| (lo.min(y), hi.max(y))));
+                                }
+                                points.push([*timestamp as f64 - start_time as f64, y])
                             }
                             let mut key_hasher = DefaultHasher::default();
                             key.hash(&mut key_hasher);
                             let r = key_hasher.finish();
-                            lines.push(Line::new(points).name(key.clone()).stroke(Stroke::new(2.0, 
-                                Color32::from_rgb(
-                                    (r & 0xFF) as u8,
-                                    ((r >> 8) & 0xFF) as u8,
-                                    ((r >> 16) & 0xFF) as u8,
-                                ))))
+                            for seg in split_at_gaps(points) {
+                                lines.push(Line::new(seg).name(key.clone()).stroke(Stroke::new(2.0, 
+                                    Color32::from_rgb(
+                                        (r & 0xFF) as u8,
+                                        ((r >> 8) & 0xFF) as u8,
+                                        ((r >> 16) & 0xFF) as u8,
+                                    ))))
+                            }
+                        }
+
+                        // Drawn as one quad per pair of samples, as egui can only fill convex polygons
+                        let mut fill_quads = Vec::new();
+                        if let Some((fill_name, _, _)) = &d.fill {
+                            let mut prev: Option<(f64, f64, f64)> = None;
+                            for (timestamp, point) in chart_data.iter() {
+                                let cur = point[idx].fill.as_ref()
+                                    .filter(|(_, lo, hi)| !lo.is_nan() && !hi.is_nan())
+                                    .map(|(_, lo, hi)| (*timestamp as f64 - start_time as f64, *lo as f64, *hi as f64));
+                                if let (Some((px, plo, phi)), Some((x, lo, hi))) = (prev, cur) {
+                                    fill_quads.push(Polygon::new(vec![[px, plo], [px, phi], [x, hi], [x, lo]])
+                                        .name(fill_name.clone())
+                                        .color(Color32::from_rgb(200, 60, 60))
+                                        .fill_alpha(0.3)
+                                        .stroke(Stroke::NONE));
+                                }
+                                prev = cur;
+                            }
+                        }
+
+                        // Y axis lock (Enabled, min, max), remembered per chart
+                        let lock_id = eframe::egui::Id::new(("diag-axis-lock", d.group_name.clone()));
+                        let (mut locked, mut y_min, mut y_max) = col.data_mut(|m| m.get_persisted::<(bool, f64, f64)>(lock_id))
+                            .unwrap_or_else(|| {
+                                let (lo, hi) = d.bounds.map(|(lo, hi)| (lo as f64, hi as f64)).or(extent).unwrap_or((0.0, 100.0));
+                                (false, lo, hi)
+                            });
+                        let fill_id = eframe::egui::Id::new(("diag-fill", d.group_name.clone()));
+                        let mut show_fill = col.data_mut(|m| m.get_persisted::<bool>(fill_id)).unwrap_or(true);
+                        col.horizontal(|row| {
+                            row.heading(d.group_name.clone());
+                            if let Some((fill_name, _, _)) = &d.fill {
+                                row.checkbox(&mut show_fill, format!("Shade {}", fill_name.to_lowercase()));
+                            }
+                            row.checkbox(&mut locked, "Lock axis")
+                                .on_hover_text("Keeps the Y axis fixed, so spikes do not rescale the chart");
+                            if locked {
+                                row.add(eframe::egui::DragValue::new(&mut y_min).prefix("Min: ").speed(1.0));
+                                row.add(eframe::egui::DragValue::new(&mut y_max).prefix("Max: ").speed(1.0));
+                            }
+                        });
+                        if y_max <= y_min {
+                            y_max = y_min + 1.0;
                         }
+                        col.data_mut(|m| m.insert_persisted(lock_id, (locked, y_min, y_max)));
+                        col.data_mut(|m| m.insert_persisted(fill_id, show_fill));
 
                         let now = self.launch_time.elapsed().as_millis() - start_time as u128;
-                        let mut last_bound = now as f64 - 20000.0;
+                        let mut last_bound = now as f64 - self.chart_window.load(Ordering::Relaxed) as f64;
                         if last_bound < 0.0 {
                             last_bound = 0.0;
                         }
@@ -247,17 +745,38 @@ impl crate::window::InterfacePage for DiagnosticsPage {
                             }
                         }
                         plot.show(col, |f| {
+                            if locked {
+                                f.set_plot_bounds(PlotBounds::from_min_max([last_bound, y_min], [now as f64, y_max]));
+                            }
+                            if show_fill {
+                                for quad in fill_quads {
+                                    f.polygon(quad);
+                                }
+                            }
                             for line in lines {
                                 f.line(line);
                             }
+                            if let LocalRecordData::Pressures(_) = &data {
+                                let (lo, hi) = if locked { (y_min, y_max) } else { extent.unwrap_or((y_min, y_max)) };
+                                self.shift_overlay.read().unwrap().draw(f, start_time as u128, now + start_time as u128, lo, hi);
+                            }
                         });
                     }
                 });
             }
         });
+        if let Some(alert) = self.alerts.write().unwrap().pop_alert() {
+            return PageAction::SendNotification { text: alert, kind: egui_toast::ToastKind::Warning };
+        }
         PageAction::None
     }
 
+    fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
+        if let Some(n) = nag {
+            self.nag = (*n).clone();
+        }
+    }
+
     fn get_title(&self) -> &'static str {
         "Ultimate-NAG52 diagnostics"
     }
@@ -270,5 +789,7 @@ impl crate::window::InterfacePage for DiagnosticsPage {
 impl Drop for DiagnosticsPage {
     fn drop(&mut self) {
         self.query_ecu.store(false, Ordering::Relaxed);
+        // Don't leave the polling thread retrying forever in the background
+        self.reconnect_cancel.store(true, Ordering::Relaxed);
     }
 }
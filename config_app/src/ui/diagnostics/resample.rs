@@ -0,0 +1,106 @@
+//! Aligns channels sampled at different times onto one time grid, for CSV export
+//!
+//! Resampling is sample-and-hold (zero order hold): each grid time takes the channel's
+//! most recent sample at or before it. Nothing is interpolated between samples, so
+//! every value in the output is one the TCU actually reported. Cells are left empty
+//! before a channel's first sample, and once its last sample is older than `max_hold_ms`
+//! (So a record that stopped being polled does not look like a flat line).
+use std::{fs::File, io::Write, path::Path};
+
+/// A named series of `[timestamp ms, value]` points, in time order
+pub struct Channel<'a> {
+    pub name: String,
+    pub points: &'a [[f64; 2]],
+}
+
+/// Channels resampled onto a common grid
+pub struct Resampled {
+    pub names: Vec<String>,
+    /// Grid timestamps in ms
+    pub times: Vec<f64>,
+    /// One row per grid timestamp, one column per channel
+    pub rows: Vec<Vec<Option<f64>>>,
+}
+
+/// Resamples `channels` onto a grid of `step_ms`, covering all of their samples
+pub fn resample_hold(channels: &[Channel], step_ms: f64, max_hold_ms: f64) -> Resampled {
+    let names = channels.iter().map(|c| c.name.clone()).collect();
+    let first = channels.iter().filter_map(|c| c.points.first()).map(|p| p[0]).fold(f64::INFINITY, f64::min);
+    let last = channels.iter().filter_map(|c| c.points.last()).map(|p| p[0]).fold(f64::NEG_INFINITY, f64::max);
+    if !first.is_finite() || step_ms <= 0.0 {
+        return Resampled { names, times: Vec::new(), rows: Vec::new() };
+    }
+    let mut times = Vec::new();
+    let mut t = first;
+    while t <= last {
+        times.push(t);
+        t += step_ms;
+    }
+    // Index of the next unused sample of each channel, as the grid only moves forward
+    let mut cursors = vec![0usize; channels.len()];
+    let rows = times
+        .iter()
+        .map(|t| {
+            channels
+                .iter()
+                .zip(cursors.iter_mut())
+                .map(|(c, cur)| {
+                    while *cur < c.points.len() && c.points[*cur][0] <= *t {
+                        *cur += 1;
+                    }
+                    let held = c.points[..*cur].last()?;
+                    (t - held[0] <= max_hold_ms).then_some(held[1])
+                })
+                .collect()
+        })
+        .collect();
+    Resampled { names, times, rows }
+}
+
+impl Resampled {
+    /// Writes a CSV with a time column (Seconds since `start_ms`) followed by one column per channel
+    pub fn write_csv(&self, path: &Path, start_ms: f64) -> std::io::Result<()> {
+        let mut f = File::create(path)?;
+        let header: Vec<String> = std::iter::once("Time (s)".to_string())
+            .chain(self.names.iter().map(|n| format!("\"{}\"", n.replace('"', "\"\""))))
+            .collect();
+        writeln!(f, "{}", header.join(","))?;
+        for (t, row) in self.times.iter().zip(&self.rows) {
+            let cells: Vec<String> = std::iter::once(format!("{:.3}", (t - start_ms) / 1000.0))
+                .chain(row.iter().map(|v| v.map(|v| v.to_string()).unwrap_or_default()))
+                .collect();
+            writeln!(f, "{}", cells.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_last_value_onto_grid() {
+        let a = [[0.0, 1.0], [150.0, 2.0]];
+        let b = [[60.0, 10.0]];
+        let res = resample_hold(
+            &[Channel { name: "a".into(), points: &a }, Channel { name: "b".into(), points: &b }],
+            100.0,
+            1000.0,
+        );
+        assert_eq!(res.times, vec![0.0, 100.0]);
+        assert_eq!(res.rows, vec![vec![Some(1.0), None], vec![Some(1.0), Some(10.0)]]);
+    }
+
+    #[test]
+    fn stale_values_are_dropped() {
+        let a = [[0.0, 1.0], [500.0, 2.0]];
+        let b = [[0.0, 5.0]];
+        let res = resample_hold(
+            &[Channel { name: "a".into(), points: &a }, Channel { name: "b".into(), points: &b }],
+            250.0,
+            300.0,
+        );
+        assert_eq!(res.rows.iter().map(|r| r[1]).collect::<Vec<_>>(), vec![Some(5.0), Some(5.0), None]);
+    }
+}
@@ -1,83 +1,69 @@
-//! Read data by local identifier data structures
-//! Based on diag_data.h in TCM source code
+//! Tables and charts of the records in [backend::diag::records]
 //!
-use backend::ecu_diagnostics::dynamic_diag::DynamicDiagSession;
-use backend::ecu_diagnostics::{DiagError, DiagServerResult};
-use eframe::egui::{self, Color32, InnerResponse, RichText, Ui};
-use packed_struct::PackedStructSlice;
-use packed_struct::prelude::{PackedStruct, PrimitiveEnum_u8};
+//! The records themselves (And reading them) live in the backend, so they can be used
+//! without egui. They are re-exported here so the diagnostic pages can keep using `rli::`.
+pub use backend::diag::records::*;
+use eframe::egui::{self, Color32, Event, InnerResponse, Label, Response, RichText, Sense, Ui, WidgetText};
+use packed_struct::prelude::PrimitiveEnum;
+
+use crate::ui::configuration::cfg_structs::EgsCanType;
 
 pub const RLI_QUERY_INTERVAL: u64 = 100;
 pub const RLI_PLOT_INTERVAL: u64 = 1000/60;
 
-#[repr(u8)]
-#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
-pub enum RecordIdents {
-    GearboxSensors = 0x20,
-    SolenoidStatus = 0x21,
-    CanDataDump = 0x22,
-    SysUsage = 0x23,
-    PressureStatus = 0x25,
-    SSData = 0x27,
-    ClutchSpeeds = 0x30,
-    ClutchVelocities = 0x31,
-}
-
-
-pub(crate) fn read_struct<T>(c: &[u8]) -> DiagServerResult<T>
-where
-    T: PackedStruct,
-{
-    T::unpack_from_slice(&c).map_err(|e| DiagError::InvalidResponseLength)
+/// Table and chart rendering of a record
+pub trait RecordView {
+    fn to_table(&self, ui: &mut Ui, ctx: &TableContext) -> InnerResponse<()>;
+    fn to_chart_data(&self) -> Vec<ChartData>;
 }
 
-impl RecordIdents {
-    pub fn query_ecu(
-        &self,
-        server: &DynamicDiagSession,
-    ) -> DiagServerResult<LocalRecordData> {
-        let resp = server.kwp_read_custom_local_identifier(*self as u8)?;
-        match self {
-            Self::GearboxSensors => Ok(LocalRecordData::Sensors(read_struct(&resp)?)),
-            Self::SolenoidStatus => Ok(LocalRecordData::Solenoids(read_struct(&resp)?)),
-            Self::CanDataDump => Ok(LocalRecordData::Canbus(read_struct(&resp)?)),
-            Self::SysUsage => Ok(LocalRecordData::SysUsage(read_struct(&resp)?)),
-            Self::PressureStatus => Ok(LocalRecordData::Pressures(read_struct(&resp)?)),
-            Self::SSData => Ok(LocalRecordData::ShiftMonitorLive(read_struct(&resp)?)),
-            Self::ClutchSpeeds => Ok(LocalRecordData::ClutchSpeeds(read_struct(&resp)?)),
-            Self::ClutchVelocities => Ok(LocalRecordData::ClutchVelocities(read_struct(&resp)?))
-        }
-    }
+/// Shows what the TCU sent for a record that could not be parsed
+pub fn bad_record_ui(bad: &BadRecordResponse, ui: &mut Ui) {
+    egui::Grid::new("bad-record").striped(true).show(ui, |ui| {
+        ui.label("Record");
+        copy_cell(ui, format!("{:?} (0x{:02X})", bad.record, bad.record as u8));
+        ui.end_row();
+        ui.label("Response length");
+        copy_cell(ui, make_text(
+            format!("{} bytes, expected {} bytes", bad.raw.len(), bad.record.expected_len()),
+            bad.raw.len() != bad.record.expected_len(),
+        ));
+        ui.end_row();
+    });
+    let hex = bad.raw.iter().map(|b| format!("{b:02X}")).collect::<Vec<_>>().join(" ");
+    ui.label(RichText::new(hex).monospace());
 }
 
-#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
-pub enum LocalRecordData {
-    Sensors(DataGearboxSensors),
-    Solenoids(DataSolenoids),
-    Canbus(DataCanDump),
-    SysUsage(DataSysUsage),
-    Pressures(DataPressures),
-    ShiftMonitorLive(DataShiftManager),
-    ClutchSpeeds(DataClutchSpeeds),
-    ClutchVelocities(DataShiftClutchVelocity),
+/// Values from other records that a table can use to show derived data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableContext {
+    /// Last known battery voltage in mV (From [DataGearboxSensors])
+    pub v_batt: Option<u16>,
+    /// CAN matrix the TCU is configured for (From the TCM core config)
+    pub can_matrix: Option<EgsCanType>,
+    /// Wheel circumference in mm (From the TCM core config)
+    pub wheel_circumference_mm: Option<u16>,
+    /// Gear the box is in, from the last calculated ratio (From [DataGearboxSensors])
+    pub gear: Option<u8>,
+    /// OS task count is above its limit or jumped (From the task count alerts)
+    pub task_anomaly: bool,
 }
 
-impl LocalRecordData {
-    pub fn to_table(&self, ui: &mut Ui) -> InnerResponse<()> {
+impl RecordView for LocalRecordData {
+    fn to_table(&self, ui: &mut Ui, ctx: &TableContext) -> InnerResponse<()> {
         match &self {
-            LocalRecordData::Sensors(s) => s.to_table(ui),
-            LocalRecordData::Solenoids(s) => s.to_table(ui),
-            LocalRecordData::Canbus(s) => s.to_table(ui),
-            LocalRecordData::SysUsage(s) => s.to_table(ui),
-            LocalRecordData::Pressures(s) => s.to_table(ui),
-            LocalRecordData::ShiftMonitorLive(s) => s.to_table(ui),
-            LocalRecordData::ClutchSpeeds(s) => s.to_table(ui),
-            LocalRecordData::ClutchVelocities(s) => s.to_table(ui),
-            _ => egui::Grid::new("DGS").striped(true).show(ui, |ui| {}),
+            LocalRecordData::Sensors(s) => s.to_table(ui, ctx),
+            LocalRecordData::Solenoids(s) => s.to_table(ui, ctx),
+            LocalRecordData::Canbus(s) => s.to_table(ui, ctx),
+            LocalRecordData::SysUsage(s) => s.to_table(ui, ctx),
+            LocalRecordData::Pressures(s) => s.to_table(ui, ctx),
+            LocalRecordData::ShiftMonitorLive(s) => s.to_table(ui, ctx),
+            LocalRecordData::ClutchSpeeds(s) => s.to_table(ui, ctx),
+            LocalRecordData::ClutchVelocities(s) => s.to_table(ui, ctx),
         }
     }
 
-    pub fn get_chart_data(&self) -> Vec<ChartData> {
+    fn to_chart_data(&self) -> Vec<ChartData> {
         match &self {
             LocalRecordData::Sensors(s) => s.to_chart_data(),
             LocalRecordData::Solenoids(s) => s.to_chart_data(),
@@ -87,83 +73,39 @@ impl LocalRecordData {
             LocalRecordData::ShiftMonitorLive(s) => s.to_chart_data(),
             LocalRecordData::ClutchSpeeds(s) => s.to_chart_data(),
             LocalRecordData::ClutchVelocities(s) => s.to_chart_data(),
-            _ => vec![],
         }
     }
 }
 
-#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct)]
-#[packed_struct(endian="lsb")]
-pub struct DataPressures {
-    pub spc_pwm: u16,
-    pub mpc_pwm: u16,
-    pub tcc_pwm: u16,
-    pub ss_flag: u8,
-    pub spc_sol_pressure: u16,
-    pub mpc_sol_pressure: u16,
-    pub spc_clutch_pressure: u16,
-    pub mpc_clutch_pressure: u16,
-    pub tcc_clutch_pressure: u16,
-    pub line_pressure: u16
-}
-
-impl DataPressures {
-    pub fn to_table(&self, ui: &mut Ui) -> InnerResponse<()> {
+impl RecordView for DataPressures {
+    fn to_table(&self, ui: &mut Ui, _ctx: &TableContext) -> InnerResponse<()> {
         egui::Grid::new("DGS").striped(true).show(ui, |ui| {
             ui.label("Shift solenoid pressure");
-            ui.label(if self.spc_sol_pressure == u16::MAX {
-                make_text("ERROR", true)
-            } else {
-                make_text(format!("{} mBar", self.spc_sol_pressure), false)
-            });
+            copy_cell(ui, sna_text(self.spc_sol_pressure, |v| format!("{} mBar", v)));
             ui.end_row();
 
             ui.label("Modulating solenoid pressure");
-            ui.label(if self.mpc_sol_pressure == u16::MAX {
-                make_text("ERROR", true)
-            } else {
-                make_text(format!("{} mBar", self.mpc_sol_pressure), false)
-            });
+            copy_cell(ui, sna_text(self.mpc_sol_pressure, |v| format!("{} mBar", v)));
             ui.end_row();
 
             ui.label("Torque converter solenoid pressure");
-            ui.label(if self.tcc_clutch_pressure == u16::MAX {
-                make_text("ERROR", true)
-            } else {
-                make_text(format!("{} mBar", self.tcc_clutch_pressure), false)
-            });
+            copy_cell(ui, sna_text(self.tcc_clutch_pressure, |v| format!("{} mBar", v)));
             ui.end_row();
 
             ui.label("Modulating solenoid clutch apply pressure");
-            ui.label(if self.mpc_clutch_pressure == u16::MAX {
-                make_text("ERROR", true)
-            } else {
-                make_text(format!("{} mBar", self.mpc_clutch_pressure), false)
-            });
+            copy_cell(ui, sna_text(self.mpc_clutch_pressure, |v| format!("{} mBar", v)));
             ui.end_row();
 
             ui.label("Shift solenoid clutch apply pressure");
-            ui.label(if self.spc_clutch_pressure == u16::MAX {
-                make_text("ERROR", true)
-            } else if self.ss_flag != 0 {
-                make_text(format!("{} mBar", self.spc_clutch_pressure), false)
-            } else {
-                make_text("0 mBar", false)
-            });
+            copy_cell(ui, sna_text(self.spc_clutch_pressure, |v| format!("{} mBar", if self.ss_flag != 0 { v } else { 0 })));
             ui.end_row();
 
             ui.label("Line pressure");
-            ui.label(if self.line_pressure == u16::MAX {
-                make_text("ERROR", true)
-            } else if self.ss_flag != 0 {
-                make_text(format!("{} mBar", self.line_pressure), false)
-            } else {
-                make_text("0 mBar", false)
-            });
+            copy_cell(ui, sna_text(self.line_pressure, |v| format!("{} mBar", if self.ss_flag != 0 { v } else { 0 })));
             ui.end_row();
 
             ui.label("Active shift circuits");
-            ui.label(if self.ss_flag == 0 {
+            copy_cell(ui, if self.ss_flag == 0 {
                 make_text("None", false)
             } else {
                 let mut s: Vec<&'static str> = Vec::new();
@@ -182,7 +124,7 @@ impl DataPressures {
         })
     }
 
-    pub fn to_chart_data(&self) -> Vec<ChartData> {
+    fn to_chart_data(&self) -> Vec<ChartData> {
         vec![ChartData::new(
             "Gearbox Pressures".into(),
             vec![
@@ -198,19 +140,6 @@ impl DataPressures {
     }
 }
 
-#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct)]
-#[packed_struct(endian="lsb")]
-pub struct DataGearboxSensors {
-    pub n2_rpm: u16,
-    pub n3_rpm: u16,
-    pub calculated_rpm: u16,
-    pub calc_ratio: u16,
-    pub v_batt: u16,
-    pub atf_temp_c: u32,
-    pub parking_lock: u8,
-    pub output_rpm: u16
-}
-
 fn make_text<T: Into<String>>(t: T, e: bool) -> egui::RichText {
     let mut s = RichText::new(t);
     if e {
@@ -219,64 +148,67 @@ fn make_text<T: Into<String>>(t: T, e: bool) -> egui::RichText {
     s
 }
 
-impl DataGearboxSensors {
-    pub fn to_table(&self, ui: &mut Ui) -> InnerResponse<()> {
+/// Value cell of a table. Clicking it, or pressing Ctrl+C whilst hovering it, copies the
+/// value as shown to the clipboard
+pub fn copy_cell(ui: &mut Ui, text: impl Into<WidgetText>) -> Response {
+    let text = text.into();
+    let plain = text.text().to_string();
+    let resp = ui.add(Label::new(text).sense(Sense::click())).on_hover_text("Click or Ctrl+C to copy");
+    let copy_key = resp.hovered() && ui.input(|i| i.events.contains(&Event::Copy));
+    if resp.clicked() || copy_key {
+        ui.output_mut(|o| o.copied_text = plain);
+    }
+    resp
+}
+
+/// Table text for a value that may not be available. Unavailable values are
+/// all shown the same way, otherwise `f` formats the value
+pub fn sna_text<T: Sentinel, F: FnOnce(T) -> String>(v: T, f: F) -> egui::RichText {
+    match v.available() {
+        Some(v) => make_text(f(v), false),
+        None => make_text("N/A", true),
+    }
+}
+
+/// Chart value for a value that may not be available. Unavailable values leave a gap in the chart
+pub fn sna_point<T: Sentinel + Into<f32>>(v: T) -> f32 {
+    v.available().map_or(f32::NAN, |v| v.into())
+}
+
+impl RecordView for DataGearboxSensors {
+    fn to_table(&self, ui: &mut Ui, _ctx: &TableContext) -> InnerResponse<()> {
         egui::Grid::new("DGS").striped(true).show(ui, |ui| {
             ui.label("N2 Pulse counter")
                 .on_hover_text("Raw counter value for PCNT for N2 hall effect RPM sensor");
-            ui.label(if self.n2_rpm == u16::MAX {
-                make_text("ERROR", true)
-            } else {
-                make_text(format!("{} pulses/min", self.n2_rpm), false)
-            });
+            copy_cell(ui, sna_text(self.n2_rpm, |v| format!("{} pulses/min", v)));
             ui.end_row();
 
             ui.label("N3 Pulse counter")
                 .on_hover_text("Raw counter value for PCNT for N3 hall effect RPM sensor");
-            ui.label(if self.n3_rpm == u16::MAX {
-                make_text("ERROR", true)
-            } else {
-                make_text(format!("{} pulses/min", self.n3_rpm), false)
-            });
+            copy_cell(ui, sna_text(self.n3_rpm, |v| format!("{} pulses/min", v)));
             ui.end_row();
 
             ui.label("Calculated input RPM")
                 .on_hover_text("Calculated input shaft RPM based on N2 and N3 raw values");
-            ui.label(if self.calculated_rpm == u16::MAX {
-                make_text("ERROR", true)
-            } else {
-                make_text(format!("{} RPM", self.calculated_rpm), false)
-            });
+            copy_cell(ui, sna_text(self.calculated_rpm, |v| format!("{} RPM", v)));
             ui.end_row();
 
             ui.label("Calculated output RPM")
                 .on_hover_text("Calculated output RPM. Either based on GPIO pin, or CAN Data");
-            ui.label(if self.output_rpm == u16::MAX {
-                make_text("ERROR", true)
-            } else {
-                make_text(format!("{} RPM", self.output_rpm), false)
-            });
+            copy_cell(ui, sna_text(self.output_rpm, |v| format!("{} RPM", v)));
             ui.end_row();
 
             ui.label("Calculated ratio")
                 .on_hover_text("Calculated gear ratio");
-            ui.label(if self.calculated_rpm == u16::MAX {
-                make_text("ERROR", true)
-            } else {
-                make_text(format!("{:.2}", self.calc_ratio as f32 / 100.0), false)
-            });
+            copy_cell(ui, sna_text(self.calc_ratio, |v| format!("{:.2}", v as f32 / 100.0)));
             ui.end_row();
 
             ui.label("Battery voltage");
-            ui.label(if self.v_batt == u16::MAX {
-                make_text("ERROR", true)
-            } else {
-                make_text(format!("{:.1} V", self.v_batt as f32 / 1000.0), false)
-            });
+            copy_cell(ui, sna_text(self.v_batt, |v| format!("{:.1} V", v as f32 / 1000.0)));
             ui.end_row();
 
             ui.label("ATF Oil temperature\n(Only when parking lock off)");
-            ui.label(if self.parking_lock != 0x00 {
+            copy_cell(ui, if self.parking_lock != 0x00 {
                 make_text("Cannot read\nParking lock engaged", true)
             } else {
                 make_text(format!("{} *C", self.atf_temp_c as i32), false)
@@ -284,7 +216,7 @@ impl DataGearboxSensors {
             ui.end_row();
 
             ui.label("Parking lock");
-            ui.label(if self.parking_lock == 0x00 {
+            copy_cell(ui, if self.parking_lock == 0x00 {
                 make_text("No", false)
             } else {
                 make_text("Yes", false)
@@ -293,13 +225,21 @@ impl DataGearboxSensors {
         })
     }
 
-    pub fn to_chart_data(&self) -> Vec<ChartData> {
+    fn to_chart_data(&self) -> Vec<ChartData> {
         vec![ChartData::new(
             "RPM sensors".into(),
             vec![
-                ("N2 raw", self.n2_rpm as f32, Some("RPM")),
-                ("N3 raw", self.n3_rpm as f32, Some("RPM")),
-                ("Calculated RPM", self.calculated_rpm as f32, Some("RPM")),
+                ("N2 raw", sna_point(self.n2_rpm), Some("RPM")),
+                ("N3 raw", sna_point(self.n3_rpm), Some("RPM")),
+                ("Calculated RPM", sna_point(self.calculated_rpm), Some("RPM")),
+            ],
+            Some((0.0, 0.0)),
+        ),
+        ChartData::new(
+            "ATF temperature".into(),
+            vec![
+                // Cannot be read with the parking lock engaged, so shown as a gap
+                ("ATF temp", if self.parking_lock == 0 { self.atf_temp_c as i32 as f32 } else { f32::NAN }, Some("*C")),
             ],
             Some((0.0, 0.0)),
         )]
@@ -312,6 +252,8 @@ pub struct ChartData {
     pub bounds: Option<(f32, f32)>,
     pub group_name: String,
     pub data: Vec<(String, f32, Option<&'static str>)>, // Data field name, data field value, data field unit
+    /// Region to shade between two values (Name, lower, upper). NaN bounds leave a gap
+    pub fill: Option<(String, f32, f32)>,
 }
 
 impl ChartData {
@@ -327,56 +269,71 @@ impl ChartData {
                 .into_iter()
                 .map(|(n, v, u)| (n.into(), v, u.map(|x| x.into())))
                 .collect(),
+            fill: None,
         }
     }
+
+    pub fn with_fill<T: Into<String>>(mut self, name: T, lower: f32, upper: f32) -> Self {
+        self.fill = Some((name.into(), lower, upper));
+        self
+    }
 }
 
-#[derive(Debug, Default, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct)]
-#[packed_struct(endian="lsb")]
-pub struct DataSolenoids {
-    pub spc_pwm: u16,
-    pub mpc_pwm: u16,
-    pub tcc_pwm: u16,
-    pub y3_pwm: u16,
-    pub y4_pwm: u16,
-    pub y5_pwm: u16,
-    pub spc_current: u16,
-    pub mpc_current: u16,
-    pub tcc_current: u16,
-    pub targ_spc_current: u16,
-    pub targ_mpc_current: u16,
-    pub adjustment_spc: u16,
-    pub adjustment_mpc: u16,
-    pub y3_current: u16,
-    pub y4_current: u16,
-    pub y5_current: u16,
+/// Splits chart points into separately drawn lines at NaN values (Invalid samples),
+/// so they show up as gaps rather than being plotted
+pub fn split_at_gaps<I: IntoIterator<Item = [f64; 2]>>(points: I) -> Vec<Vec<[f64; 2]>> {
+    let mut res = Vec::new();
+    let mut current = Vec::new();
+    for p in points {
+        if p[1].is_nan() {
+            if !current.is_empty() {
+                res.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(p);
+        }
+    }
+    if !current.is_empty() {
+        res.push(current);
+    }
+    res
 }
 
-impl DataSolenoids {
-    pub fn to_table(&self, ui: &mut Ui) -> InnerResponse<()> {
-        egui::Grid::new("DGS").striped(true).show(ui, |ui| {
+/// Shift solenoid used for each shift of the 722.6 (Lower gear, upper gear, solenoid)
+const SHIFT_SOLENOID_MAP: [(u8, u8, &str); 4] = [
+    (1, 2, "Y3"),
+    (2, 3, "Y5"),
+    (3, 4, "Y4"),
+    (4, 5, "Y3"),
+];
+/// Current above which a shift solenoid is considered energised
+const SHIFT_SOLENOID_ON_MA: u16 = 200;
+
+impl RecordView for DataSolenoids {
+    fn to_table(&self, ui: &mut Ui, ctx: &TableContext) -> InnerResponse<()> {
+        let resp = egui::Grid::new("DGS").striped(true).show(ui, |ui| {
             ui.label("MPC Solenoid");
-            ui.label(format!(
+            copy_cell(ui, format!(
                 "PWM {:>4}/4096, Est current {} mA. Targ current {} mA. PWM Trim {:.2} %",
                 self.mpc_pwm,
                 self.mpc_current,
                 self.targ_mpc_current,
-                (self.adjustment_mpc as f32 / 10.0) - 100.0
+                self.mpc_trim_pct()
             ));
             ui.end_row();
 
             ui.label("SPC Solenoid");
-            ui.label(format!(
+            copy_cell(ui, format!(
                 "PWM {:>4}/4096, Est current {} mA. Targ current {} mA. PWM Trim {:.2} %",
                 self.spc_pwm,
                 self.spc_current,
                 self.targ_spc_current,
-                (self.adjustment_spc as f32 / 10.0) - 100.0
+                self.spc_trim_pct()
             ));
             ui.end_row();
 
             ui.label("TCC Solenoid");
-            ui.label(format!(
+            copy_cell(ui, format!(
                 "PWM {:>4}/4096, Est current {} mA",
                 self.tcc_pwm,
                 self.tcc_current
@@ -384,7 +341,7 @@ impl DataSolenoids {
             ui.end_row();
 
             ui.label("Y3 shift Solenoid");
-            ui.label(format!(
+            copy_cell(ui, format!(
                 "PWM {:>4}/4096, Est current {} mA",
                 self.y3_pwm,
                 self.y3_current
@@ -392,7 +349,7 @@ impl DataSolenoids {
             ui.end_row();
 
             ui.label("Y4 shift Solenoid");
-            ui.label(format!(
+            copy_cell(ui, format!(
                 "PWM {:>4}/4096, Est current {} mA",
                 self.y4_pwm,
                 self.y4_current
@@ -400,7 +357,7 @@ impl DataSolenoids {
             ui.end_row();
 
             ui.label("Y5 shift Solenoid");
-            ui.label(format!(
+            copy_cell(ui, format!(
                 "PWM {:>4}/4096, Est current {} mA",
                 self.y5_pwm,
                 self.y5_current
@@ -408,20 +365,26 @@ impl DataSolenoids {
             ui.end_row();
 
             ui.label("Total current consumption");
-            ui.label(format!(
-                "{} mA",
-                self.y5_current as u32
-                    + self.y4_current as u32
-                    + self.y3_current as u32
-                    + self.mpc_current as u32
-                    + self.spc_current as u32
-                    + self.tcc_current as u32
-            ));
+            copy_cell(ui, format!("{} mA", self.total_current_ma()));
             ui.end_row();
-        })
+
+            ui.label("Total power consumption");
+            copy_cell(ui, match ctx.v_batt {
+                Some(v_batt) => make_text(
+                    format!("{:.1} W", (self.total_current_ma() as f32 / 1000.0) * (v_batt as f32 / 1000.0)),
+                    false
+                ),
+                None => make_text("Needs battery voltage", true),
+            });
+            ui.end_row();
+        });
+        solenoid_fault_ui(self, ui);
+        ui.separator();
+        shift_solenoid_table(self, ui, ctx.gear);
+        resp
     }
 
-    pub fn to_chart_data(&self) -> Vec<ChartData> {
+    fn to_chart_data(&self) -> Vec<ChartData> {
         vec![
             ChartData::new(
                 "Solenoid PWM".into(),
@@ -447,244 +410,203 @@ impl DataSolenoids {
                 ],
                 Some((0.0, 6600.0)),
             ),
+            ChartData::new(
+                "Solenoid PWM trim".into(),
+                vec![
+                    ("MPC Solenoid", self.mpc_trim_pct(), Some("%")),
+                    ("SPC Solenoid", self.spc_trim_pct(), Some("%")),
+                ],
+                Some((-20.0, 20.0)),
+            ),
         ]
     }
 }
 
-
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PrimitiveEnum_u8)]
-pub enum TorqueReqCtrlType {
-    None = 0,
-    NormalSpeed = 1,
-    FastAsPossible = 2
+/// Wiring fault hints of a solenoid record, shown as warnings under the values they came from
+pub fn solenoid_fault_ui(s: &DataSolenoids, ui: &mut Ui) {
+    for hint in s.fault_hints() {
+        ui.label(RichText::new(format!("⚠ Possible wiring fault: {hint}")).color(Color32::from_rgb(255, 165, 0)));
+    }
 }
 
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PrimitiveEnum_u8)]
-pub enum TorqueReqBounds {
-    LessThan = 0,
-    MoreThan = 1,
-    Exact = 2
+/// Shift solenoid states, compared against the shifts possible from the current gear.
+/// Shift solenoids are only energised during a shift, so in a steady gear all should be
+/// off, and only the solenoids of the shifts into neighbouring gears may turn on
+fn shift_solenoid_table(s: &DataSolenoids, ui: &mut Ui, gear: Option<u8>) {
+    let states = [
+        ("Y3", s.y3_current),
+        ("Y4", s.y4_current),
+        ("Y5", s.y5_current),
+    ];
+    ui.label(match gear {
+        Some(g) => format!("Shift solenoids (Currently in gear {g})"),
+        None => "Shift solenoids (Gear unknown, needs gearbox sensor data)".into(),
+    });
+    egui::Grid::new("shift-sol-map").striped(true).show(ui, |ui| {
+        ui.strong("Shift");
+        ui.strong("Solenoid");
+        ui.strong("Expected");
+        ui.strong("Measured");
+        ui.end_row();
+        for (from, to, sol) in SHIFT_SOLENOID_MAP {
+            let active = states.iter().any(|(name, current)| *name == sol && *current > SHIFT_SOLENOID_ON_MA);
+            let possible = gear.map(|g| g == from || g == to).unwrap_or(false);
+            let label = format!("{from}<->{to}");
+            ui.label(if possible { RichText::new(label).strong() } else { RichText::new(label) });
+            ui.label(sol);
+            ui.label(if possible { "Off, on when shifting" } else { "Off" });
+            copy_cell(ui, match (active, possible, gear.is_some()) {
+                (false, _, _) => make_text("Off", false),
+                (true, true, _) | (true, _, false) => make_text("Energised", false),
+                (true, false, true) => make_text("Energised! Not expected in this gear", true),
+            });
+            ui.end_row();
+        }
+    });
 }
 
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PrimitiveEnum_u8)]
-pub enum PaddlePosition {
-    None = 0,
-    Plus = 1,
-    Minus = 2,
-    PlusAndMinus = 3,
-    SNV = 0xFF,
+/// Positions the gear lever of each CAN matrix can report
+///
+/// * EGS51 - Mechanical gate with 4/3/2/1 positions (No tip +/-)
+/// * EGS52 - EWM selector with tip +/-
+/// * EGS53 - Steering column lever (P/R/N/D only, +/- come from the paddles)
+fn valid_for_matrix(matrix: EgsCanType) -> &'static [ShifterPosition] {
+    use ShifterPosition::*;
+    match matrix {
+        EgsCanType::EGS51 => &[Park, ParkReverse, Reverse, ReverseNeutral, Neutral, NeutralDrive, Drive, Four, Three, Two, One],
+        EgsCanType::EGS52 => &[Park, ParkReverse, Reverse, ReverseNeutral, Neutral, NeutralDrive, Drive, Plus, Minus],
+        EgsCanType::EGS53 => &[Park, Reverse, Neutral, Drive],
+        EgsCanType::UNKNOWN => &[Park, ParkReverse, Reverse, ReverseNeutral, Neutral, NeutralDrive, Drive, Plus, Minus, Four, Three, Two, One],
+    }
 }
 
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PrimitiveEnum_u8)]
-pub enum ShifterPosition {
-    Park = 0,
-    ParkReverse = 1,
-    Reverse = 2,
-    ReverseNeutral = 3,
-    Neutral = 4,
-    NeutralDrive = 5,
-    Drive = 6,
-    Plus = 7,
-    Minus = 8,
-    Four = 9,
-    Three = 10,
-    Two = 11,
-    One = 12,
-    SNV = 0xFF,
+/// Decoding of the raw selector position, which depends on the CAN matrix the TCU is configured for
+pub trait ShifterDecode: Sized {
+    /// Decodes a raw selector byte for the given CAN matrix.
+    /// Returns an error text if the value is not a position that matrix can report
+    fn decode(raw: u8, matrix: Option<EgsCanType>) -> Result<Self, String>;
 }
 
-#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct)]
-#[packed_struct(endian="lsb")]
-pub struct DataCanDump {
-    pub pedal_position: u8,
-    pub min_torque_ms: u16,
-    pub max_torque_ms: u16,
-    pub static_torque: u16,
-    pub driver_torque: u16,
-    pub left_rear_rpm: u16,
-    pub right_rear_rpm: u16,
-    pub shift_profile_pressed: u8,
-    #[packed_field(size_bytes="1", ty="enum")]
-    pub selector_position: ShifterPosition,
-    #[packed_field(size_bytes="1", ty="enum")]
-    pub paddle_position: PaddlePosition,
-    pub engine_rpm: u16,
-    pub fuel_flow: u16,
-    pub egs_req_torque: u16,
-    #[packed_field(size_bytes="1", ty="enum")]
-    pub egs_torque_req_ctrl_type: TorqueReqCtrlType,
-    #[packed_field(size_bytes="1", ty="enum")]
-    pub egs_torque_req_bounds: TorqueReqBounds,
-    pub engine_iat_temp: i16,
-    pub engine_oil_temp: i16,
-    pub engine_coolant_temp: i16
+impl ShifterDecode for ShifterPosition {
+    fn decode(raw: u8, matrix: Option<EgsCanType>) -> Result<Self, String> {
+        let matrix = matrix.unwrap_or(EgsCanType::UNKNOWN);
+        match ShifterPosition::from_primitive(raw) {
+            Some(ShifterPosition::SNV) => Ok(ShifterPosition::SNV),
+            Some(pos) if valid_for_matrix(matrix).contains(&pos) => Ok(pos),
+            Some(pos) => Err(format!("{} is not a valid position for {:?} (Raw 0x{:02X})", pos.short_name(), matrix, raw)),
+            None => Err(format!("Unknown position (Raw 0x{:02X})", raw)),
+        }
+    }
 }
 
-impl DataCanDump {
-    pub fn to_table(&self, ui: &mut Ui) -> InnerResponse<()> {
+impl RecordView for DataCanDump {
+    fn to_table(&self, ui: &mut Ui, ctx: &TableContext) -> InnerResponse<()> {
         egui::Grid::new("DGS").striped(true).show(ui, |ui| {
             ui.label("Accelerator pedal position");
-            ui.label(if self.pedal_position == u8::MAX {
-                make_text("Signal not available", true)
-            } else {
-                make_text(
-                    format!("{:.1} %", self.pedal_position as f32 / 250.0 * 100.0),
-                    false,
-                )
-            });
+            copy_cell(ui, sna_text(self.pedal_position, |v| format!("{:.1} %", v as f32 / 250.0 * 100.0)));
             ui.end_row();
 
             ui.label("Engine RPM");
-            ui.label(if self.engine_rpm == u16::MAX {
-                make_text("Signal not available", true)
-            } else {
-                make_text(format!("{} RPM", self.engine_rpm as f32), false)
-            });
+            copy_cell(ui, sna_text(self.engine_rpm, |v| format!("{} RPM", v as f32)));
             ui.end_row();
 
             ui.label("Engine minimum torque");
-            ui.label(if self.min_torque_ms == u16::MAX {
-                make_text("Signal not available", true)
-            } else {
-                make_text(
-                    format!("{:.1} Nm", self.min_torque_ms as f32 / 4.0 - 500.0),
-                    false,
-                )
-            });
+            copy_cell(ui, sna_text(self.min_torque_ms, |v| format!("{:.1} Nm", v as f32 / 4.0 - 500.0)));
             ui.end_row();
 
             ui.label("Engine maximum torque");
-            ui.label(if self.max_torque_ms == u16::MAX {
-                make_text("Signal not available", true)
-            } else {
-                make_text(
-                    format!("{:.1} Nm", self.max_torque_ms as f32 / 4.0 - 500.0),
-                    false,
-                )
-            });
+            copy_cell(ui, sna_text(self.max_torque_ms, |v| format!("{:.1} Nm", v as f32 / 4.0 - 500.0)));
             ui.end_row();
 
             ui.label("Engine static torque");
-            ui.label(if self.static_torque == u16::MAX {
-                make_text("Signal not available", true)
-            } else {
-                make_text(
-                    format!("{:.1} Nm", self.static_torque as f32 / 4.0 - 500.0),
-                    false,
-                )
-            });
+            copy_cell(ui, sna_text(self.static_torque, |v| format!("{:.1} Nm", v as f32 / 4.0 - 500.0)));
             ui.end_row();
 
             ui.label("Driver req torque");
-            ui.label(if self.driver_torque == u16::MAX {
-                make_text("Signal not available", true)
-            } else {
-                make_text(
-                    format!("{:.1} Nm", self.driver_torque as f32 / 4.0 - 500.0),
-                    false,
-                )
-            });
+            copy_cell(ui, sna_text(self.driver_torque, |v| format!("{:.1} Nm", v as f32 / 4.0 - 500.0)));
             ui.end_row();
 
             ui.label("Rear right wheel speed");
-            ui.label(if self.right_rear_rpm == u16::MAX {
-                make_text("Signal not available", true)
-            } else {
-                make_text(
-                    format!("{:.1} RPM", self.right_rear_rpm as f32 / 2.0),
-                    false,
-                )
-            });
+            copy_cell(ui, sna_text(self.right_rear_rpm, |v| format!("{:.1} RPM", v as f32 / 2.0)));
             ui.end_row();
 
             ui.label("Rear left wheel speed");
-            ui.label(if self.left_rear_rpm == u16::MAX {
-                make_text("Signal not available", true)
-            } else {
-                make_text(
-                    format!("{:.1} RPM", self.left_rear_rpm as f32 / 2.0),
-                    false,
-                )
+            copy_cell(ui, sna_text(self.left_rear_rpm, |v| format!("{:.1} RPM", v as f32 / 2.0)));
+            ui.end_row();
+
+            ui.label("Vehicle speed")
+                .on_hover_text("Average of the rear wheel speeds, using the configured wheel circumference");
+            copy_cell(ui, match ctx.wheel_circumference_mm.and_then(|c| self.vehicle_speed_kmh(c)) {
+                Some(speed) if self.wheel_speed_mismatch() => make_text(
+                    format!("{:.1} km/h\nLeft/right wheel speeds differ. Check the wheel speed sensors", speed),
+                    true,
+                ),
+                Some(speed) => make_text(format!("{:.1} km/h", speed), false),
+                None => make_text("N/A", true),
             });
             ui.end_row();
 
             ui.label("Gear selector position");
-            ui.label(if self.selector_position == ShifterPosition::SNV {
-                make_text("Signal not available", true)
-            } else {
-                make_text(format!("{:?}", self.selector_position), false)
+            copy_cell(ui, match ShifterPosition::decode(self.selector_position, ctx.can_matrix) {
+                Ok(ShifterPosition::SNV) => make_text("N/A", true),
+                Ok(pos) => make_text(pos.short_name(), false),
+                Err(e) => make_text(e, true),
             });
             ui.end_row();
 
             ui.label("Shift paddle position");
-            ui.label(if self.paddle_position == PaddlePosition::SNV {
-                make_text("Signal not available", true)
+            copy_cell(ui, if self.paddle_position == PaddlePosition::SNV {
+                make_text("N/A", true)
             } else {
                 make_text(format!("{:?}", self.paddle_position), false)
             });
             ui.end_row();
 
             ui.label("Fuel flow");
-            ui.label(format!("{} ul/s", self.fuel_flow));
+            copy_cell(ui, sna_text(self.fuel_flow, |v| format!("{:.2} L/h ({} ul/s)", self.fuel_flow_lph(), v)));
+            ui.end_row();
+
+            ui.label("Fuel economy")
+                .on_hover_text("Based on fuel flow and the average rear wheel speed");
+            copy_cell(ui, match ctx.wheel_circumference_mm.and_then(|c| self.fuel_economy_l100km(c)) {
+                Some(e) => make_text(format!("{:.1} L/100km", e), false),
+                None => make_text("N/A", true),
+            });
             ui.end_row();
 
             ui.label("Torque request");
             if self.egs_torque_req_ctrl_type == TorqueReqCtrlType::None {
                 ui.label("None");
             } else {
-                ui.label(format!("{} Nm ({:?})", self.egs_req_torque as f32 / 4.0 - 500.0, self.egs_torque_req_ctrl_type));
+                copy_cell(ui, sna_text(self.egs_req_torque, |v| format!("{} Nm ({:?})", v as f32 / 4.0 - 500.0, self.egs_torque_req_ctrl_type)));
                 ui.end_row();
                 ui.label(format!("({:?})", self.egs_torque_req_bounds));
             }
             ui.end_row();
             
             ui.label("Engine intake air temp");
-            ui.label(if self.engine_iat_temp == core::i16::MAX {
-                make_text("Signal not available", true)
-            } else {
-                make_text(format!("{}C", self.engine_iat_temp), false)
-            });
+            copy_cell(ui, sna_text(self.engine_iat_temp, |v| format!("{}C", v)));
             ui.end_row();
 
             ui.label("Engine coolant temp");
-            ui.label(if self.engine_coolant_temp == core::i16::MAX {
-                make_text("Signal not available", true)
-            } else {
-                make_text(format!("{}C", self.engine_coolant_temp), false)
-            });
+            copy_cell(ui, sna_text(self.engine_coolant_temp, |v| format!("{}C", v)));
             ui.end_row();
 
             ui.label("Engine oil temp");
-            ui.label(if self.engine_oil_temp == core::i16::MAX {
-                make_text("Signal not available", true)
-            } else {
-                make_text(format!("{}C", self.engine_oil_temp), false)
-            });
+            copy_cell(ui, sna_text(self.engine_oil_temp, |v| format!("{}C", v)));
             ui.end_row();
         })
     }
 
-    pub fn to_chart_data(&self) -> Vec<ChartData> {
-        let min = if self.min_torque_ms == u16::MAX {
-            0.0
-        } else {
-            self.min_torque_ms as f32 / 4.0 - 500.0
-        };
-        let sta = if self.static_torque == u16::MAX {
-            0.0
-        } else {
-            self.static_torque as f32 / 4.0 - 500.0
-        };
-        let drv = if self.driver_torque == u16::MAX {
-            0.0
-        } else {
-            self.driver_torque as f32 / 4.0 - 500.0
-        };
-        let egs = if self.egs_req_torque == u16::MAX || self.egs_torque_req_ctrl_type == TorqueReqCtrlType::None {
-            0.0
-        } else {
-            self.egs_req_torque as f32 / 4.0 - 500.0
-        };
+    fn to_chart_data(&self) -> Vec<ChartData> {
+        let trq = |raw: u16| raw.available().map(|v| v as f32 / 4.0 - 500.0).unwrap_or(0.0);
+        let min = trq(self.min_torque_ms);
+        let sta = trq(self.static_torque);
+        let drv = trq(self.driver_torque);
+        let egs_active = self.egs_torque_req_ctrl_type != TorqueReqCtrlType::None;
+        let egs = if egs_active { trq(self.egs_req_torque) } else { 0.0 };
+        // Torque the gearbox is taking away from the engine, only whilst it is asking for less
+        let (cut_lo, cut_hi) = if egs_active && egs < sta { (egs, sta) } else { (f32::NAN, f32::NAN) };
         vec![ChartData::new(
             "Torque data".into(),
             vec![
@@ -694,39 +616,27 @@ impl DataCanDump {
                 ("EGS Requested trq", egs, Some("Nm"))
             ],
             None,
-        ),
+        ).with_fill("Torque cut", cut_lo, cut_hi),
         ChartData::new(
             "Fuel usage".into(),
             vec![
-                ("Fuel flow", self.fuel_flow as f32, Some("ul/sec")),
+                ("Fuel flow", self.fuel_flow_lph(), Some("L/h")),
             ],
             None,
         ),
         ChartData::new(
             "Wheel speeds".into(),
             vec![
-                ("Rear left wheel", self.left_rear_rpm as f32, Some("RPM")),
-                ("Rear right wheel", self.right_rear_rpm as f32, Some("RPM")),
+                ("Rear left wheel", sna_point(self.left_rear_rpm), Some("RPM")),
+                ("Rear right wheel", sna_point(self.right_rear_rpm), Some("RPM")),
             ],
             None,
         )]
     }
 }
 
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct)]
-#[packed_struct(endian="lsb")]
-pub struct DataSysUsage {
-    core1_usage: u16,
-    core2_usage: u16,
-    free_ram: u32,
-    total_ram: u32,
-    free_psram: u32,
-    total_psram: u32,
-    num_tasks: u32,
-}
-
-impl DataSysUsage {
-    pub fn to_table(&self, ui: &mut Ui) -> InnerResponse<()> {
+impl RecordView for DataSysUsage {
+    fn to_table(&self, ui: &mut Ui, ctx: &TableContext) -> InnerResponse<()> {
         let r_f = self.free_ram as f32;
         let r_t = self.total_ram as f32;
         let p_f = self.free_psram as f32;
@@ -737,15 +647,15 @@ impl DataSysUsage {
 
         egui::Grid::new("DGS").striped(true).show(ui, |ui| {
             ui.label("Core 1 usage");
-            ui.label(format!("{:.1} %", self.core1_usage as f32 / 10.0));
+            copy_cell(ui, format!("{:.1} %", self.core1_usage as f32 / 10.0));
             ui.end_row();
 
             ui.label("Core 2 usage");
-            ui.label(format!("{:.1} %", self.core2_usage as f32 / 10.0));
+            copy_cell(ui, format!("{:.1} %", self.core2_usage as f32 / 10.0));
             ui.end_row();
 
             ui.label("Free internal RAM");
-            ui.label(format!(
+            copy_cell(ui, format!(
                 "{:.1} Kb ({:.1}% Used)",
                 self.free_ram as f32 / 1024.0,
                 used_ram_perc
@@ -753,7 +663,7 @@ impl DataSysUsage {
             ui.end_row();
 
             ui.label("Free PSRAM");
-            ui.label(format!(
+            copy_cell(ui, format!(
                 "{:.1} Kb ({:.1}% Used)",
                 self.free_psram as f32 / 1024.0,
                 used_psram_perc
@@ -761,12 +671,13 @@ impl DataSysUsage {
             ui.end_row();
 
             ui.label("Num. OS Tasks");
-            ui.label(format!("{}", self.num_tasks));
+            copy_cell(ui, make_text(format!("{}", self.num_tasks), ctx.task_anomaly))
+                .on_hover_text("Red if above, or increased by more than, the task count limits under Alerts");
             ui.end_row();
         })
     }
 
-    pub fn to_chart_data(&self) -> Vec<ChartData> {
+    fn to_chart_data(&self) -> Vec<ChartData> {
         let r_f = self.free_ram as f32;
         let r_t = self.total_ram as f32;
         let p_f = self.free_psram as f32;
@@ -789,6 +700,21 @@ impl DataSysUsage {
             ],
             Some((0.0, 100.0))
         ),
+        // Free memory on its own scale (Not a percentage), so a slow leak is visible
+        ChartData::new(
+            "Free IRAM".into(),
+            vec![
+                ("IRAM", r_f / 1024.0, Some("Kb")),
+            ],
+            None
+        ),
+        ChartData::new(
+            "Free PSRAM".into(),
+            vec![
+                ("PSRAM", p_f / 1024.0, Some("Kb")),
+            ],
+            None
+        ),
         ChartData::new(
             "OS Task count".into(),
             vec![
@@ -799,184 +725,126 @@ impl DataSysUsage {
     }
 }
 
-#[repr(u8)]
-pub enum ShiftIdx {
-    NoShift = 0,
-    OneTwo = 1,
-    TwoThree = 2,
-    ThreeFour = 3,
-    FourFive = 4,
-    FiveFour = 5,
-    FourThree = 6,
-    ThreeTwo = 7,
-    TwoOne = 8,
-    Unknown = 0xFF,
-}
-
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct)]
-#[packed_struct(endian="lsb")]
-pub struct DataShiftManager {
-    pub spc_pressure_mbar: u16,
-    pub mpc_pressure_mbar: u16,
-    pub tcc_pressure_mbar: u16,
-    pub shift_solenoid_pos: u8,
-    pub input_rpm: u16,
-    pub engine_rpm: u16,
-    pub output_rpm: u16,
-    pub engine_torque: u16,
-    pub req_engine_torque: u16,
-    pub atf_temp: u8,
-    pub shift_idx: u8,
-}
-
-impl DataShiftManager {
-    pub fn to_table(&self, ui: &mut Ui) -> InnerResponse<()> {
+impl RecordView for DataShiftManager {
+    fn to_table(&self, ui: &mut Ui, _ctx: &TableContext) -> InnerResponse<()> {
         egui::Grid::new("SM").striped(true).show(ui, |ui| {
             ui.label("SPC Pressure");
-            ui.label(format!("{} mBar", self.spc_pressure_mbar));
+            copy_cell(ui, sna_text(self.spc_pressure_mbar, |v| format!("{} mBar", v)));
             ui.end_row();
 
             ui.label("MPC pressure");
-            ui.label(format!("{} mBar", self.mpc_pressure_mbar));
+            copy_cell(ui, sna_text(self.mpc_pressure_mbar, |v| format!("{} mBar", v)));
             ui.end_row();
 
             ui.label("TCC pressure");
-            ui.label(format!("{} mBar", self.tcc_pressure_mbar));
+            copy_cell(ui, sna_text(self.tcc_pressure_mbar, |v| format!("{} mBar", v)));
             ui.end_row();
 
             ui.label("Shift solenoid pos");
-            ui.label(format!("{}/255", self.shift_solenoid_pos));
+            // 255 is a valid (Full scale) position, so this has no 'not available' value
+            copy_cell(ui, format!("{}/255", self.shift_solenoid_pos));
             ui.end_row();
 
             ui.label("Input shaft speed");
-            ui.label(format!("{} RPM", self.input_rpm));
+            copy_cell(ui, sna_text(self.input_rpm, |v| format!("{} RPM", v)));
             ui.end_row();
 
             ui.label("Engine speed");
-            ui.label(format!("{} RPM", self.engine_rpm));
+            copy_cell(ui, sna_text(self.engine_rpm, |v| format!("{} RPM", v)));
             ui.end_row();
 
             ui.label("Output shaft speed");
-            ui.label(format!("{} RPM", self.output_rpm));
+            copy_cell(ui, sna_text(self.output_rpm, |v| format!("{} RPM", v)));
             ui.end_row();
 
             ui.label("Shift state");
-            ui.label(match self.shift_idx {
-                0 => "None",
-                1 => "1 -> 2",
-                2 => "2 -> 3",
-                3 => "3 -> 4",
-                4 => "4 -> 5",
-                5 => "5 -> 4",
-                6 => "4 -> 3",
-                7 => "3 -> 2",
-                8 => "2 -> 1",
-                _ => "UNKNOWN",
-            });
+            copy_cell(ui, shift_idx_name(self.shift_idx));
             ui.end_row();
         })
     }
 
-    pub fn to_chart_data(&self) -> Vec<ChartData> {
+    fn to_chart_data(&self) -> Vec<ChartData> {
         vec![ChartData::new(
             "RPMs".into(),
             vec![
-                ("Input speed", self.input_rpm as f32, Some("RPM")),
-                ("Engine speed", self.engine_rpm as f32, Some("RPM")),
+                ("Input speed", sna_point(self.input_rpm), Some("RPM")),
+                ("Engine speed", sna_point(self.engine_rpm), Some("RPM")),
             ],
             None,
         ),
         ChartData::new(
             "Solenoid pressures".into(),
             vec![
-                ("Modulating pressure", self.mpc_pressure_mbar as f32, Some("mBar")),
-                ("Shift pressure", self.spc_pressure_mbar as f32, Some("mBar")),
-                ("TCC pressure", self.tcc_pressure_mbar as f32, Some("mBar")),
+                ("Modulating pressure", sna_point(self.mpc_pressure_mbar), Some("mBar")),
+                ("Shift pressure", sna_point(self.spc_pressure_mbar), Some("mBar")),
+                ("TCC pressure", sna_point(self.tcc_pressure_mbar), Some("mBar")),
             ],
             None,
         )]
     }
 }
 
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct)]
-#[packed_struct(endian="lsb")]
-pub struct DataClutchSpeeds {
-    k1: i16,
-    k2: i16,
-    k3: i16,
-    b1: i16,
-    b2: i16,
-    b3: i16,
-}
-
-impl DataClutchSpeeds {
-    pub fn to_table(&self, ui: &mut Ui) -> InnerResponse<()> {
+impl RecordView for DataClutchSpeeds {
+    fn to_table(&self, ui: &mut Ui, _ctx: &TableContext) -> InnerResponse<()> {
         egui::Grid::new("SM").striped(true).show(ui, |ui| {
 
             ui.label("K1 speed");
-            ui.label(format!("{} RPM", self.k1));
+            copy_cell(ui, sna_text(self.k1, |v| format!("{} RPM", v)));
             ui.end_row();
 
             ui.label("K2 speed");
-            ui.label(format!("{} RPM", self.k2));
+            copy_cell(ui, sna_text(self.k2, |v| format!("{} RPM", v)));
             ui.end_row();
 
             ui.label("K3 speed");
-            ui.label(format!("{} RPM", self.k3));
+            copy_cell(ui, sna_text(self.k3, |v| format!("{} RPM", v)));
             ui.end_row();
 
             ui.label("B1 speed");
-            ui.label(format!("{} RPM", self.b1));
+            copy_cell(ui, sna_text(self.b1, |v| format!("{} RPM", v)));
             ui.end_row();
 
             ui.label("B2 speed");
-            ui.label(format!("{} RPM", self.b2));
+            copy_cell(ui, sna_text(self.b2, |v| format!("{} RPM", v)));
             ui.end_row();
 
             ui.label("B3 speed");
-            ui.label(format!("{} RPM", self.b3));
+            copy_cell(ui, sna_text(self.b3, |v| format!("{} RPM", v)));
             ui.end_row();
         })
     }
 
-    pub fn to_chart_data(&self) -> Vec<ChartData> {
+    fn to_chart_data(&self) -> Vec<ChartData> {
         vec![ChartData::new(
             "RPMs".into(),
             vec![
-                ("K1", self.k1 as f32, Some("RPM")),
-                ("K2", self.k2 as f32, Some("RPM")),
-                ("K3", self.k3 as f32, Some("RPM")),
-                ("B1", self.b1 as f32, Some("RPM")),
-                ("B2", self.b2 as f32, Some("RPM")),
-                ("B3", self.b3 as f32, Some("RPM")),
+                ("K1", sna_point(self.k1), Some("RPM")),
+                ("K2", sna_point(self.k2), Some("RPM")),
+                ("K3", sna_point(self.k3), Some("RPM")),
+                ("B1", sna_point(self.b1), Some("RPM")),
+                ("B2", sna_point(self.b2), Some("RPM")),
+                ("B3", sna_point(self.b3), Some("RPM")),
             ],
             None,
         )]
     }
 }
 
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, PackedStruct)]
-#[packed_struct(endian="lsb")]
-pub struct DataShiftClutchVelocity {
-    on_vel: i16,
-    off_vel: i16
-}
-
-impl DataShiftClutchVelocity {
-    pub fn to_table(&self, ui: &mut Ui) -> InnerResponse<()> {
+impl RecordView for DataShiftClutchVelocity {
+    fn to_table(&self, ui: &mut Ui, _ctx: &TableContext) -> InnerResponse<()> {
         egui::Grid::new("SM").striped(true).show(ui, |ui| {
 
             ui.label("On clutch acceleration");
-            ui.label(format!("{} RPM/100ms", self.on_vel));
+            copy_cell(ui, format!("{} RPM/100ms", self.on_vel));
             ui.end_row();
 
             ui.label("Off clutch acceleration");
-            ui.label(format!("{} RPM/100ms", self.off_vel));
+            copy_cell(ui, format!("{} RPM/100ms", self.off_vel));
             ui.end_row();
         })
     }
 
-    pub fn to_chart_data(&self) -> Vec<ChartData> {
+    fn to_chart_data(&self) -> Vec<ChartData> {
         vec![ChartData::new(
             "Velocities".into(),
             vec![
@@ -0,0 +1,235 @@
+//! Multi-channel scope, overlaying series from several records on one time axis
+//!
+//! The selected records are polled in rotation, so each one updates at a different
+//! time. Every sample adds a point to all channels, with channels of the other records
+//! carrying forward their last value, which keeps the lines time aligned.
+use std::collections::{hash_map::DefaultHasher, VecDeque};
+use std::hash::{Hash, Hasher};
+
+use eframe::egui::{
+    plot::{Legend, Line, Plot, PlotPoints},
+    Color32, Ui,
+};
+use eframe::epaint::Stroke;
+
+use super::resample::{resample_hold, Channel};
+use super::rli::{split_at_gaps, ChartData, LocalRecordData, RecordIdents, RecordView};
+
+
+/// Records that can be added to the scope
+const SCOPE_RECORDS: [(RecordIdents, &str); 6] = [
+    (RecordIdents::GearboxSensors, "Gearbox sensors"),
+    (RecordIdents::SolenoidStatus, "Solenoids"),
+    (RecordIdents::PressureStatus, "Pressures"),
+    (RecordIdents::SysUsage, "Performance"),
+    (RecordIdents::ClutchSpeeds, "Clutch speeds"),
+    (RecordIdents::ClutchVelocities, "Clutch velocities"),
+];
+
+/// Selectable time grids of the CSV export (ms, label)
+const CSV_STEPS: [(u64, &str); 4] = [(100, "100ms"), (250, "250ms"), (500, "500ms"), (1000, "1s")];
+/// A channel with no sample for this long is left empty in the CSV, rather than held
+const CSV_MAX_HOLD_MS: f64 = 2000.0;
+
+#[derive(Debug, Clone, PartialEq)]
+struct ScopeChannel {
+    record: RecordIdents,
+    group: String,
+    series: String,
+    unit: Option<&'static str>,
+    points: VecDeque<[f64; 2]>,
+}
+
+impl ScopeChannel {
+    fn name(&self) -> String {
+        match self.unit {
+            Some(u) => format!("{} - {} ({})", self.group, self.series, u.trim()),
+            None => format!("{} - {}", self.group, self.series),
+        }
+    }
+
+    fn value_in(&self, data: &[ChartData]) -> Option<f32> {
+        data.iter()
+            .find(|g| g.group_name == self.group)
+            .and_then(|g| g.data.iter().find(|(k, _, _)| *k == self.series))
+            .map(|(_, v, _)| *v)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    records: Vec<RecordIdents>,
+    /// How long samples are kept for, in ms
+    window_ms: u128,
+    /// Last chart data of each polled record
+    latest: Vec<(RecordIdents, Vec<ChartData>)>,
+    channels: Vec<ScopeChannel>,
+    /// Next record to poll
+    rotation: usize,
+    /// Time grid of the CSV export in ms
+    csv_step_ms: u64,
+    csv_err: Option<String>,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self {
+            records: Vec::new(),
+            window_ms: super::RLI_CHART_DISPLAY_TIME,
+            latest: Vec::new(),
+            channels: Vec::new(),
+            rotation: 0,
+            csv_step_ms: CSV_STEPS[0].0,
+            csv_err: None,
+        }
+    }
+}
+
+impl Scope {
+    pub fn set_window(&mut self, window_ms: u128) {
+        self.window_ms = window_ms;
+    }
+
+    /// Record to poll next, or None if the scope is not in use
+    pub fn next_record(&mut self) -> Option<RecordIdents> {
+        if self.records.is_empty() {
+            return None;
+        }
+        self.rotation = (self.rotation + 1) % self.records.len();
+        Some(self.records[self.rotation])
+    }
+
+    pub fn on_sample(&mut self, timestamp_ms: u128, record: RecordIdents, data: &LocalRecordData) {
+        if !self.records.contains(&record) {
+            return;
+        }
+        let cd = data.to_chart_data();
+        match self.latest.iter_mut().find(|(r, _)| *r == record) {
+            Some((_, d)) => *d = cd,
+            None => self.latest.push((record, cd)),
+        }
+        let ts = timestamp_ms as f64;
+        for c in self.channels.iter_mut() {
+            let value = self.latest.iter()
+                .find(|(r, _)| *r == c.record)
+                .and_then(|(_, d)| c.value_in(d));
+            if let Some(v) = value {
+                c.points.push_back([ts, v as f64]);
+            }
+            while let Some([t, _]) = c.points.front() {
+                if ts - t > self.window_ms as f64 {
+                    c.points.pop_front();
+                } else {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn toggle_record(&mut self, record: RecordIdents, enabled: bool) {
+        if enabled {
+            self.records.push(record);
+        } else {
+            self.records.retain(|r| *r != record);
+            self.latest.retain(|(r, _)| *r != record);
+            self.channels.retain(|c| c.record != record);
+        }
+    }
+
+    fn toggle_channel(&mut self, record: RecordIdents, group: &str, series: &str, unit: Option<&'static str>, enabled: bool) {
+        let is_channel = |c: &ScopeChannel| c.record == record && c.group == group && c.series == series;
+        if enabled {
+            self.channels.push(ScopeChannel {
+                record,
+                group: group.to_string(),
+                series: series.to_string(),
+                unit,
+                points: VecDeque::new(),
+            });
+        } else {
+            self.channels.retain(|c| !is_channel(c));
+        }
+    }
+
+    /// Writes every channel to a CSV, aligned onto a grid of `csv_step_ms`
+    fn export_csv(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let points: Vec<Vec<[f64; 2]>> = self.channels.iter().map(|c| c.points.iter().copied().collect()).collect();
+        let channels: Vec<Channel> = self.channels.iter()
+            .zip(&points)
+            .map(|(c, p)| Channel { name: c.name(), points: p })
+            .collect();
+        let res = resample_hold(&channels, self.csv_step_ms as f64, CSV_MAX_HOLD_MS);
+        res.write_csv(path, res.times.first().copied().unwrap_or_default())
+    }
+
+    pub fn make_ui(&mut self, ui: &mut Ui, now_ms: u128) {
+        ui.label("Select records to poll, then the values to plot");
+        for (record, name) in SCOPE_RECORDS {
+            let mut enabled = self.records.contains(&record);
+            if ui.checkbox(&mut enabled, name).changed() {
+                self.toggle_record(record, enabled);
+            }
+            if !enabled {
+                continue;
+            }
+            let groups = self.latest.iter().find(|(r, _)| *r == record).map(|(_, d)| d.clone());
+            ui.indent(name, |ui| match groups {
+                None => {
+                    ui.label("Waiting for data");
+                },
+                Some(groups) => {
+                    for g in groups {
+                        ui.horizontal_wrapped(|ui| {
+                            ui.label(format!("{}:", g.group_name));
+                            for (series, _, unit) in &g.data {
+                                let mut plotted = self.channels.iter().any(|c| c.record == record && c.group == g.group_name && c.series == *series);
+                                if ui.checkbox(&mut plotted, series.as_str()).changed() {
+                                    self.toggle_channel(record, &g.group_name, series, *unit, plotted);
+                                }
+                            }
+                        });
+                    }
+                }
+            });
+        }
+        if self.channels.is_empty() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.label("CSV time step:");
+            for (ms, label) in CSV_STEPS {
+                ui.selectable_value(&mut self.csv_step_ms, ms, label);
+            }
+            if ui.button("Export CSV")
+                .on_hover_text("Each row holds every channel's last value at that time. Channels not updated for 2s are left empty")
+                .clicked()
+            {
+                if let Some(p) = rfd::FileDialog::new().add_filter("csv", &["csv"]).set_file_name("scope.csv").save_file() {
+                    self.csv_err = self.export_csv(&p).err().map(|e| format!("Could not export CSV: {e}"));
+                }
+            }
+        });
+        if let Some(e) = &self.csv_err {
+            ui.label(eframe::egui::RichText::new(e).color(Color32::RED));
+        }
+        Plot::new("diag-scope")
+            .height(250.0)
+            .allow_drag(false)
+            .legend(Legend::default().position(eframe::egui::plot::Corner::LeftTop))
+            .include_x(now_ms.saturating_sub(self.window_ms) as f64)
+            .include_x(now_ms as f64)
+            .x_axis_formatter(|f, _r| format!("{:.1}s", f / 1000.0))
+            .show(ui, |p| {
+                for c in &self.channels {
+                    let name = c.name();
+                    let mut key_hasher = DefaultHasher::default();
+                    name.hash(&mut key_hasher);
+                    let r = key_hasher.finish();
+                    let color = Color32::from_rgb((r & 0xFF) as u8, ((r >> 8) & 0xFF) as u8, ((r >> 16) & 0xFF) as u8);
+                    for seg in split_at_gaps(c.points.iter().copied()) {
+                        p.line(Line::new(PlotPoints::new(seg)).name(&name).stroke(Stroke::new(2.0, color)));
+                    }
+                }
+            });
+    }
+}
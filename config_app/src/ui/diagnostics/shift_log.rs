@@ -0,0 +1,107 @@
+//! Shift event logger
+//!
+//! Watches the live shift manager record and writes one JSON object
+//! per completed shift to a file (JSON-lines format)
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+
+use serde::Serialize;
+
+use super::rli::{shift_idx_name, DataShiftManager};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShiftRecord {
+    pub shift: &'static str,
+    /// Milliseconds since the diagnostics page was opened
+    pub start_ms: u128,
+    pub duration_ms: u128,
+    /// Largest engine RPM - input RPM difference seen during the shift
+    pub peak_slip_rpm: i32,
+    pub spc_start_mbar: u16,
+    pub mpc_start_mbar: u16,
+    pub spc_end_mbar: u16,
+    pub mpc_end_mbar: u16,
+}
+
+struct ActiveShift {
+    idx: u8,
+    start_ms: u128,
+    start: DataShiftManager,
+    last: DataShiftManager,
+    peak_slip_rpm: i32,
+}
+
+pub struct ShiftLogger {
+    path: PathBuf,
+    file: File,
+    active: Option<ActiveShift>,
+    shift_count: usize,
+}
+
+fn slip(s: &DataShiftManager) -> i32 {
+    s.engine_rpm as i32 - s.input_rpm as i32
+}
+
+impl ShiftLogger {
+    pub fn new(path: PathBuf) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file,
+            active: None,
+            shift_count: 0,
+        })
+    }
+
+    pub fn get_path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn get_shift_count(&self) -> usize {
+        self.shift_count
+    }
+
+    /// Feed a new sample of shift manager data into the logger.
+    /// A shift record is written when the shift index changes away from an active shift
+    pub fn on_sample(&mut self, timestamp_ms: u128, sample: &DataShiftManager) -> std::io::Result<()> {
+        let finished = match self.active.as_mut() {
+            Some(active) if active.idx == sample.shift_idx => {
+                active.peak_slip_rpm = active.peak_slip_rpm.max(slip(sample));
+                active.last = *sample;
+                false
+            }
+            Some(_) => true,
+            None => false,
+        };
+        if finished {
+            let active = self.active.take().unwrap();
+            let record = ShiftRecord {
+                shift: shift_idx_name(active.idx),
+                start_ms: active.start_ms,
+                duration_ms: timestamp_ms - active.start_ms,
+                peak_slip_rpm: active.peak_slip_rpm,
+                spc_start_mbar: active.start.spc_pressure_mbar,
+                mpc_start_mbar: active.start.mpc_pressure_mbar,
+                spc_end_mbar: active.last.spc_pressure_mbar,
+                mpc_end_mbar: active.last.mpc_pressure_mbar,
+            };
+            let mut line = serde_json::to_string(&record)?;
+            line.push('\n');
+            self.file.write_all(line.as_bytes())?;
+            self.shift_count += 1;
+        }
+        if self.active.is_none() && sample.shift_idx != 0 && sample.shift_idx != 0xFF {
+            self.active = Some(ActiveShift {
+                idx: sample.shift_idx,
+                start_ms: timestamp_ms,
+                start: *sample,
+                last: *sample,
+                peak_slip_rpm: slip(sample),
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+//! Shift regions drawn over the pressure charts, so pressure spikes can be lined up with shifts
+use std::collections::VecDeque;
+
+use eframe::egui::{
+    plot::{PlotPoint, PlotUi, Polygon, Text},
+    Align2, Color32,
+};
+
+use super::rli::{shift_idx_name, DataShiftManager};
+
+#[derive(Debug, Clone, Copy)]
+struct ShiftRegion {
+    idx: u8,
+    start_ms: u128,
+    /// None whilst the shift is still in progress
+    end_ms: Option<u128>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ShiftOverlay {
+    regions: VecDeque<ShiftRegion>,
+}
+
+impl ShiftOverlay {
+    /// Feed a new sample of shift manager data. Timestamps are the same ones the chart data uses
+    pub fn on_sample(&mut self, timestamp_ms: u128, sample: &DataShiftManager) {
+        let active = self.regions.back_mut().filter(|r| r.end_ms.is_none());
+        match active {
+            Some(r) if r.idx == sample.shift_idx => return,
+            Some(r) => r.end_ms = Some(timestamp_ms),
+            None => {}
+        }
+        if sample.shift_idx != 0 && sample.shift_idx != 0xFF {
+            self.regions.push_back(ShiftRegion { idx: sample.shift_idx, start_ms: timestamp_ms, end_ms: None });
+        }
+    }
+
+    /// Drops shifts that finished before `oldest_ms`
+    pub fn trim(&mut self, oldest_ms: u128) {
+        while self.regions.front().and_then(|r| r.end_ms).map(|e| e < oldest_ms).unwrap_or(false) {
+            self.regions.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Draws every shift as a shaded band between `y_min` and `y_max`, labelled at the top.
+    /// `start_ms` is subtracted from the timestamps, the same as for the chart lines
+    pub fn draw(&self, plot: &mut PlotUi, start_ms: u128, now_ms: u128, y_min: f64, y_max: f64) {
+        for r in &self.regions {
+            let x0 = r.start_ms.saturating_sub(start_ms) as f64;
+            let x1 = r.end_ms.unwrap_or(now_ms).saturating_sub(start_ms) as f64;
+            plot.polygon(
+                Polygon::new(vec![[x0, y_min], [x1, y_min], [x1, y_max], [x0, y_max]])
+                    .color(Color32::LIGHT_BLUE)
+                    .fill_alpha(0.15)
+                    .name("Shift"),
+            );
+            plot.text(Text::new(PlotPoint::new(x0, y_max), shift_idx_name(r.idx)).anchor(Align2::LEFT_TOP));
+        }
+    }
+}
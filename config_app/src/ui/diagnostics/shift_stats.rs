@@ -0,0 +1,143 @@
+//! Shift time statistics over a session, per shift type
+use eframe::egui::{self, Ui};
+
+use super::rli::{shift_idx_name, DataShiftManager};
+
+/// Shift indexes the TCU reports (1 -> 2 up to 2 -> 1)
+const NUM_SHIFTS: usize = 8;
+/// A shift that went this long without a sample (Polling paused) is not timed, as
+/// its end was missed
+const MAX_SAMPLE_GAP_MS: u128 = 500;
+
+#[derive(Debug, Clone, Copy)]
+struct ShiftTimes {
+    count: u32,
+    total_ms: u128,
+    min_ms: u128,
+    max_ms: u128,
+}
+
+impl ShiftTimes {
+    fn new(duration_ms: u128) -> Self {
+        Self { count: 1, total_ms: duration_ms, min_ms: duration_ms, max_ms: duration_ms }
+    }
+
+    fn add(&mut self, duration_ms: u128) {
+        self.count += 1;
+        self.total_ms += duration_ms;
+        self.min_ms = self.min_ms.min(duration_ms);
+        self.max_ms = self.max_ms.max(duration_ms);
+    }
+
+    fn avg_ms(&self) -> u128 {
+        self.total_ms / self.count as u128
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ShiftStats {
+    /// Shift index and start timestamp of the shift in progress
+    active: Option<(u8, u128)>,
+    last_sample_ms: u128,
+    /// Indexed by shift index - 1
+    times: [Option<ShiftTimes>; NUM_SHIFTS],
+}
+
+impl ShiftStats {
+    /// Feed a new sample of shift manager data. A shift is timed once the shift index changes away from it
+    pub fn on_sample(&mut self, timestamp_ms: u128, sample: &DataShiftManager) {
+        if timestamp_ms.saturating_sub(self.last_sample_ms) > MAX_SAMPLE_GAP_MS {
+            self.active = None;
+        }
+        self.last_sample_ms = timestamp_ms;
+        match self.active {
+            Some((idx, _)) if idx == sample.shift_idx => return,
+            Some((idx, start_ms)) => {
+                let duration_ms = timestamp_ms - start_ms;
+                let slot = &mut self.times[idx as usize - 1];
+                match slot {
+                    Some(t) => t.add(duration_ms),
+                    None => *slot = Some(ShiftTimes::new(duration_ms)),
+                }
+                self.active = None;
+            },
+            None => {}
+        }
+        if (1..=NUM_SHIFTS as u8).contains(&sample.shift_idx) {
+            self.active = Some((sample.shift_idx, timestamp_ms));
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn make_ui(&mut self, ui: &mut Ui) {
+        if ui.button("Reset statistics").clicked() {
+            self.reset();
+        }
+        if self.times.iter().all(|t| t.is_none()) {
+            ui.label("No shifts recorded yet");
+            return;
+        }
+        egui::Grid::new("shift-stats").striped(true).show(ui, |ui| {
+            ui.strong("Shift");
+            ui.strong("Count");
+            ui.strong("Average");
+            ui.strong("Min");
+            ui.strong("Max");
+            ui.end_row();
+            for (i, t) in self.times.iter().enumerate() {
+                if let Some(t) = t {
+                    ui.label(shift_idx_name(i as u8 + 1));
+                    ui.label(t.count.to_string());
+                    ui.label(format!("{} ms", t.avg_ms()));
+                    ui.label(format!("{} ms", t.min_ms));
+                    ui.label(format!("{} ms", t.max_ms));
+                    ui.end_row();
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(shift_idx: u8) -> DataShiftManager {
+        DataShiftManager {
+            spc_pressure_mbar: 0,
+            mpc_pressure_mbar: 0,
+            tcc_pressure_mbar: 0,
+            shift_solenoid_pos: 0,
+            input_rpm: 0,
+            engine_rpm: 0,
+            output_rpm: 0,
+            engine_torque: 0,
+            req_engine_torque: 0,
+            atf_temp: 0,
+            shift_idx,
+        }
+    }
+
+    #[test]
+    fn times_each_shift_type() {
+        let mut stats = ShiftStats::default();
+        for (ts, idx) in [(0, 0), (100, 1), (200, 1), (400, 0), (500, 1), (800, 2), (900, 0)] {
+            stats.on_sample(ts, &sample(idx));
+        }
+        let one_two = stats.times[0].unwrap();
+        assert_eq!((one_two.count, one_two.min_ms, one_two.max_ms, one_two.avg_ms()), (2, 300, 300, 300));
+        assert_eq!(stats.times[1].unwrap().max_ms, 100);
+    }
+
+    #[test]
+    fn shift_over_a_polling_gap_is_not_timed() {
+        let mut stats = ShiftStats::default();
+        for (ts, idx) in [(0, 1), (2000, 0)] {
+            stats.on_sample(ts, &sample(idx));
+        }
+        assert!(stats.times[0].is_none());
+    }
+}
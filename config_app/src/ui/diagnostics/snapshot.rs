@@ -0,0 +1,99 @@
+//! One-off capture of every record, read back to back so the values belong together
+use std::{fs::File, io::Write, path::Path};
+
+use backend::diag::{ident::IdentData, Nag52Diag};
+use chrono::{DateTime, Local};
+use eframe::egui::{self, Color32, RichText, Ui};
+
+use super::rli::{LocalRecordData, RecordIdents, RecordView, Sentinel, TableContext};
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    taken_at: DateTime<Local>,
+    ident: Result<IdentData, String>,
+    serial: Result<String, String>,
+    records: Vec<(RecordIdents, Result<LocalRecordData, String>)>,
+}
+
+impl Snapshot {
+    /// Queries every record once, along with the TCU's identification
+    pub fn capture(nag: &Nag52Diag) -> Self {
+        let taken_at = Local::now();
+        let ident = nag.query_ecu_data().map_err(|e| e.to_string());
+        let serial = nag.get_ecu_sn().map_err(|e| e.to_string());
+        let records = RecordIdents::ALL
+            .into_iter()
+            .map(|r| (r, nag.with_kwp(|server| r.query_ecu(server)).map_err(|e| e.to_string())))
+            .collect();
+        Self { taken_at, ident, serial, records }
+    }
+
+    /// `ctx` is the live table context. Values that come from other records are taken from
+    /// this snapshot instead where possible, so the tables stay consistent with each other
+    pub fn make_ui(&self, ui: &mut Ui, ctx: &TableContext) {
+        let mut ctx = *ctx;
+        let sensors = self.records.iter().find_map(|(_, r)| match r {
+            Ok(LocalRecordData::Sensors(s)) => Some(s.clone()),
+            _ => None,
+        });
+        if let Some(s) = sensors {
+            ctx.v_batt = s.v_batt.available();
+            ctx.gear = s.gear();
+        }
+        ui.label(format!("Taken at {}", self.taken_at_text()));
+        for (record, res) in &self.records {
+            egui::CollapsingHeader::new(format!("{:?} (0x{:02X})", record, *record as u8))
+                .id_source(("snapshot", *record as u8))
+                .show(ui, |ui| match res {
+                    Ok(data) => {
+                        data.to_table(ui, &ctx);
+                    },
+                    Err(e) => {
+                        ui.label(RichText::new(format!("Could not read: {e}")).color(Color32::RED));
+                    }
+                });
+        }
+    }
+
+    fn taken_at_text(&self) -> String {
+        self.taken_at.format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    pub fn export(&self, path: &Path) -> std::io::Result<()> {
+        let mut f = File::create(path)?;
+        writeln!(f, "Diagnostics snapshot taken at {}", self.taken_at_text())?;
+        for (record, res) in &self.records {
+            writeln!(f, "\n# {:?} (0x{:02X})", record, *record as u8)?;
+            match res {
+                Ok(data) => writeln!(f, "{:#?}", data)?,
+                Err(e) => writeln!(f, "Could not read: {e}")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Exports the snapshot as a JSON document, with the raw field values of every record.
+    /// Records that could not be read have an `error` instead
+    pub fn export_json(&self, path: &Path) -> std::io::Result<()> {
+        fn or_error<T: serde::Serialize>(res: &Result<T, String>) -> serde_json::Value {
+            match res {
+                Ok(v) => serde_json::to_value(v).unwrap_or_default(),
+                Err(e) => serde_json::json!({ "error": e }),
+            }
+        }
+        let records: serde_json::Map<String, serde_json::Value> = self
+            .records
+            .iter()
+            .map(|(record, res)| (format!("{:?}", record), or_error(res)))
+            .collect();
+        let doc = serde_json::json!({
+            "taken_at": self.taken_at.to_rfc3339(),
+            "serial_number": or_error(&self.serial),
+            "ident": or_error(&self.ident),
+            "records": records,
+        });
+        let f = File::create(path)?;
+        serde_json::to_writer_pretty(f, &doc)?;
+        Ok(())
+    }
+}
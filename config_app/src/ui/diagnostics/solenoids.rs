@@ -10,9 +10,9 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::{window::{PageAction, get_context}};
+use crate::{repaint::{self, RepaintMode}, window::PageAction};
 
-use super::{rli::{DataSolenoids, LocalRecordData, RecordIdents, RLI_PLOT_INTERVAL}, RLI_CHART_DISPLAY_TIME};
+use super::{rli::{solenoid_fault_ui, DataSolenoids, LocalRecordData, RecordIdents, RLI_PLOT_INTERVAL}, RLI_CHART_DISPLAY_TIME};
 
 const UPDATE_DELAY_MS: u64 = 100;
 
@@ -51,7 +51,7 @@ impl SolenoidPage {
 
         let _ = thread::spawn(move || {
             while run_tt.load(Ordering::Relaxed) {
-                get_context().request_repaint();
+                repaint::plot_tick();
                 std::thread::sleep(Duration::from_millis(RLI_PLOT_INTERVAL));
             };
         });
@@ -72,6 +72,7 @@ impl SolenoidPage {
                                 launch_time_t.elapsed().as_millis() as u64,
                                 Ordering::Relaxed,
                             );
+                            repaint::new_data();
                         }
                     }
                     Ok(())
@@ -123,6 +124,8 @@ impl crate::window::InterfacePage for SolenoidPage {
 
         let curr = self.curr_values.read().unwrap().clone().unwrap_or_default();
         let prev = self.prev_values.read().unwrap().clone().unwrap_or_default();
+        // Judged on the latest raw record, not the blended values the bars show
+        solenoid_fault_ui(&curr, ui);
 
         let ms_since_update = std::cmp::min(
             UPDATE_DELAY_MS,
@@ -139,6 +142,11 @@ impl crate::window::InterfacePage for SolenoidPage {
             proportion_prev = 0.5;
             proportion_curr = 0.5;
         }
+        if repaint::mode() == RepaintMode::OnData {
+            // No frames in between samples to blend over
+            proportion_prev = 0.0;
+            proportion_curr = 1.0;
+        }
 
         let mut bars = Vec::new();
         let mut legend = Legend::default();
@@ -0,0 +1,134 @@
+//! Watch panel of pinned fields, taken from any record
+//!
+//! Only the records that have pinned fields are polled, one per cycle, so a few
+//! pinned values cost far less bus time than streaming whole records.
+use eframe::egui::{self, Ui};
+
+use super::rli::{ChartData, LocalRecordData, RecordIdents, RecordView};
+
+/// egui memory key of the pinned fields
+pub const PINS_ID: &str = "diag-pinned-fields";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pin {
+    record: RecordIdents,
+    group: String,
+    series: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Watch {
+    pins: Vec<Pin>,
+    /// Last chart data of each pinned record
+    latest: Vec<(RecordIdents, Vec<ChartData>)>,
+    /// Next record to poll
+    rotation: usize,
+}
+
+impl Watch {
+    fn records(&self) -> Vec<RecordIdents> {
+        let mut res: Vec<RecordIdents> = Vec::new();
+        for p in &self.pins {
+            if !res.contains(&p.record) {
+                res.push(p.record);
+            }
+        }
+        res
+    }
+
+    /// Record to poll next, or None if nothing is pinned
+    pub fn next_record(&mut self) -> Option<RecordIdents> {
+        let records = self.records();
+        if records.is_empty() {
+            return None;
+        }
+        self.rotation = (self.rotation + 1) % records.len();
+        Some(records[self.rotation])
+    }
+
+    pub fn on_sample(&mut self, record: RecordIdents, data: &LocalRecordData) {
+        if !self.pins.iter().any(|p| p.record == record) {
+            return;
+        }
+        let cd = data.to_chart_data();
+        match self.latest.iter_mut().find(|(r, _)| *r == record) {
+            Some((_, d)) => *d = cd,
+            None => self.latest.push((record, cd)),
+        }
+    }
+
+    /// Pins as stored in egui memory (Record ID, group, series)
+    pub fn to_persisted(&self) -> Vec<(u8, String, String)> {
+        self.pins.iter().map(|p| (p.record as u8, p.group.clone(), p.series.clone())).collect()
+    }
+
+    pub fn load_persisted(&mut self, pins: Vec<(u8, String, String)>) {
+        self.pins = pins
+            .into_iter()
+            .filter_map(|(id, group, series)| RecordIdents::from_id(id).map(|record| Pin { record, group, series }))
+            .collect();
+    }
+
+    fn is_pinned(&self, record: RecordIdents, group: &str, series: &str) -> bool {
+        self.pins.iter().any(|p| p.record == record && p.group == group && p.series == series)
+    }
+
+    fn set_pinned(&mut self, record: RecordIdents, group: &str, series: &str, pinned: bool) {
+        if pinned {
+            self.pins.push(Pin { record, group: group.to_string(), series: series.to_string() });
+        } else {
+            self.pins.retain(|p| !(p.record == record && p.group == group && p.series == series));
+            if !self.pins.iter().any(|p| p.record == record) {
+                self.latest.retain(|(r, _)| *r != record);
+            }
+        }
+    }
+
+    /// `selected` is the record currently shown in the table, whose fields can be pinned.
+    /// Returns true if the pins changed
+    pub fn make_ui(&mut self, ui: &mut Ui, selected: Option<(RecordIdents, &LocalRecordData)>) -> bool {
+        let mut changed = false;
+        if self.pins.is_empty() {
+            ui.label("Nothing pinned yet");
+        }
+        let mut unpin = None;
+        egui::Grid::new("diag-watch").striped(true).show(ui, |ui| {
+            for p in &self.pins {
+                let value = self.latest.iter()
+                    .find(|(r, _)| *r == p.record)
+                    .and_then(|(_, d)| d.iter().find(|g| g.group_name == p.group))
+                    .and_then(|g| g.data.iter().find(|(k, _, _)| *k == p.series));
+                ui.label(format!("{} - {}", p.group, p.series));
+                match value {
+                    Some((_, v, unit)) => ui.strong(format!("{:.2} {}", v, unit.unwrap_or_default())),
+                    None => ui.label("Waiting for data"),
+                };
+                if ui.small_button("Unpin").clicked() {
+                    unpin = Some(p.clone());
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(p) = unpin {
+            self.set_pinned(p.record, &p.group, &p.series, false);
+            changed = true;
+        }
+        if let Some((record, data)) = selected {
+            ui.collapsing("Pin fields from the selected record", |ui| {
+                for g in data.to_chart_data() {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label(format!("{}:", g.group_name));
+                        for (series, _, _) in &g.data {
+                            let mut pinned = self.is_pinned(record, &g.group_name, series);
+                            if ui.checkbox(&mut pinned, series.as_str()).changed() {
+                                self.set_pinned(record, &g.group_name, series, pinned);
+                                changed = true;
+                            }
+                        }
+                    });
+                }
+            });
+        }
+        changed
+    }
+}
@@ -1,5 +1,6 @@
 use backend::{diag::Nag52Diag, ecu_diagnostics::kwp2000::KwpSessionType};
 use eframe::egui::plot::{Legend, Line, Plot};
+use eframe::egui::{Button, Color32, RichText};
 use std::{
     char::MAX,
     sync::{
@@ -10,7 +11,7 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::{window::PageAction};
+use crate::{repaint, vehicle_state, window::PageAction};
 
 use rli::{DataSolenoids, LocalRecordData, RecordIdents};
 
@@ -56,8 +57,12 @@ impl IoManipulatorPage {
                                 launch_time_t.elapsed().as_millis() as u64,
                                 Ordering::Relaxed,
                             );
+                            repaint::new_data();
                         }
                     }
+                    if let Ok(LocalRecordData::Canbus(c)) = RecordIdents::CanDataDump.query_ecu(&mut server) {
+                        vehicle_state::update(&c);
+                    }
                     let taken = start.elapsed().as_millis() as u64;
                     if taken < UPDATE_DELAY_MS {
                         std::thread::sleep(Duration::from_millis(UPDATE_DELAY_MS - taken));
@@ -98,10 +103,19 @@ impl crate::window::InterfacePage for IoManipulatorPage {
             Upon exiting this page, the TCM will reboot to reset to its default state.
         ");
 
+        // Unknown until the first CAN sample, so treat it as unsafe until then
+        let interlock = match vehicle_state::latest() {
+            Some(state) => state.interlock_reason(),
+            None => Some("Waiting for the vehicle state"),
+        };
+        if let Some(reason) = interlock {
+            ui.label(RichText::new(format!("{reason}. Outputs can only be controlled with the car stationary and the engine off")).color(Color32::RED));
+        }
+
         if !self.show_ui {
             let mut btn_action = None;
             ui.horizontal(|row| {
-                if row.button("I understand").clicked() {}
+                if row.add_enabled(interlock.is_none(), Button::new("I understand")).clicked() {}
                 if row.button("Take me to safety").clicked() {
                     btn_action = Some(PageAction::Destroy);
                 }
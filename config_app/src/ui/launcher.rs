@@ -4,7 +4,7 @@ use std::{
 };
 
 use backend::{
-    diag::{AdapterHw, AdapterType, Nag52Diag},
+    diag::{AdapterHw, AdapterType, ConnectionOverrides, Nag52Diag},
     ecu_diagnostics::{
         hardware::{
             passthru::PassthruScanner, Hardware, HardwareInfo,
@@ -12,7 +12,7 @@ use backend::{
         },
         DiagError, DiagServerResult,
     },
-    hw::usb_scanner::Nag52UsbScanner,
+    hw::{sim::SimulatedTcu, usb_scanner::Nag52UsbScanner},
 };
 
 #[cfg(unix)]
@@ -22,7 +22,7 @@ use eframe::egui;
 use eframe::egui::*;
 
 use crate::{
-    ui::main::MainPage,
+    ui::{main::MainPage, settings_diff::SettingsDiffPage},
     window::{InterfacePage, PageAction},
 };
 
@@ -30,46 +30,105 @@ use super::widgets::range_display::range_display;
 
 type ScanResult = std::result::Result<Vec<String>, String>;
 
+/// Everything found by one scan of all adapter types
+#[derive(Debug, Clone, Default)]
+struct DeviceScan {
+    devices: Vec<(AdapterType, HardwareInfo)>,
+    /// SocketCAN interfaces that exist, but are down
+    down_can_ifaces: Vec<String>,
+}
+
 pub struct Launcher {
     selected: String,
     old_selected: String,
     launch_err: Option<String>,
-    usb_scanner: Nag52UsbScanner,
-    pt_scanner: PassthruScanner,
-    #[cfg(unix)]
-    scan_scanner: SocketCanScanner,
-    selected_device: String,
-    curr_api_type: AdapterType,
-    curr_dev_list: Vec<HardwareInfo>,
+    /// Result of the last device scan. None whilst scanning
+    scan: Arc<Mutex<Option<DeviceScan>>>,
+    /// Adapter type and name of the selected device
+    selected_device: Option<(AdapterType, String)>,
+    overrides: ConnectionOverrides,
 }
 
 impl Launcher {
     pub fn new() -> Self {
-        Self {
+        let mut res = Self {
             selected: "".into(),
             old_selected: "".into(),
             launch_err: None,
-            usb_scanner: Nag52UsbScanner::new(),
-            pt_scanner: PassthruScanner::new(),
-            #[cfg(unix)]
-            scan_scanner: SocketCanScanner::new(),
-            selected_device: String::new(),
-            curr_api_type: AdapterType::USB,
-            curr_dev_list: vec![],
+            scan: Arc::new(Mutex::new(None)),
+            selected_device: None,
+            overrides: ConnectionOverrides::default(),
+        };
+        res.start_scan();
+        res
+    }
+}
+
+/// Scans all adapter types at the same time, as some scanners (Passthru drivers especially) are slow
+fn scan_all_adapters() -> DeviceScan {
+    std::thread::scope(|s| {
+        let usb = s.spawn(|| Launcher::get_device_list(&Nag52UsbScanner::new()));
+        let pt = s.spawn(|| Launcher::get_device_list(&PassthruScanner::new()));
+        #[cfg(unix)]
+        let can = s.spawn(|| Launcher::get_device_list(&SocketCanScanner::new()));
+        let mut scan = DeviceScan::default();
+        let mut add = |ty: AdapterType, found: std::thread::Result<Vec<HardwareInfo>>| match found {
+            Ok(devs) => scan.devices.extend(devs.into_iter().map(|d| (ty, d))),
+            Err(_) => log::error!("Scanning for {:?} devices failed", ty),
+        };
+        add(AdapterType::USB, usb.join());
+        add(AdapterType::Passthru, pt.join());
+        #[cfg(unix)]
+        {
+            // Interfaces that are down cannot be opened, so only offer the ones that are up
+            let (up, down): (Vec<_>, Vec<_>) = can.join().unwrap_or_default()
+                .into_iter()
+                .partition(|d| can_iface_is_up(&d.name));
+            add(AdapterType::SocketCAN, Ok(up));
+            scan.down_can_ifaces = down.into_iter().map(|d| d.name).collect();
         }
+        scan
+    })
+}
+
+pub(crate) fn adapter_name(ty: AdapterType) -> &'static str {
+    match ty {
+        AdapterType::USB => "USB",
+        AdapterType::Passthru => "Passthru",
+        #[cfg(unix)]
+        AdapterType::SocketCAN => "SocketCAN",
+        AdapterType::Simulated => "Demo",
     }
 }
 
 impl Launcher {
-    pub fn open_device(&self, name: &str) -> DiagServerResult<Nag52Diag> {
+    fn start_scan(&mut self) {
+        let scan = self.scan.clone();
+        *scan.lock().unwrap() = None;
+        std::thread::spawn(move || {
+            let res = scan_all_adapters();
+            *scan.lock().unwrap() = Some(res);
+        });
+    }
+
+    pub fn open_device(&self, ty: AdapterType, name: &str) -> DiagServerResult<Nag52Diag> {
         println!("Opening '{}'", name);
         let hw_info = self
-            .curr_dev_list
-            .iter()
-            .find(|x| x.name == name)
+            .scan
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|s| s.devices.iter().find(|(t, x)| *t == ty && x.name == name).map(|(_, x)| x.clone()))
             .ok_or(DiagError::ParameterInvalid)?;
-        let hw = AdapterHw::try_connect(hw_info, self.curr_api_type).map_err(|e| DiagError::from(Arc::new(e)))?;
-        Nag52Diag::new(hw)
+        log::info!("Connecting to '{}' ({:?}, overrides {:?})", name, ty, self.overrides);
+        let res = AdapterHw::try_connect(&hw_info, ty)
+            .map_err(|e| DiagError::from(Arc::new(e)))
+            .and_then(|hw| Nag52Diag::new_with_overrides(hw, self.overrides));
+        match &res {
+            Ok(_) => log::info!("Connected to '{}'", name),
+            Err(e) => log::error!("Could not connect to '{}': {}", name, e),
+        }
+        res
     }
 
     pub fn get_device_list<T, X: Hardware>(scanner: &T) -> Vec<HardwareInfo>
@@ -80,70 +139,192 @@ impl Launcher {
     }
 }
 
+/// True if the network interface has IFF_UP set. Interfaces whose flags cannot be read are assumed to be up
+#[cfg(unix)]
+fn can_iface_is_up(name: &str) -> bool {
+    const IFF_UP: u32 = 0x1;
+    std::fs::read_to_string(format!("/sys/class/net/{name}/flags"))
+        .ok()
+        .and_then(|f| u32::from_str_radix(f.trim().trim_start_matches("0x"), 16).ok())
+        .map(|flags| flags & IFF_UP != 0)
+        .unwrap_or(true)
+}
+
+/// Device name, with the vendor if it is known
+fn device_label(info: &HardwareInfo) -> String {
+    match &info.vendor {
+        Some(v) if !v.is_empty() => format!("{} ({})", info.name, v),
+        _ => info.name.clone(),
+    }
+}
+
+fn device_info_ui(ui: &mut Ui, info: &HardwareInfo) {
+    egui::Grid::new("device-info").striped(true).show(ui, |ui| {
+        let rows = [
+            ("Vendor", &info.vendor),
+            ("Driver library", &info.library_location),
+            ("Library version", &info.library_version),
+            ("API version", &info.api_version),
+            ("Device firmware", &info.device_fw_version),
+        ];
+        for (name, value) in rows {
+            ui.label(name);
+            ui.label(value.as_deref().unwrap_or("Unknown"));
+            ui.end_row();
+        }
+    });
+}
+
+/// Checkbox to enable an override, with the value editor next to it
+fn isotp_override_ui(ui: &mut Ui, name: &str, value: &mut Option<u8>) {
+    ui.horizontal(|row| {
+        let mut enabled = value.is_some();
+        row.checkbox(&mut enabled, format!("Override {name}"));
+        match (enabled, value.as_mut()) {
+            (true, Some(v)) => {
+                row.add(DragValue::new(v).hexadecimal(2, false, true));
+            },
+            (true, None) => *value = Some(0),
+            (false, _) => *value = None,
+        }
+    });
+}
+
+/// Checkbox to enable a timeout override (Default 10000ms), with the value editor next to it
+fn timeout_override_ui(ui: &mut Ui, name: &str, value: &mut Option<u32>) {
+    ui.horizontal(|row| {
+        let mut enabled = value.is_some();
+        row.checkbox(&mut enabled, name);
+        match (enabled, value.as_mut()) {
+            (true, Some(v)) => {
+                row.add(DragValue::new(v).clamp_range(500..=60000).speed(100).suffix("ms"));
+            },
+            (true, None) => *value = Some(10000),
+            (false, _) => *value = None,
+        }
+    });
+}
+
 impl InterfacePage for Launcher {
     fn make_ui(&mut self, ui: &mut Ui, frame: &eframe::Frame) -> crate::window::PageAction {
         ui.label("Ultimate-Nag52 configuration utility!");
         ui.label(
-            "Please plug in your TCM via USB and select the correct port, or select another API",
+            "Please plug in your TCM via USB or connect your OBD adapter, then select it below",
         );
 
-        ui.radio_value(&mut self.curr_api_type, AdapterType::USB, "USB connection");
-        ui.radio_value(
-            &mut self.curr_api_type,
-            AdapterType::Passthru,
-            "Passthru OBD adapter",
-        );
-        #[cfg(unix)]
-        {
-            ui.radio_value(
-                &mut self.curr_api_type,
-                AdapterType::SocketCAN,
-                "SocketCAN device",
-            );
-        }
         ui.heading("Devices");
 
-        let dev_list = match self.curr_api_type {
-            AdapterType::Passthru => Self::get_device_list(&self.pt_scanner),
-            #[cfg(unix)]
-            AdapterType::SocketCAN => Self::get_device_list(&self.scan_scanner),
-            AdapterType::USB => Self::get_device_list(&self.usb_scanner),
-        };
-        self.curr_dev_list = dev_list.clone();
-
-        if dev_list.len() == 0 {
-        } else {
-            egui::ComboBox::from_label("Select device")
-                .width(400.0)
-                .selected_text(&self.selected_device)
-                .show_ui(ui, |cb_ui| {
-                    for dev in dev_list {
-                        cb_ui.selectable_value(
-                            &mut self.selected_device,
-                            dev.name.clone(),
-                            dev.name,
-                        );
-                    }
+        let scan = self.scan.lock().unwrap().clone();
+        match &scan {
+            None => {
+                ui.horizontal(|row| {
+                    row.spinner();
+                    row.label("Looking for USB, Passthru and SocketCAN devices...");
                 });
+            },
+            Some(scan) => {
+                #[cfg(unix)]
+                for iface in &scan.down_can_ifaces {
+                    ui.label(format!("{iface} is down. Bring it up with 'sudo ip link set {iface} up type can bitrate 500000'"));
+                }
+                if scan.devices.is_empty() {
+                    ui.label("No devices found. Is your TCU plugged in via USB, or your OBD adapter's driver installed?");
+                } else {
+                    let selected_text = match &self.selected_device {
+                        Some((ty, name)) => format!("[{}] {}", adapter_name(*ty), name),
+                        None => String::new(),
+                    };
+                    egui::ComboBox::from_label("Select device")
+                        .width(400.0)
+                        .selected_text(selected_text)
+                        .show_ui(ui, |cb_ui| {
+                            for (ty, dev) in &scan.devices {
+                                cb_ui.selectable_value(
+                                    &mut self.selected_device,
+                                    Some((*ty, dev.name.clone())),
+                                    format!("[{}] {}", adapter_name(*ty), device_label(dev)),
+                                );
+                            }
+                        });
+                    // Several J2534 drivers can be installed at once, so show which one will be used
+                    if let Some((AdapterType::Passthru, name)) = &self.selected_device {
+                        if let Some((_, info)) = scan.devices.iter().find(|(t, d)| *t == AdapterType::Passthru && d.name == *name) {
+                            device_info_ui(ui, info);
+                        }
+                    }
+                }
+            }
         }
 
-        if !self.selected_device.is_empty() && ui.button("Launch configuration app").clicked() {
-            match self.open_device(&self.selected_device) {
-                Ok(mut dev) => {
-                    return PageAction::Add(Box::new(MainPage::new(dev)));
+        ui.collapsing("Advanced connection settings", |adv| {
+            adv.label("Only change these if you get dropped frames during large transfers (E.g. coredump reads)");
+            isotp_override_ui(adv, "Block size", &mut self.overrides.block_size);
+            isotp_override_ui(adv, "STmin", &mut self.overrides.st_min);
+            adv.separator();
+            adv.label("Add a delay between requests if settings writes fail intermittently with negative responses");
+            adv.horizontal(|row| {
+                let mut enabled = self.overrides.command_cooldown_ms.is_some();
+                row.checkbox(&mut enabled, "Command cooldown");
+                match (enabled, self.overrides.command_cooldown_ms.as_mut()) {
+                    (true, Some(v)) => {
+                        row.add(DragValue::new(v).clamp_range(0..=100).suffix("ms"));
+                    },
+                    (true, None) => self.overrides.command_cooldown_ms = Some(10),
+                    (false, _) => self.overrides.command_cooldown_ms = None,
+                }
+            });
+            adv.separator();
+            adv.label("Shorten the tester present interval if the TCU drops out of its diagnostic session. Longer intervals leave more time for other requests");
+            adv.horizontal(|row| {
+                let mut enabled = self.overrides.tester_present_interval_ms.is_some();
+                row.checkbox(&mut enabled, "Tester present interval");
+                match (enabled, self.overrides.tester_present_interval_ms.as_mut()) {
+                    (true, Some(v)) => {
+                        row.add(DragValue::new(v).clamp_range(500..=5000).speed(10).suffix("ms"));
+                    },
+                    (true, None) => self.overrides.tester_present_interval_ms = Some(2000),
+                    (false, _) => self.overrides.tester_present_interval_ms = None,
+                }
+            });
+            let mut no_response = self.overrides.tester_present_require_response == Some(false);
+            adv.checkbox(&mut no_response, "Don't wait for tester present responses")
+                .on_hover_text("Can help if long transfers fail, but a lost connection is only noticed on the next request");
+            self.overrides.tester_present_require_response = if no_response { Some(false) } else { None };
+            adv.separator();
+            adv.label("Shorter timeouts report a missing TCU sooner. Slow operations (E.g. flashing) may need longer ones");
+            timeout_override_ui(adv, "Read timeout", &mut self.overrides.read_timeout_ms);
+            timeout_override_ui(adv, "Write timeout", &mut self.overrides.write_timeout_ms);
+        });
+
+        if let Some((ty, name)) = self.selected_device.clone() {
+            if ui.button("Launch configuration app").clicked() {
+                match self.open_device(ty, &name) {
+                    Ok(mut dev) => {
+                        return PageAction::Add(Box::new(MainPage::new(dev)));
+                    }
+                    Err(e) => self.launch_err = Some(format!("Cannot open device: {}", e)),
                 }
-                Err(e) => self.launch_err = Some(format!("Cannot open device: {}", e)),
             }
         }
 
-        if ui.button("Refresh device list").clicked() {
-            self.pt_scanner = PassthruScanner::new();
-            self.usb_scanner = Nag52UsbScanner::new();
-            #[cfg(unix)]
-            {
-                self.scan_scanner = SocketCanScanner::new();
+        if ui.button("Launch demo mode (Simulated TCU)")
+            .on_hover_text("No hardware needed. Live data is simulated, most other pages will not work")
+            .clicked()
+        {
+            log::info!("Launching demo mode");
+            match Nag52Diag::new(AdapterHw::Simulated(SimulatedTcu::new())) {
+                Ok(dev) => return PageAction::Add(Box::new(MainPage::new(dev))),
+                Err(e) => self.launch_err = Some(format!("Cannot start demo mode: {}", e)),
             }
-            self.selected_device.clear();
+        }
+
+        if ui.button("Compare settings files (Offline)").clicked() {
+            return PageAction::Add(Box::new(SettingsDiffPage::new()));
+        }
+
+        if ui.add_enabled(scan.is_some(), egui::Button::new("Refresh device list")).clicked() {
+            self.start_scan();
+            self.selected_device = None;
         }
 
         if let Some(e) = &self.launch_err {
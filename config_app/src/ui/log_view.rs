@@ -0,0 +1,215 @@
+//! Viewer for the TCU's ESP log stream (USB connections only)
+use std::{collections::VecDeque, fs::File, io::Write, path::Path};
+
+use backend::hw::usb::{EspLogLevel, EspLogMessage};
+use eframe::{egui::{self, RichText, Ui}, epaint::Color32};
+use egui_extras::{Column, TableBuilder};
+use egui_toast::ERROR_COLOR;
+
+use crate::file_log::{self, RotatingFile};
+
+/// Messages kept in the view (And in the backlog whilst paused)
+const MAX_LOG_MESSAGES: usize = 1000;
+
+fn level_color(lvl: EspLogLevel, is_dark: bool) -> Color32 {
+    match lvl {
+        EspLogLevel::Verbose => Color32::GRAY,
+        EspLogLevel::Debug => Color32::DEBUG_COLOR,
+        EspLogLevel::Info => if is_dark { Color32::GREEN } else { Color32::DARK_GREEN },
+        EspLogLevel::Warn => if is_dark { Color32::YELLOW } else { Color32::GOLD },
+        EspLogLevel::Error => if is_dark { ERROR_COLOR } else { Color32::DARK_RED },
+    }
+}
+
+/// Text of a log line in its level's colour. Errors and warnings are bold so they stand out
+fn level_text(text: impl Into<String>, lvl: EspLogLevel, is_dark: bool) -> RichText {
+    let t = RichText::new(text).color(level_color(lvl, is_dark));
+    match lvl {
+        EspLogLevel::Warn | EspLogLevel::Error => t.strong(),
+        _ => t,
+    }
+}
+
+fn level_name(lvl: EspLogLevel) -> &'static str {
+    match lvl {
+        EspLogLevel::Verbose => "VERBOSE",
+        EspLogLevel::Debug => "DEBUG",
+        EspLogLevel::Info => "INFO",
+        EspLogLevel::Warn => "WARN",
+        EspLogLevel::Error => "ERROR",
+    }
+}
+
+fn push_capped(q: &mut VecDeque<EspLogMessage>, msg: EspLogMessage) {
+    q.push_back(msg);
+    if q.len() > MAX_LOG_MESSAGES {
+        q.pop_front();
+    }
+}
+
+pub struct LogView {
+    logs: VecDeque<EspLogMessage>,
+    /// Messages received whilst paused, added to the view on resume
+    backlog: VecDeque<EspLogMessage>,
+    paused: bool,
+    auto_scroll: bool,
+    /// Levels to show, in the order of [EspLogLevel::ALL]
+    levels: [bool; 5],
+    /// Only show messages whose tag or text contain this
+    filter: String,
+    /// File every message is written to as it arrives, regardless of filters and pausing
+    capture: Option<RotatingFile>,
+    capture_err: Option<String>,
+}
+
+impl LogView {
+    pub fn new() -> Self {
+        Self {
+            logs: VecDeque::new(),
+            backlog: VecDeque::new(),
+            paused: false,
+            auto_scroll: true,
+            levels: [true; 5],
+            filter: String::new(),
+            capture: None,
+            capture_err: None,
+        }
+    }
+
+    pub fn push(&mut self, msg: EspLogMessage) {
+        if let Some(f) = self.capture.as_mut() {
+            let li = msg.lvl.letter();
+            let line = format!("{} {} {}{} - ({}) {}\n", file_log::host_timestamp(), msg.timestamp, li, li, msg.tag, msg.msg);
+            if let Err(e) = f.write_line(&line) {
+                self.capture_err = Some(format!("Stopped saving the log: {e}"));
+                self.capture = None;
+            }
+        }
+        if self.paused {
+            push_capped(&mut self.backlog, msg);
+        } else {
+            push_capped(&mut self.logs, msg);
+        }
+    }
+
+    fn is_shown(&self, msg: &EspLogMessage) -> bool {
+        let level_ok = EspLogLevel::ALL.iter().zip(self.levels).any(|(l, on)| on && *l == msg.lvl);
+        let f = self.filter.to_lowercase();
+        level_ok && (f.is_empty() || msg.tag.to_lowercase().contains(&f) || msg.msg.to_lowercase().contains(&f))
+    }
+
+    fn save(path: &Path, msgs: &[&EspLogMessage]) -> std::io::Result<()> {
+        let mut f = File::create(path)?;
+        for msg in msgs {
+            let li = msg.lvl.letter();
+            writeln!(f, "{} {}{} - ({}) {}", msg.timestamp, li, li, msg.tag, msg.msg)?;
+        }
+        Ok(())
+    }
+
+    pub fn make_ui(&mut self, ui: &mut Ui) {
+        let is_dark = ui.style().visuals.dark_mode;
+        ui.horizontal(|ui| {
+            for (lvl, on) in EspLogLevel::ALL.iter().zip(self.levels.iter_mut()) {
+                ui.checkbox(on, RichText::new(lvl.letter().to_string()).color(level_color(*lvl, is_dark)))
+                    .on_hover_text(level_name(*lvl));
+            }
+            ui.separator();
+            ui.label("Filter:");
+            ui.text_edit_singleline(&mut self.filter).on_hover_text("Matches the module or the message");
+        });
+        let shown: Vec<&EspLogMessage> = self.logs.iter().filter(|m| self.is_shown(m)).collect();
+        let shown_count = shown.len();
+        TableBuilder::new(ui)
+            .striped(false)
+            .resizable(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::auto()) // Level
+            .column(Column::initial(100.0).at_least(40.0)) // Timestamp
+            .column(Column::initial(100.0).range(40.0..=300.0).clip(true)) // Module
+            .column(Column::remainder()) // Message
+            .stick_to_bottom(self.auto_scroll)
+            .max_scroll_height(400.0)
+            .min_scrolled_height(100.0)
+            .header(20.0, |mut header| {
+                header.col(|ui| {
+                    ui.strong("Level");
+                });
+                header.col(|ui| {
+                    ui.strong("Since boot");
+                });
+                header.col(|ui| {
+                    ui.strong("Module");
+                });
+                header.col(|ui| {
+                    ui.strong("Message");
+                });
+            })
+            .body(|body| {
+                body.rows(10.0, shown.len(), |row_index, mut row| {
+                    let msg = shown[row_index];
+                    row.col(|ui| {
+                        ui.label(level_text(level_name(msg.lvl), msg.lvl, is_dark));
+                    });
+                    row.col(|ui| {
+                        ui.label(level_text(format!("{} Ms", msg.timestamp), msg.lvl, is_dark));
+                    });
+                    row.col(|ui| {
+                        ui.label(level_text(&msg.tag, msg.lvl, is_dark));
+                    });
+                    row.col(|ui| {
+                        ui.label(level_text(&msg.msg, msg.lvl, is_dark));
+                    });
+                })
+            });
+        ui.horizontal(|ui| {
+            ui.label(format!("Showing {} of {} messages", shown_count, self.logs.len()));
+            ui.checkbox(&mut self.auto_scroll, "Auto-scroll");
+            let pause_text = if self.paused {
+                format!("Resume ({} new)", self.backlog.len())
+            } else {
+                "Pause".to_string()
+            };
+            if ui.button(pause_text).clicked() {
+                self.paused = !self.paused;
+                if !self.paused {
+                    while let Some(msg) = self.backlog.pop_front() {
+                        push_capped(&mut self.logs, msg);
+                    }
+                }
+            }
+            if ui.button("Clear logs").clicked() {
+                self.logs.clear();
+                self.backlog.clear();
+            }
+            let mut capture = self.capture.is_some();
+            if ui.checkbox(&mut capture, "Save continuously")
+                .on_hover_text("Writes every message, with the time on this computer, to the log folder as it arrives")
+                .changed()
+            {
+                self.capture_err = None;
+                self.capture = None;
+                if capture {
+                    match file_log::open_esp_log() {
+                        Ok(f) => self.capture = Some(f),
+                        Err(e) => self.capture_err = Some(format!("Could not open log file: {e}")),
+                    }
+                }
+            }
+            if ui.button("Save logs to disk").on_hover_text("Saves the messages that are shown").clicked() {
+                if let Some(p) = rfd::FileDialog::new().add_filter("log file", &["log"]).save_file() {
+                    let shown: Vec<&EspLogMessage> = self.logs.iter().filter(|m| self.is_shown(m)).collect();
+                    if let Err(e) = Self::save(&p, &shown) {
+                        log::error!("Could not save logs to {}: {e}", p.display());
+                    }
+                }
+            }
+        });
+        if let Some(f) = &self.capture {
+            ui.label(format!("Saving to {}", f.path().display()));
+        }
+        if let Some(e) = &self.capture_err {
+            ui.label(RichText::new(e).color(ERROR_COLOR));
+        }
+    }
+}
@@ -1,16 +1,22 @@
 use backend::diag::DataState;
 use backend::diag::ident::IdentData;
 use backend::diag::Nag52Diag;
+use backend::ecu_diagnostics::kwp2000::{KwpSessionType, ResetType};
 use config_app_macros::include_base64;
 use eframe::egui;
 use eframe::Frame;
 use eframe::egui::RichText;
 use eframe::epaint::Color32;
 use eframe::epaint::mutex::RwLock;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use crate::i18n::{tr, tr_fmt};
 use crate::window::{InterfacePage, PageAction};
 
 use super::nvs_editor::NvsEditor;
+use super::report::export_report;
 use super::settings_ui_gen::TcuAdvSettingsUi;
 use super::updater::UpdatePage;
 use super::{
@@ -21,30 +27,86 @@ use super::{
 use crate::ui::diagnostics::DiagnosticsPage;
 
 pub struct MainPage {
-    diag_server: &'static mut Nag52Diag,
+    diag_server: Nag52Diag,
     info: Arc<RwLock<DataState<IdentData>>>,
     sn: Arc<RwLock<DataState<String>>>,
-    first_run: bool
+    /// Bumped to start a new ident query, or to cancel one. A query only publishes its
+    /// result if this has not changed since it started
+    ident_query: Arc<AtomicU64>,
+    /// Connection generation the ident was read on, see [Nag52Diag::connection_generation]
+    ident_generation: u32,
+    first_run: bool,
+    confirm_reboot: bool,
+    /// Result of a reboot in progress. None until the TCU has come back (Or not)
+    reboot: Option<Arc<Mutex<Option<Result<(), String>>>>>,
+    /// Result of a vehicle report export in progress. None until it has been written
+    report: Option<Arc<Mutex<Option<Result<PathBuf, String>>>>>,
+}
+
+/// How long to wait for the TCU to answer again after a reboot
+const REBOOT_WAIT_ATTEMPTS: u32 = 20;
+const REBOOT_RETRY_MS: u64 = 500;
+
+/// Resets the TCU, then waits for it to answer again
+fn reboot_tcu(nag: &Nag52Diag) -> Result<(), String> {
+    nag.with_kwp(|k| k.kwp_reset_ecu(ResetType::PowerOnReset.into())).map_err(|e| e.to_string())?;
+    let mut last_err = String::new();
+    for _ in 0..REBOOT_WAIT_ATTEMPTS {
+        std::thread::sleep(Duration::from_millis(REBOOT_RETRY_MS));
+        match nag.with_kwp(|k| k.kwp_set_session(KwpSessionType::Normal.into())) {
+            Ok(_) => return Ok(()),
+            Err(e) => last_err = e.to_string(),
+        }
+    }
+    Err(last_err)
 }
 
 impl MainPage {
     pub fn new(nag: Nag52Diag) -> Self {
-        // Static mutable ref creation
-        // this Nag52 lives the whole lifetime of the app once created,
-        // so we have no need to clone it constantly, just throw the pointer around at
-        // the subpages.
-        //
-        // We can keep it here as a ref to create a box from it when Drop() is called
-        // so we can drop it safely without a memory leak
-        let static_ref: &'static mut Nag52Diag = Box::leak(Box::new(nag));
-        
+        // Nag52Diag is a cheap to clone handle to the shared diag server,
+        // so subpages just get their own clone of it
         Self {
-            diag_server: static_ref,
+            diag_server: nag,
             info: Arc::new(RwLock::new(DataState::Unint)),
             sn: Arc::new(RwLock::new(DataState::Unint)),
+            ident_query: Arc::new(AtomicU64::new(0)),
+            ident_generation: 0,
             first_run: false,
+            confirm_reboot: false,
+            reboot: None,
+            report: None,
         }
     }
+
+    /// Reads the ECU ident and serial number in the background
+    fn query_ident(&mut self) {
+        self.ident_generation = self.diag_server.connection_generation();
+        let tcu = self.diag_server.clone();
+        let setting_lock = self.info.clone();
+        let sn_lock = self.sn.clone();
+        *setting_lock.write() = DataState::Unint;
+        *sn_lock.write() = DataState::Unint;
+        let query = self.ident_query.clone();
+        let id = query.fetch_add(1, Ordering::Relaxed) + 1;
+        std::thread::spawn(move|| {
+            println!("Querying TCU");
+            let state = match tcu.query_ecu_data() {
+                Ok(info) => DataState::LoadOk(info),
+                Err(err) => DataState::LoadErr(err.to_string()),
+            };
+            if query.load(Ordering::Relaxed) != id {
+                return;
+            }
+            *setting_lock.write() = state;
+            let state: DataState<String> = match tcu.get_ecu_sn() {
+                Ok(sn) => DataState::LoadOk(sn),
+                Err(err) => DataState::LoadErr(err.to_string()),
+            };
+            if query.load(Ordering::Relaxed) == id {
+                *sn_lock.write() = state;
+            }
+        });
+    }
 }
 
 impl InterfacePage for MainPage {
@@ -54,7 +116,7 @@ impl InterfacePage for MainPage {
             return PageAction::RegisterNag(Arc::new(self.diag_server.clone()));
         }
         ui.vertical_centered(|x| {
-            x.heading("Welcome to the Ultimate-NAG52 configuration app!");
+            x.heading(tr("main.welcome"));
             let os_logo = if cfg!(windows) {
                 egui::special_emojis::OS_WINDOWS
             } else if cfg!(unix) {
@@ -62,9 +124,9 @@ impl InterfacePage for MainPage {
             } else {
                 egui::special_emojis::OS_APPLE
             };
-            x.label(format!("Config app version {} for {} (Build {})", env!("CARGO_PKG_VERSION"), os_logo, env!("GIT_BUILD")));
+            x.label(tr_fmt("main.version", &[("version", &env!("CARGO_PKG_VERSION")), ("os", &os_logo), ("build", &env!("GIT_BUILD"))]));
             if env!("GIT_BUILD").ends_with("-dirty") {
-                x.label(RichText::new("Warning. You have a modified copy of the config app! Bugs may be present!").color(Color32::RED));
+                x.label(RichText::new(tr("main.modified_warning")).color(Color32::RED));
             } else {
                 // Check for updates
                 if env!("GIT_BRANCH") == "main" {
@@ -77,78 +139,166 @@ impl InterfacePage for MainPage {
             }
         });
         ui.separator();
-        ui.label(r#"
-            This application lets you do many things with the TCU!
-            If you are lost or need help, you can always consult the wiki below,
-            or join the Ultimate-NAG52 discussions Telegram group!
-        "#);
-        ui.heading("Useful links");
+        ui.label(tr("main.intro"));
+        ui.heading(tr("main.useful_links"));
         // Weblinks are base64 encoded to avoid potential scraping
-        ui.hyperlink_to(format!("📓 Ultimate-NAG52 wiki"), include_base64!("ZG9jcy51bHRpbWF0ZS1uYWc1Mi5uZXQ"));
-        ui.hyperlink_to(format!("💁 Ultimate-NAG52 dicsussion group"), include_base64!("aHR0cHM6Ly90Lm1lLyt3dU5wZkhua0tTQmpNV0pr"));
-        ui.hyperlink_to(format!(" Project progress playlist"), include_base64!("aHR0cHM6Ly93d3cueW91dHViZS5jb20vcGxheWxpc3Q_bGlzdD1QTHhydy00VnQ3eHR1OWQ4bENrTUNHMF9LN29IY3NTTXRG"));
-        ui.label("Code repositories");
-        ui.hyperlink_to(format!(" The configuration app"), include_base64!("aHR0cHM6Ly9naXRodWIuY29tL3JuZC1hc2gvdWx0aW1hdGUtbmFnNTItY29uZmlnLWFwcA"));
-        ui.hyperlink_to(format!(" TCU Firmware"), include_base64!("aHR0cDovL2dpdGh1Yi5jb20vcm5kLWFzaC91bHRpbWF0ZS1uYWc1Mi1mdw"));
+        ui.hyperlink_to(tr("main.link_wiki"), include_base64!("ZG9jcy51bHRpbWF0ZS1uYWc1Mi5uZXQ"));
+        ui.hyperlink_to(tr("main.link_group"), include_base64!("aHR0cHM6Ly90Lm1lLyt3dU5wZkhua0tTQmpNV0pr"));
+        ui.hyperlink_to(tr("main.link_playlist"), include_base64!("aHR0cHM6Ly93d3cueW91dHViZS5jb20vcGxheWxpc3Q_bGlzdD1QTHhydy00VnQ3eHR1OWQ4bENrTUNHMF9LN29IY3NTTXRG"));
+        ui.label(tr("main.code_repos"));
+        ui.hyperlink_to(tr("main.link_app_repo"), include_base64!("aHR0cHM6Ly9naXRodWIuY29tL3JuZC1hc2gvdWx0aW1hdGUtbmFnNTItY29uZmlnLWFwcA"));
+        ui.hyperlink_to(tr("main.link_fw_repo"), include_base64!("aHR0cDovL2dpdGh1Yi5jb20vcm5kLWFzaC91bHRpbWF0ZS1uYWc1Mi1mdw"));
         ui.add(egui::Separator::default());
         let mut create_page = None;
         ui.vertical_centered(|v| {
-            v.heading("Tools");
-            if v.button("Updater").clicked() {
+            v.heading(tr("main.tools"));
+            if v.button(tr("main.btn_updater")).clicked() {
                 create_page = Some(PageAction::Add(Box::new(UpdatePage::new(
                     self.diag_server.clone(),
                 ))));
             }
-            if v.button("Diagnostics").clicked() {
+            if v.button(tr("main.btn_diagnostics")).clicked() {
                 create_page = Some(PageAction::Add(Box::new(DiagnosticsPage::new(
                     self.diag_server.clone(),
                 ))));
             }
-            if v.button("Solenoid live view").clicked() {
+            if v.button(tr("main.btn_solenoids")).clicked() {
                 create_page = Some(PageAction::Add(Box::new(SolenoidPage::new(
                     self.diag_server.clone(),
                 ))));
             }
-            if v.button("IO Manipulator").clicked() {
+            if v.button(tr("main.btn_io")).clicked() {
                 create_page = Some(PageAction::Add(Box::new(IoManipulatorPage::new(
                     self.diag_server.clone(),
                 ))));
             }
-            if v.button("Diagnostic routine executor").clicked() {
+            if v.button(tr("main.btn_routines")).clicked() {
                 create_page = Some(PageAction::Add(Box::new(RoutinePage::new(
                     self.diag_server.clone(),
                 ))));
             }
-            if v.button("Map Tuner").clicked() {
+            if v.button(tr("main.btn_map_tuner")).clicked() {
                 create_page = Some(PageAction::Add(Box::new(MapEditor::new(
                     self.diag_server.clone(),
                 ))));
             }
-            if v.button("TCU Program settings").on_hover_text("CAUTION. DANGEROUS!").clicked() {
+            if v.button(tr("main.btn_settings")).on_hover_text(tr("main.dangerous")).clicked() {
                 create_page = Some(PageAction::Add(Box::new(TcuAdvSettingsUi::new(
                     self.diag_server.clone(),
                 ))));
             }
-            if v.button("NVS Editor").on_hover_text("CAUTION. DANGEROUS!").clicked() {
+            if v.button(tr("main.btn_nvs")).on_hover_text(tr("main.dangerous")).clicked() {
                 create_page = Some(PageAction::Add(Box::new(NvsEditor::new(
                     self.diag_server.clone(),
                 ))));
             }
-            if v.button("Configure drive profiles").clicked() {
+            if v.add_enabled(self.report.is_none(), egui::Button::new(tr("main.btn_report")))
+                .on_hover_text(tr("main.report_hint"))
+                .clicked()
+            {
+                self.report = export_report(&self.diag_server);
+            }
+            if v.button(tr("main.btn_drive_profiles")).clicked() {
                 create_page = Some(
                     PageAction::SendNotification { 
-                        text: "You have found a unimplemented feature!".into(), 
+                        text: tr("main.unimplemented"), 
                         kind: egui_toast::ToastKind::Info 
                     }
                 );
             }
-            if v.button("Configure vehicle / gearbox").clicked() {
+            if v.button(tr("main.btn_vehicle_config")).clicked() {
                 create_page = Some(PageAction::Add(Box::new(ConfigPage::new(
                     self.diag_server.clone(),
                 ))));
             }
+            if v.add_enabled(self.reboot.is_none(), egui::Button::new(tr("main.btn_reboot")))
+                .on_hover_text(tr("main.reboot_hint"))
+                .clicked()
+            {
+                self.confirm_reboot = true;
+            }
         });
 
+        if self.confirm_reboot {
+            let mut open = true;
+            egui::Window::new(tr("main.reboot_confirm_title"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .open(&mut open)
+                .show(ui.ctx(), |win| {
+                    win.label(tr("main.reboot_confirm_text"));
+                    win.horizontal(|row| {
+                        if row.button(tr("main.reboot_yes")).clicked() {
+                            let res = Arc::new(Mutex::new(None));
+                            let res_t = res.clone();
+                            let nag = self.diag_server.clone();
+                            std::thread::spawn(move || {
+                                *res_t.lock().unwrap() = Some(reboot_tcu(&nag));
+                            });
+                            self.reboot = Some(res);
+                            self.confirm_reboot = false;
+                        }
+                        if row.button(tr("main.reboot_no")).clicked() {
+                            self.confirm_reboot = false;
+                        }
+                    });
+                });
+            if !open {
+                self.confirm_reboot = false;
+            }
+        }
+
+        if let Some(reboot) = &self.reboot {
+            let res = reboot.lock().unwrap().take();
+            match res {
+                None => {
+                    ui.horizontal(|row| {
+                        row.spinner();
+                        row.label(tr("main.rebooting"));
+                    });
+                },
+                Some(res) => {
+                    self.reboot = None;
+                    create_page = Some(match res {
+                        Ok(()) => {
+                            // Ident can change after a reboot (EFUSE writes, firmware updates)
+                            self.query_ident();
+                            PageAction::SendNotification { text: tr("main.reboot_ok"), kind: egui_toast::ToastKind::Success }
+                        },
+                        Err(e) => PageAction::SendNotification {
+                            text: tr_fmt("main.reboot_err", &[("error", &e)]),
+                            kind: egui_toast::ToastKind::Error
+                        },
+                    });
+                }
+            }
+        }
+
+        if let Some(report) = &self.report {
+            let res = report.lock().unwrap().take();
+            match res {
+                None => {
+                    ui.horizontal(|row| {
+                        row.spinner();
+                        row.label(tr("main.report_exporting"));
+                    });
+                },
+                Some(res) => {
+                    self.report = None;
+                    create_page = Some(match res {
+                        Ok(path) => PageAction::SendNotification {
+                            text: tr_fmt("main.report_ok", &[("path", &path.display())]),
+                            kind: egui_toast::ToastKind::Success
+                        },
+                        Err(e) => PageAction::SendNotification {
+                            text: tr_fmt("main.report_err", &[("error", &e)]),
+                            kind: egui_toast::ToastKind::Error
+                        },
+                    });
+                }
+            }
+        }
 
         if let Some(page) = create_page {
             return page;
@@ -156,32 +306,46 @@ impl InterfacePage for MainPage {
 
         let info_state = self.info.read().clone();
         match info_state {
-            DataState::Unint => { ui.spinner(); },
-            DataState::LoadErr(e) => { ui.label(format!("Could not query ECU Ident data: {e}")); },
+            DataState::Unint => {
+                ui.horizontal(|row| {
+                    row.spinner();
+                    row.label(tr("main.ident_reading"));
+                    if row.button(tr("main.ident_cancel")).clicked() {
+                        self.ident_query.fetch_add(1, Ordering::Relaxed);
+                        *self.info.write() = DataState::LoadErr(tr("main.ident_cancelled"));
+                    }
+                });
+            },
+            DataState::LoadErr(e) => {
+                ui.horizontal(|row| {
+                    row.label(tr_fmt("main.ident_err", &[("error", &e)]));
+                    if row.button(tr("main.ident_retry")).clicked() {
+                        self.query_ident();
+                    }
+                });
+            },
             DataState::LoadOk(info) => {
-                ui.collapsing("Show TCU Info", |ui| {
-                    ui.label(format!(
-                        "ECU Serial number: {}",
-                        match self.sn.read().clone() {
-                            DataState::LoadOk(s) => s,
-                            DataState::Unint => "...".to_string(),
-                            DataState::LoadErr(_) => "Unknown".to_string(),
-                        }
-                    ));
-                    ui.label(format!(
-                        "PCB Version: {} (HW date: {} week 20{})",
-                        info.board_ver, info.hw_week, info.hw_year
+                ui.collapsing(tr("main.show_info"), |ui| {
+                    let sn = match self.sn.read().clone() {
+                        DataState::LoadOk(s) => s,
+                        DataState::Unint => "...".to_string(),
+                        DataState::LoadErr(_) => tr("main.unknown"),
+                    };
+                    ui.label(tr_fmt("main.serial", &[("sn", &sn)]));
+                    ui.label(tr_fmt(
+                        "main.pcb_version",
+                        &[("version", &info.board_ver), ("week", &info.hw_week), ("year", &info.hw_year)]
                     ));
-                    ui.label(format!(
-                        "PCB Production date: {}/{}/20{}",
-                        info.manf_day, info.manf_month, info.manf_year
+                    ui.label(tr_fmt(
+                        "main.pcb_prod_date",
+                        &[("day", &info.manf_day), ("month", &info.manf_month), ("year", &info.manf_year)]
                     ));
-                    ui.label(format!(
-                        "PCB Software date: week {} of 20{}",
-                        info.sw_week, info.sw_year
+                    ui.label(tr_fmt(
+                        "main.pcb_sw_date",
+                        &[("week", &info.sw_week), ("year", &info.sw_year)]
                     ));
                     ui
-                        .label(format!("EGS CAN Matrix selected: {}", info.egs_mode));
+                        .label(tr_fmt("main.can_matrix", &[("matrix", &info.egs_mode)]));
                 });
             }
         }
@@ -201,31 +365,15 @@ impl InterfacePage for MainPage {
     }
 
     fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
-        println!("OnLoad called");
-        let tcu = self.diag_server.clone();
-        let setting_lock = self.info.clone();
-        let sn_lock = self.sn.clone();
-        std::thread::spawn(move|| {
-            println!("Querying TCU");
-            let state = match tcu.query_ecu_data() {
-                Ok(info) => DataState::LoadOk(info),
-                Err(err) => DataState::LoadErr(err.to_string()),
-            };
-            *setting_lock.write() = state;
-            let state: DataState<String> = match tcu.get_ecu_sn() {
-                Ok(sn) => DataState::LoadOk(sn),
-                Err(err) => DataState::LoadErr(err.to_string()),
-            };
-            *sn_lock.write() = state;
-        });
+        if let Some(n) = nag {
+            self.diag_server = (*n).clone();
+        }
+        // Only re-read after a reconnect, or if there is no ident yet. A read still in progress is left to finish
+        let started = self.ident_query.load(Ordering::Relaxed) != 0;
+        let failed = matches!(*self.info.read(), DataState::LoadErr(_)) || matches!(*self.sn.read(), DataState::LoadErr(_));
+        if !started || failed || self.ident_generation != self.diag_server.connection_generation() {
+            self.query_ident();
+        }
     }
 
 }
-
-impl Drop for MainPage {
-    fn drop(&mut self) {
-        // Create a temp box so we can drop it
-        let b = unsafe { Box::from_raw(self.diag_server) };
-        drop(b);
-    }
-}
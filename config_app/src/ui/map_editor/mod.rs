@@ -753,6 +753,12 @@ impl super::InterfacePage for MapEditor {
         PageAction::None
     }
 
+    fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
+        if let Some(n) = nag {
+            self.nag = (*n).clone();
+        }
+    }
+
     fn get_title(&self) -> &'static str {
         "Map editor"
     }
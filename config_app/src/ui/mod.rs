@@ -8,6 +8,7 @@ pub mod diagnostics;
 pub mod io_maipulator;
 pub mod kwp_event;
 pub mod launcher;
+pub mod log_view;
 pub mod main;
 pub mod map_editor;
 pub mod routine_tests;
@@ -15,6 +16,9 @@ pub mod widgets;
 pub mod updater;
 pub mod param_editor;
 pub mod settings_ui_gen;
+pub mod settings_profiles;
+pub mod settings_diff;
+pub mod report;
 pub mod nvs_editor;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -102,6 +102,12 @@ impl InterfacePage for NvsEditor {
         PageAction::None
     }
 
+    fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
+        if let Some(n) = nag {
+            self.nag = (*n).clone();
+        }
+    }
+
     fn get_title(&self) -> &'static str {
         "NVS Editor"
     }
@@ -109,4 +115,8 @@ impl InterfacePage for NvsEditor {
     fn should_show_statusbar(&self) -> bool {
         true
     }
+
+    fn is_dangerous(&self) -> bool {
+        true
+    }
 }
\ No newline at end of file
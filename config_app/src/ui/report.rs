@@ -0,0 +1,168 @@
+//! Vehicle report bundle, a single zip with everything needed to look into a support request
+//!
+//! Every section is read on its own, so one failed read only leaves an error in that
+//! section's file rather than aborting the whole report.
+use std::{fs::File, io::Write, path::PathBuf, sync::{Arc, Mutex}};
+
+use backend::{
+    diag::{
+        settings::{AdpSettings, EtsSettings, NagSettings, PrmSettings, SbsSettings, SolSettings, TccSettings, TcuSettings},
+        Nag52Diag,
+    },
+    serde_yaml,
+};
+use packed_struct::PackedStructSlice;
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::window::get_context;
+
+use super::{
+    configuration::cfg_structs::{TcmCoreConfig, TcmEfuseConfig},
+    diagnostics::rli::RecordIdents,
+    settings_ui_gen::read_scn,
+};
+
+/// KWP ReadDiagnosticTroubleCodesByStatus, all stored DTCs of all groups
+const READ_DTC_REQ: [u8; 4] = [0x18, 0x02, 0xFF, 0x00];
+
+struct Section {
+    file_name: String,
+    title: String,
+    content: Result<String, String>,
+}
+
+impl Section {
+    fn new(file_name: &str, title: &str, content: Result<String, String>) -> Self {
+        Self {
+            file_name: file_name.to_string(),
+            title: title.to_string(),
+            content,
+        }
+    }
+
+    fn body(&self) -> String {
+        match &self.content {
+            // YML files are left as is, so they can be loaded back into the settings editor
+            Ok(c) if self.file_name.ends_with(".yml") => c.clone(),
+            Ok(c) => format!("# {}\n\n{}\n", self.title, c),
+            Err(e) => format!("# {}\n\nCould not read this section: {}\n", self.title, e),
+        }
+    }
+}
+
+fn read_ident(nag: &Nag52Diag) -> Result<String, String> {
+    let ident = nag.query_ecu_data().map_err(|e| e.to_string())?;
+    let sn = nag.get_ecu_sn().unwrap_or_else(|e| format!("Unknown ({e})"));
+    Ok(format!("ECU Serial number: {sn}\n{ident:#?}"))
+}
+
+fn read_config<T: PackedStructSlice + std::fmt::Debug>(nag: &Nag52Diag, id: u8) -> Result<String, String> {
+    let res = nag.with_kwp(|k| k.kwp_read_custom_local_identifier(id)).map_err(|e| e.to_string())?;
+    T::unpack_from_slice(&res)
+        .map(|c| format!("{c:#?}"))
+        .map_err(|e| format!("{e} (Raw: {res:02X?})"))
+}
+
+fn read_settings<T: TcuSettings>(nag: &Nag52Diag) -> Section {
+    let content = read_scn::<T>(nag).and_then(|s| serde_yaml::to_string(&s).map_err(|e| e.to_string()));
+    Section::new(&format!("settings/{}.yml", T::setting_name()), &format!("{} settings", T::setting_name()), content)
+}
+
+/// Formats a 2 byte DTC as per SAE J2012. The top 2 bits select the system letter
+fn format_dtc(hi: u8, lo: u8) -> String {
+    let system = ['P', 'C', 'B', 'U'][(hi >> 6) as usize];
+    format!("{system}{}{:X}{lo:02X}", (hi >> 4) & 0x03, hi & 0x0F)
+}
+
+fn read_dtcs(nag: &Nag52Diag) -> Result<String, String> {
+    let res = nag.with_kwp(|k| k.send_byte_array_with_response(&READ_DTC_REQ)).map_err(|e| e.to_string())?;
+    // 0x58, DTC count, then 2 bytes of DTC and 1 byte of status per DTC
+    let dtcs = res.get(2..).unwrap_or_default();
+    if dtcs.is_empty() {
+        return Ok("No stored DTCs".into());
+    }
+    Ok(dtcs
+        .chunks(3)
+        .map(|c| match c {
+            [hi, lo, status] => format!("{} (Status 0x{status:02X})", format_dtc(*hi, *lo)),
+            other => format!("Incomplete entry: {other:02X?}"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn read_sys_usage(nag: &Nag52Diag) -> Result<String, String> {
+    nag.with_kwp(|k| RecordIdents::SysUsage.query_ecu(k))
+        .map(|r| format!("{r:#?}"))
+        .map_err(|e| e.to_string())
+}
+
+fn build_sections(nag: &Nag52Diag) -> Vec<Section> {
+    vec![
+        Section::new("ident.txt", "TCU Identification", read_ident(nag)),
+        Section::new("core_config.txt", "TCU Core configuration (TcmCoreConfig)", read_config::<TcmCoreConfig>(nag, 0xFE)),
+        Section::new("efuse_config.txt", "TCU EFUSE configuration (TcmEfuseConfig)", read_config::<TcmEfuseConfig>(nag, 0xFD)),
+        read_settings::<TccSettings>(nag),
+        read_settings::<SolSettings>(nag),
+        read_settings::<SbsSettings>(nag),
+        read_settings::<NagSettings>(nag),
+        read_settings::<PrmSettings>(nag),
+        read_settings::<AdpSettings>(nag),
+        read_settings::<EtsSettings>(nag),
+        Section::new("dtcs.txt", "Stored DTCs", read_dtcs(nag)),
+        Section::new("sys_usage.txt", "TCU System usage snapshot", read_sys_usage(nag)),
+    ]
+}
+
+fn manifest(sections: &[Section], timestamp: &str) -> String {
+    let mut s = format!(
+        "Ultimate-NAG52 vehicle report\nCreated: {}\nConfig app: {} (Build {})\n\n",
+        timestamp,
+        env!("CARGO_PKG_VERSION"),
+        env!("GIT_BUILD")
+    );
+    for section in sections {
+        let status = match &section.content {
+            Ok(_) => "OK".to_string(),
+            Err(e) => format!("FAILED - {e}"),
+        };
+        s.push_str(&format!("{:<20} {}: {}\n", section.file_name, section.title, status));
+    }
+    s
+}
+
+fn write_zip(path: &PathBuf, sections: &[Section], timestamp: &str) -> Result<(), String> {
+    let f = File::create(path).map_err(|e| format!("Cannot create {:?}: {e}", path))?;
+    let mut zip = ZipWriter::new(f);
+    let mut add = |name: &str, content: &str| -> zip::result::ZipResult<()> {
+        zip.start_file(name, FileOptions::default())?;
+        zip.write_all(content.as_bytes())?;
+        Ok(())
+    };
+    add("README.txt", &manifest(sections, timestamp)).map_err(|e| e.to_string())?;
+    for section in sections {
+        add(&section.file_name, &section.body()).map_err(|e| e.to_string())?;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Asks where to save the report, then reads everything from the TCU in the background.
+///
+/// Returns None if the save dialog was cancelled, otherwise where the result is put once the report is written
+pub fn export_report(nag: &Nag52Diag) -> Option<Arc<Mutex<Option<Result<PathBuf, String>>>>> {
+    let now = chrono::Local::now();
+    let path = rfd::FileDialog::new()
+        .add_filter("zip", &["zip"])
+        .set_file_name(&format!("nag52_report_{}.zip", now.format("%Y%m%d_%H%M%S")))
+        .save_file()?;
+    let res = Arc::new(Mutex::new(None));
+    let res_t = res.clone();
+    let nag = nag.clone();
+    std::thread::spawn(move || {
+        let sections = build_sections(&nag);
+        *res_t.lock().unwrap() = Some(write_zip(&path, &sections, &now.format("%Y-%m-%d %H:%M:%S").to_string()).map(|_| path));
+        get_context().request_repaint();
+    });
+    Some(res)
+}
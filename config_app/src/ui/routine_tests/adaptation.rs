@@ -1,26 +1,84 @@
-use backend::diag::Nag52Diag;
-
-use crate::window::PageAction;
+use std::sync::{Arc, RwLock, atomic::{AtomicBool, Ordering}};
 
+use backend::diag::{Nag52Diag, settings::AdpSettings};
+use eframe::egui::{self, Color32, RichText};
 
+use crate::{ui::settings_ui_gen::{reset_scn_settings, TcuSettingsWrapper}, window::PageAction};
 
 pub struct AdaptationViewerPage {
     nag: Nag52Diag,
-    
+    /// User clicked reset, and has to confirm it
+    confirm_reset: bool,
+    running: Arc<AtomicBool>,
+    /// Result of the last reset, taken once it has been shown as a notification
+    result: Arc<RwLock<Option<Result<(), String>>>>,
 }
 
 impl AdaptationViewerPage {
     pub fn new(nag: Nag52Diag) -> Self {
         Self {
             nag,
+            confirm_reset: false,
+            running: Arc::new(AtomicBool::new(false)),
+            result: Arc::new(RwLock::new(None)),
         }
     }
+
+    fn reset_adaptation(&self) {
+        let nag = self.nag.clone();
+        let running = self.running.clone();
+        let result = self.result.clone();
+        running.store(true, Ordering::Relaxed);
+        std::thread::spawn(move || {
+            let (adp, _) = TcuSettingsWrapper::<AdpSettings>::new_pair();
+            *result.write().unwrap() = Some(reset_scn_settings(&nag, &adp));
+            running.store(false, Ordering::Relaxed);
+        });
+    }
 }
 
 impl crate::window::InterfacePage for AdaptationViewerPage {
     fn make_ui(&mut self, ui: &mut eframe::egui::Ui, frame: &eframe::Frame) -> crate::window::PageAction {
         ui.heading("Adaptation viewer");
-        PageAction::None
+        ui.label("Resetting adaptation is recommended after changing ATF or any work on the gearbox.");
+        ui.label("Shifts may feel harsh or slow until the TCU has relearned, which takes a few drives.");
+        ui.separator();
+        if self.running.load(Ordering::Relaxed) {
+            ui.horizontal(|row| {
+                row.spinner();
+                row.label("Resetting adaptation...");
+            });
+        } else if self.confirm_reset {
+            ui.label(RichText::new("Are you sure? The TCU will forget everything it has learned about this gearbox.").color(Color32::RED));
+            ui.horizontal(|row| {
+                if row.button("Yes, reset adaptation").clicked() {
+                    self.confirm_reset = false;
+                    self.reset_adaptation();
+                }
+                if row.button("Cancel").clicked() {
+                    self.confirm_reset = false;
+                }
+            });
+        } else if ui.button("Reset adaptation").clicked() {
+            self.confirm_reset = true;
+        }
+        match self.result.write().unwrap().take() {
+            Some(Ok(())) => PageAction::SendNotification {
+                text: "Adaptation reset. Drive gently whilst the TCU relearns".into(),
+                kind: egui_toast::ToastKind::Success,
+            },
+            Some(Err(e)) => PageAction::SendNotification {
+                text: format!("Could not reset adaptation: {e}"),
+                kind: egui_toast::ToastKind::Error,
+            },
+            None => PageAction::None,
+        }
+    }
+
+    fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
+        if let Some(n) = nag {
+            self.nag = (*n).clone();
+        }
     }
 
     fn get_title(&self) -> &'static str {
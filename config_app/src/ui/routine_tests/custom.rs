@@ -0,0 +1,120 @@
+//! Runs an arbitrary routine local identifier, so new firmware routines
+//! can be exercised before the app has a dedicated page for them
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, RwLock,
+};
+
+use backend::{diag::Nag52Diag, ecu_diagnostics::kwp2000::KwpSessionType};
+use eframe::egui::{self, Color32, RichText, Ui};
+
+use super::history::{RoutineHistory, RoutineRun};
+
+pub struct CustomRoutine {
+    nag: Nag52Diag,
+    history: RoutineHistory,
+    routine_id: String,
+    params: String,
+    running: Arc<AtomicBool>,
+    status: Arc<RwLock<String>>,
+}
+
+/// Parses space separated hex bytes (`01 ff 0x20`)
+fn parse_hex_bytes(s: &str) -> Result<Vec<u8>, String> {
+    s.split_whitespace()
+        .map(|b| {
+            let b = b.trim_start_matches("0x").trim_start_matches("0X");
+            u8::from_str_radix(b, 16).map_err(|_| format!("'{b}' is not a hex byte"))
+        })
+        .collect()
+}
+
+impl CustomRoutine {
+    pub fn new(nag: Nag52Diag, history: RoutineHistory) -> Self {
+        Self {
+            nag,
+            history,
+            routine_id: String::new(),
+            params: String::new(),
+            running: Arc::new(AtomicBool::new(false)),
+            status: Arc::new(RwLock::new(String::new())),
+        }
+    }
+
+    fn run(&mut self, id: u8, params: Vec<u8>, ctx: egui::Context) {
+        let nag_c = self.nag.clone();
+        let status_c = self.status.clone();
+        let running_c = self.running.clone();
+        let history = self.history.clone();
+        std::thread::spawn(move || {
+            running_c.store(true, Ordering::Relaxed);
+            *status_c.write().unwrap() = String::new();
+            ctx.request_repaint();
+
+            let mut req = vec![0x31, id];
+            req.extend_from_slice(&params);
+            let res = nag_c.with_kwp(|kwp| {
+                kwp.kwp_set_session(KwpSessionType::ExtendedDiagnostics.into())?;
+                let res = kwp.send_byte_array_with_response(&req);
+                let _ = kwp.kwp_set_session(KwpSessionType::Normal.into());
+                res
+            });
+
+            let name = format!("Custom routine {params:02X?}");
+            *status_c.write().unwrap() = match res {
+                Ok(resp) => {
+                    let data = resp.get(2..).unwrap_or_default().to_vec();
+                    let s = format!("Response: {resp:02X?}");
+                    history.add(RoutineRun::new(id, name, true, data, s.clone()));
+                    s
+                }
+                Err(e) => {
+                    history.add(RoutineRun::new(id, name, false, vec![], e.to_string()));
+                    format!("Routine failed. Error: {e}")
+                }
+            };
+
+            running_c.store(false, Ordering::Relaxed);
+            ctx.request_repaint();
+        });
+    }
+
+    pub fn make_ui(&mut self, ui: &mut Ui) {
+        ui.label(
+            RichText::new("
+            CAUTION. Routines are sent to the TCU as-is, with no checks of what they do.
+            Only run routines you know are safe with the vehicle in its current state!
+        ").color(Color32::RED),
+        );
+        egui::Grid::new("custom-routine").show(ui, |ui| {
+            ui.label("Routine ID (hex)");
+            ui.text_edit_singleline(&mut self.routine_id);
+            ui.end_row();
+            ui.label("Parameter bytes (hex, space separated)");
+            ui.text_edit_singleline(&mut self.params);
+            ui.end_row();
+        });
+
+        let id = match parse_hex_bytes(&self.routine_id) {
+            Ok(v) if v.len() == 1 => Ok(v[0]),
+            Ok(_) => Err("Routine ID must be a single byte".to_string()),
+            Err(e) => Err(e),
+        };
+        let params = parse_hex_bytes(&self.params);
+        if self.running.load(Ordering::Relaxed) {
+            ui.label("Routine running...");
+        } else {
+            match (id, params) {
+                (Ok(id), Ok(params)) => {
+                    if ui.button(format!("Run routine 0x{id:02X}")).clicked() {
+                        self.run(id, params, ui.ctx().clone());
+                    }
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    ui.label(RichText::new(e).color(Color32::RED));
+                }
+            }
+        }
+        ui.label(self.status.read().unwrap().clone());
+    }
+}
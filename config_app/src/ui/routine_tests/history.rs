@@ -0,0 +1,100 @@
+//! History of routine runs, shared between the routine pages so results
+//! from before and after a repair can be compared (Or exported)
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use eframe::egui::{self, Color32, RichText, Ui};
+use egui_extras::{Column, TableBuilder};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoutineRun {
+    /// Local identifier of the routine
+    pub routine_id: u8,
+    pub name: String,
+    /// Local time the routine finished
+    pub timestamp: String,
+    pub passed: bool,
+    /// Raw routine response (Without the positive response header)
+    pub response: Vec<u8>,
+    /// Decoded results, or the error message if the routine failed
+    pub summary: String,
+}
+
+impl RoutineRun {
+    pub fn new<N: Into<String>, S: Into<String>>(routine_id: u8, name: N, passed: bool, response: Vec<u8>, summary: S) -> Self {
+        Self {
+            routine_id,
+            name: name.into(),
+            timestamp: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            passed,
+            response,
+            summary: summary.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RoutineHistory(Arc<RwLock<Vec<RoutineRun>>>);
+
+impl RoutineHistory {
+    pub fn add(&self, run: RoutineRun) {
+        self.0.write().unwrap().push(run);
+    }
+
+    pub fn clear(&self) {
+        self.0.write().unwrap().clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.read().unwrap().is_empty()
+    }
+
+    /// Writes the history as a JSON array
+    pub fn export<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let s = serde_json::to_string_pretty(&*self.0.read().unwrap())?;
+        File::create(path)?.write_all(s.as_bytes())
+    }
+
+    pub fn make_ui(&self, ui: &mut Ui) {
+        let runs = self.0.read().unwrap().clone();
+        if runs.is_empty() {
+            ui.label("No routines have been run yet");
+            return;
+        }
+        TableBuilder::new(ui)
+            .striped(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::auto()) // Time
+            .column(Column::auto()) // Routine
+            .column(Column::auto()) // Result
+            .column(Column::remainder().clip(true)) // Summary
+            .max_scroll_height(200.0)
+            .header(20.0, |mut header| {
+                header.col(|ui| { ui.strong("Time"); });
+                header.col(|ui| { ui.strong("Routine"); });
+                header.col(|ui| { ui.strong("Result"); });
+                header.col(|ui| { ui.strong("Details"); });
+            })
+            .body(|body| {
+                body.rows(18.0, runs.len(), |idx, mut row| {
+                    let run = &runs[idx];
+                    row.col(|ui| { ui.label(&run.timestamp); });
+                    row.col(|ui| { ui.label(format!("{} (0x{:02X})", run.name, run.routine_id)); });
+                    row.col(|ui| {
+                        ui.label(match run.passed {
+                            true => RichText::new("PASS").color(Color32::GREEN),
+                            false => RichText::new("FAIL").color(Color32::RED),
+                        });
+                    });
+                    row.col(|ui| {
+                        ui.label(&run.summary).on_hover_text(format!("Raw response: {:02X?}", run.response));
+                    });
+                });
+            });
+    }
+}
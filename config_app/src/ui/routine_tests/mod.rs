@@ -4,18 +4,30 @@ use backend::diag::Nag52Diag;
 
 use crate::window::PageAction;
 
-use self::{solenoid_test::SolenoidTestPage, adaptation::AdaptationViewerPage, tcc_control::TccControlPage};
+use self::{solenoid_test::SolenoidTestPage, solenoid_check::SolenoidCheckPage, adaptation::AdaptationViewerPage, tcc_control::TccControlPage, history::RoutineHistory, custom::CustomRoutine};
 
 pub mod solenoid_test;
+pub mod solenoid_check;
 pub mod adaptation;
 pub mod tcc_control;
+pub mod history;
+pub mod custom;
 pub struct RoutinePage {
     nag: Nag52Diag,
+    history: RoutineHistory,
+    show_advanced: bool,
+    custom: CustomRoutine,
 }
 
 impl RoutinePage {
     pub fn new(nag: Nag52Diag) -> Self {
-        Self { nag }
+        let history = RoutineHistory::default();
+        Self {
+            custom: CustomRoutine::new(nag.clone(), history.clone()),
+            nag,
+            history,
+            show_advanced: false,
+        }
     }
 }
 
@@ -43,7 +55,20 @@ impl crate::window::InterfacePage for RoutinePage {
         );
         if ui.button("Solenoid test").clicked() {
             page_action = PageAction::Add(Box::new(SolenoidTestPage::new(
-                self.nag.clone()
+                self.nag.clone(),
+                self.history.clone()
+            )));
+        }
+
+        ui.label(
+            "
+            Check all solenoids in one go, with a table of which are OK, open circuit or shorted
+        ",
+        );
+        if ui.button("Solenoid health check").clicked() {
+            page_action = PageAction::Add(Box::new(SolenoidCheckPage::new(
+                self.nag.clone(),
+                self.history.clone()
             )));
         }
 
@@ -66,13 +91,54 @@ impl crate::window::InterfacePage for RoutinePage {
         );
         if ui.button("TCC solenoid toggler").clicked() {
             page_action = PageAction::Add(Box::new(TccControlPage::new(
-                self.nag.clone()
+                self.nag.clone(),
+                self.history.clone()
             )));
         }
 
+        ui.separator();
+        ui.checkbox(&mut self.show_advanced, "Show advanced routine executor")
+            .on_hover_text("CAUTION. DANGEROUS!");
+        if self.show_advanced {
+            self.custom.make_ui(ui);
+        }
+
+        ui.separator();
+        ui.heading("Routine history");
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!self.history.is_empty(), eframe::egui::Button::new("Export history")).clicked() {
+                if let Some(save_path) = rfd::FileDialog::new()
+                    .set_file_name("routine_history.json")
+                    .add_filter("json", &["json"])
+                    .save_file()
+                {
+                    page_action = match self.history.export(save_path) {
+                        Ok(_) => PageAction::SendNotification {
+                            text: "Routine history exported".into(),
+                            kind: egui_toast::ToastKind::Success,
+                        },
+                        Err(e) => PageAction::SendNotification {
+                            text: format!("Could not export routine history: {e}"),
+                            kind: egui_toast::ToastKind::Error,
+                        },
+                    };
+                }
+            }
+            if ui.add_enabled(!self.history.is_empty(), eframe::egui::Button::new("Clear history")).clicked() {
+                self.history.clear();
+            }
+        });
+        self.history.make_ui(ui);
+
         page_action
     }
 
+    fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
+        if let Some(n) = nag {
+            self.nag = (*n).clone();
+        }
+    }
+
     fn get_title(&self) -> &'static str {
         "Routine executor"
     }
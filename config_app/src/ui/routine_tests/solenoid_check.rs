@@ -0,0 +1,225 @@
+//! One click health check of every solenoid
+//!
+//! Runs the firmware solenoid test, which energises each solenoid in turn and measures the current
+//! it draws, then reads the solenoid record back to check nothing still conducts once the test has
+//! switched every solenoid off again.
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, RwLock,
+};
+
+use backend::diag::{
+    records::{LocalRecordData, RecordIdents, SolenoidFaultHint},
+    Nag52Diag,
+};
+use eframe::egui::{self, Color32, Context, RichText};
+
+use crate::{vehicle_state, window::PageAction};
+
+use super::{
+    history::{RoutineHistory, RoutineRun},
+    solenoid_test::{calc_resistance, expected_current, run_solenoid_test, solenoid_verdict, SolenoidVerdict, TestResultsSolenoid},
+};
+
+#[derive(Debug, Clone)]
+struct SolenoidResult {
+    name: &'static str,
+    current_ma: u16,
+    v_batt_mv: u16,
+    /// Resistance, adjusted to the temperature the specification is for
+    resistance: f32,
+    /// Current a healthy solenoid draws at the measured battery voltage and ATF temperature
+    expected_ma: (f32, f32),
+    verdict: SolenoidVerdict,
+}
+
+#[derive(Debug, Clone)]
+struct CheckResults {
+    atf_temp: i16,
+    solenoids: Vec<SolenoidResult>,
+    /// Faults seen in the solenoid record once the test had finished
+    readback: Result<Vec<SolenoidFaultHint>, String>,
+}
+
+impl CheckResults {
+    fn new(test: &TestResultsSolenoid, readback: Result<Vec<SolenoidFaultHint>, String>) -> Self {
+        let atf_temp = test.atf_temp();
+        let solenoids = test.solenoids().into_iter().map(|(name, current_ma, v_batt_mv, range)| {
+            let resistance = calc_resistance(current_ma, v_batt_mv, atf_temp);
+            SolenoidResult {
+                name,
+                current_ma,
+                v_batt_mv,
+                resistance,
+                expected_ma: expected_current(v_batt_mv, atf_temp, &range),
+                verdict: solenoid_verdict(current_ma, resistance, &range),
+            }
+        }).collect();
+        Self { atf_temp, solenoids, readback }
+    }
+
+    fn passed(&self) -> bool {
+        self.solenoids.iter().all(|s| s.verdict == SolenoidVerdict::Ok)
+            && self.readback.as_ref().map_or(false, |h| h.is_empty())
+    }
+
+    /// One line summary for the routine history
+    fn summary(&self) -> String {
+        let mut parts: Vec<String> = self.solenoids.iter()
+            .map(|s| format!("{} {:?} ({} mA)", s.name, s.verdict, s.current_ma))
+            .collect();
+        match &self.readback {
+            Ok(hints) => parts.extend(hints.iter().map(|h| h.to_string())),
+            Err(e) => parts.push(format!("Readback failed: {e}")),
+        }
+        parts.join(", ")
+    }
+}
+
+fn verdict_text(verdict: SolenoidVerdict) -> RichText {
+    match verdict {
+        SolenoidVerdict::Ok => RichText::new("OK").color(Color32::GREEN),
+        SolenoidVerdict::OpenCircuit => RichText::new("FAIL! Open circuit").color(Color32::RED),
+        SolenoidVerdict::ShortCircuit => RichText::new("FAIL! Short circuit").color(Color32::RED),
+        SolenoidVerdict::OutOfRange => RichText::new("FAIL! Resistance out of range").color(Color32::RED),
+    }
+}
+
+/// Checks the car is stationary with the engine off, then runs the test and reads the solenoids back
+fn run_check(nag: &Nag52Diag) -> Result<(CheckResults, Vec<u8>), String> {
+    if let Some(reason) = vehicle_state::read(nag)?.interlock_reason() {
+        return Err(format!("{reason}. The car must be stationary with the engine off"));
+    }
+    let (test, raw) = nag
+        .with_kwp(|server| Ok(run_solenoid_test(server)))
+        .unwrap_or_else(|e| Err(e.to_string()))?;
+    let readback = match nag.read_record(RecordIdents::SolenoidStatus) {
+        Ok(LocalRecordData::Solenoids(s)) => Ok(s.fault_hints()),
+        Ok(_) => unreachable!(),
+        Err(e) => Err(e.to_string()),
+    };
+    Ok((CheckResults::new(&test, readback), raw))
+}
+
+pub struct SolenoidCheckPage {
+    nag: Nag52Diag,
+    history: RoutineHistory,
+    running: Arc<AtomicBool>,
+    results: Arc<RwLock<Option<Result<CheckResults, String>>>>,
+}
+
+impl SolenoidCheckPage {
+    pub fn new(nag: Nag52Diag, history: RoutineHistory) -> Self {
+        Self {
+            nag,
+            history,
+            running: Arc::new(AtomicBool::new(false)),
+            results: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    fn start(&mut self, ctx: Context) {
+        let nag = self.nag.clone();
+        let history = self.history.clone();
+        let running = self.running.clone();
+        let results = self.results.clone();
+        running.store(true, Ordering::Relaxed);
+        *results.write().unwrap() = None;
+        std::thread::spawn(move || {
+            let res = run_check(&nag);
+            history.add(match &res {
+                Ok((r, raw)) => RoutineRun::new(0xDE, "Solenoid health check", r.passed(), raw.clone(), r.summary()),
+                Err(e) => RoutineRun::new(0xDE, "Solenoid health check", false, vec![], e.clone()),
+            });
+            *results.write().unwrap() = Some(res.map(|(r, _)| r));
+            running.store(false, Ordering::Relaxed);
+            ctx.request_repaint();
+        });
+    }
+}
+
+impl crate::window::InterfacePage for SolenoidCheckPage {
+    fn make_ui(&mut self, ui: &mut eframe::egui::Ui, frame: &eframe::Frame) -> crate::window::PageAction {
+        ui.heading("Solenoid health check");
+        ui.label("
+            This runs the TCU's solenoid test, which energises each solenoid in turn for a moment and
+            measures the current it draws. Each current is checked against what a healthy solenoid draws
+            at the measured battery voltage and ATF temperature. Afterwards the solenoids are read back,
+            to make sure none of them still draw current once switched off.
+        ");
+        ui.separator();
+        ui.label("
+            TEST REQUIREMENTS:
+
+            1. Shifter in D or R
+            2. Engine off
+            3. Not moving
+            4. Battery at least 11.5V
+        ");
+
+        if self.running.load(Ordering::Relaxed) {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Testing solenoids...");
+            });
+        } else if ui.button("Check all solenoids").clicked() {
+            self.start(ui.ctx().clone());
+        }
+
+        match &*self.results.read().unwrap() {
+            Some(Ok(results)) => {
+                ui.separator();
+                ui.label(format!("ATF temperature was {} C", results.atf_temp));
+                egui::Grid::new("solenoid-check").striped(true).show(ui, |ui| {
+                    ui.strong("Solenoid");
+                    ui.strong("Current");
+                    ui.strong("Expected");
+                    ui.strong("Resistance");
+                    ui.strong("Result");
+                    ui.end_row();
+                    for r in &results.solenoids {
+                        let (min, max) = r.expected_ma;
+                        ui.label(r.name);
+                        ui.label(format!("{} mA", r.current_ma));
+                        ui.label(format!("{:.0}..{:.0} mA at {:.1}V", min, max, r.v_batt_mv as f32 / 1000.0));
+                        ui.label(if r.current_ma == 0 { "-".to_string() } else { format!("{:.2}Ω", r.resistance) });
+                        ui.label(verdict_text(r.verdict));
+                        ui.end_row();
+                    }
+                });
+                match &results.readback {
+                    Ok(hints) if hints.is_empty() => {
+                        ui.label(RichText::new("All solenoids are off after the test").color(Color32::GREEN));
+                    },
+                    Ok(hints) => {
+                        for hint in hints {
+                            ui.label(RichText::new(hint.to_string()).color(Color32::RED));
+                        }
+                    },
+                    Err(e) => {
+                        ui.label(RichText::new(format!("Could not read the solenoids back: {e}")).color(Color32::RED));
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                ui.label(RichText::new(format!("Check could not run: {e}")).color(Color32::RED));
+            }
+            None => {}
+        }
+        PageAction::None
+    }
+
+    fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
+        if let Some(n) = nag {
+            self.nag = (*n).clone();
+        }
+    }
+
+    fn get_title(&self) -> &'static str {
+        "Solenoid health check"
+    }
+
+    fn should_show_statusbar(&self) -> bool {
+        true
+    }
+}
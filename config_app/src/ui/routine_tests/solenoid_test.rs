@@ -13,7 +13,7 @@ use std::{
 use backend::{
     diag::Nag52Diag,
     ecu_diagnostics::{
-        DiagError, DiagServerResult, kwp2000::KwpSessionType,
+        DiagError, DiagServerResult, dynamic_diag::DynamicDiagSession, kwp2000::KwpSessionType,
     },
 };
 use eframe::egui::{
@@ -24,7 +24,10 @@ use eframe::egui::{
 
 use crate::{window::PageAction};
 
+use super::history::{RoutineHistory, RoutineRun};
+
 pub struct SolenoidTestPage {
+    history: RoutineHistory,
     test_state: Arc<AtomicU8>,
     test_result: Arc<RwLock<Option<TestResultsSolenoid>>>,
     test_status: Arc<RwLock<String>>,
@@ -75,9 +78,10 @@ pub struct TestResultsSolenoid {
 }
 
 impl SolenoidTestPage {
-    pub fn new(nag: Nag52Diag) -> Self {
+    pub fn new(nag: Nag52Diag, history: RoutineHistory) -> Self {
         Self {
             nag,
+            history,
             test_state: Arc::new(AtomicU8::new(0)),
             test_result: Arc::new(RwLock::new(None)),
             test_status: Arc::new(RwLock::new(String::new())),
@@ -85,12 +89,111 @@ impl SolenoidTestPage {
     }
 }
 
-fn calc_resistance(current: u16, batt: u16, temp: i16) -> f32 {
+pub(super) fn calc_resistance(current: u16, batt: u16, temp: i16) -> f32 {
     let resistance_now = batt as f32 / current as f32;
     return resistance_now
         + resistance_now * (((ResistanceMeasureTemp - temp as f32) * TempCoefficient) / 100.0);
 }
 
+/// Current range (mA) a solenoid within `range` at [ResistanceMeasureTemp] draws at `batt` mV and `temp` C
+pub(super) fn expected_current(batt: u16, temp: i16, range: &RangeInclusive<f32>) -> (f32, f32) {
+    let scale = 1.0 + ((ResistanceMeasureTemp - temp as f32) * TempCoefficient) / 100.0;
+    (batt as f32 * scale / range.end(), batt as f32 * scale / range.start())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SolenoidVerdict {
+    Ok,
+    OpenCircuit,
+    ShortCircuit,
+    OutOfRange,
+}
+
+pub(super) fn solenoid_verdict(c_raw: u16, r: f32, range: &RangeInclusive<f32>) -> SolenoidVerdict {
+    if c_raw == 0 {
+        SolenoidVerdict::OpenCircuit
+    } else if c_raw > 3200 && *range != ResitanceTCC {
+        SolenoidVerdict::ShortCircuit
+    } else if range.contains(&r) {
+        SolenoidVerdict::Ok
+    } else {
+        SolenoidVerdict::OutOfRange
+    }
+}
+
+/// Checks a single solenoid, returning if it passed along with a short description
+fn check_solenoid(c_raw: u16, r: f32, range: RangeInclusive<f32>) -> (bool, String) {
+    match solenoid_verdict(c_raw, r, &range) {
+        SolenoidVerdict::OpenCircuit => (false, "open circuit".into()),
+        SolenoidVerdict::ShortCircuit => (false, "short circuit".into()),
+        v => (v == SolenoidVerdict::Ok, format!("{:.2}Ω", r)),
+    }
+}
+
+/// Runs the firmware solenoid test (Routine 0xDE) and waits for it to finish.
+/// Returns the results along with the raw result data
+pub(super) fn run_solenoid_test(server: &DynamicDiagSession) -> Result<(TestResultsSolenoid, Vec<u8>), String> {
+    server
+        .kwp_set_session(KwpSessionType::ExtendedDiagnostics.into())
+        .map_err(|e| format!("ECU failed to enter extended diagnostic mode: {}", e))?;
+    if let Err(e) = server.send_byte_array_with_response(&[0x31, 0xDE]) {
+        let _ = server.kwp_set_session(KwpSessionType::Normal.into());
+        return Err(format!("ECU rejected the test: {}", e));
+    }
+    let res = loop {
+        // Request test results in a loop
+        match server.send_byte_array_with_response(&[0x33, 0xDE]) {
+            Ok(res) if res.len() < 2 + std::mem::size_of::<TestResultsSolenoid>() => {
+                break Err(format!("ECU test results are too short: {:02X?}", res));
+            }
+            Ok(res) => {
+                let routine_res_ptr: *const TestResultsSolenoid =
+                    res[2..].as_ptr() as *const TestResultsSolenoid;
+                let routine_res: TestResultsSolenoid = unsafe { *routine_res_ptr };
+                break Ok((routine_res, res[2..].to_vec()));
+            }
+            // Just waiting for test to finish!
+            Err(DiagError::ECUError { code: 0x22, .. }) => {}
+            Err(e) => break Err(format!("Failed to get ECU test results: {}", e)),
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    };
+    let _ = server.kwp_set_session(KwpSessionType::Normal.into());
+    res
+}
+
+impl TestResultsSolenoid {
+    pub(super) fn atf_temp(&self) -> i16 {
+        self.atf_temp
+    }
+
+    /// Name, current whilst energised (mA), battery voltage at that moment (mV) and
+    /// resistance specification of each solenoid
+    pub(super) fn solenoids(&self) -> [(&'static str, u16, u16, RangeInclusive<f32>); 6] {
+        [
+            ("MPC", self.mpc_on_current, self.vbatt_mpc, ResitanceMPC),
+            ("SPC", self.spc_on_current, self.vbatt_spc, ResitanceSPC),
+            ("TCC", self.tcc_on_current, self.vbatt_tcc, ResitanceTCC),
+            ("Y3", self.y3_on_current, self.vbatt_y3, ResitanceY3),
+            ("Y4", self.y4_on_current, self.vbatt_y4, ResitanceY4),
+            ("Y5", self.y5_on_current, self.vbatt_y5, ResitanceY5),
+        ]
+    }
+
+    /// Pass/fail of the whole test, and a one line summary of each solenoid for the routine history
+    fn summary(&self) -> (bool, String) {
+        let t = self.atf_temp;
+        let mut passed = true;
+        let mut parts = Vec::new();
+        for (name, c, v, range) in self.solenoids() {
+            let (ok, desc) = check_solenoid(c, calc_resistance(c, v, t), range);
+            passed &= ok;
+            parts.push(format!("{name} {}{desc}", if ok { "" } else { "FAIL " }));
+        }
+        (passed, parts.join(", "))
+    }
+}
+
 fn make_resistance_text(c_raw: u16, r: f32, range: RangeInclusive<f32>) -> egui::Label {
     if c_raw == 0 {
         return egui::Label::new(RichText::new("FAIL! Open circuit detected!").color(Color32::RED));
@@ -159,59 +262,27 @@ impl crate::window::InterfacePage for SolenoidTestPage {
                 let str_ref = self.test_status.clone();
                 let state_ref = self.test_state.clone();
                 let res_ref = self.test_result.clone();
-                let mut n = self.nag.clone();
+                let history = self.history.clone();
+                let n = self.nag.clone();
                 std::thread::spawn(move || {
                     state_ref.store(1, Ordering::Relaxed);
-                    n.with_kwp(|server| {
-                        if let Err(e) =
-                            server.kwp_set_session(KwpSessionType::ExtendedDiagnostics.into())
-                        {
-                            *str_ref.write().unwrap() =
-                                format!("ECU failed to enter extended diagnostic mode: {}", e);
-                            state_ref.store(2, Ordering::Relaxed);
-                            ctx.request_repaint();
-                            return Ok(());
-                        }
-                        if let Err(e) = server.send_byte_array_with_response(&[0x31, 0xDE]) {
-                            let _ = server.kwp_set_session(KwpSessionType::Normal.into());
-                            *str_ref.write().unwrap() = format!("ECU rejected the test: {}", e);
-                            state_ref.store(2, Ordering::Relaxed);
-                            ctx.request_repaint();
-                            return Ok(());
+                    let res = n
+                        .with_kwp(|server| Ok(run_solenoid_test(server)))
+                        .unwrap_or_else(|e| Err(e.to_string()));
+                    *str_ref.write().unwrap() = match res {
+                        Ok((routine_res, raw)) => {
+                            *res_ref.write().unwrap() = Some(routine_res);
+                            let (passed, summary) = routine_res.summary();
+                            history.add(RoutineRun::new(0xDE, "Solenoid test", passed, raw, summary));
+                            "ECU Test Completed!".into()
                         }
-                        loop {
-                            match server.send_byte_array_with_response(&[0x33, 0xDE]) {
-                                // Request test results in a loop
-                                Ok(res) => {
-                                    let routine_res_ptr: *const TestResultsSolenoid =
-                                        res[2..].as_ptr() as *const TestResultsSolenoid;
-                                    let routine_res: TestResultsSolenoid =
-                                        unsafe { *routine_res_ptr };
-                                    *res_ref.write().unwrap() = Some(routine_res);
-                                    *str_ref.write().unwrap() = format!("ECU Test Completed!");
-                                    break;
-                                }
-                                Err(e) => {
-                                    let mut failed = true;
-                                    if let DiagError::ECUError { code, def: _ } = e {
-                                        if code == 0x22 {
-                                            failed = false;
-                                        } // Just waiting for test to finish!
-                                    }
-                                    if failed {
-                                        *str_ref.write().unwrap() =
-                                            format!("Failed to get ECU test results: {}", e);
-                                        break;
-                                    }
-                                }
-                            }
-                            std::thread::sleep(std::time::Duration::from_millis(500));
+                        Err(e) => {
+                            history.add(RoutineRun::new(0xDE, "Solenoid test", false, vec![], e.clone()));
+                            e
                         }
-                        let _ = server.kwp_set_session(KwpSessionType::Normal.into());
-                        state_ref.store(2, Ordering::Relaxed);
-                        ctx.request_repaint();
-                        Ok(())
-                    });
+                    };
+                    state_ref.store(2, Ordering::Relaxed);
+                    ctx.request_repaint();
                 });
             }
             if state == 2 {
@@ -303,6 +374,12 @@ impl crate::window::InterfacePage for SolenoidTestPage {
         PageAction::None
     }
 
+    fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
+        if let Some(n) = nag {
+            self.nag = (*n).clone();
+        }
+    }
+
     fn get_title(&self) -> &'static str {
         "IO Manipulator view"
     }
@@ -5,6 +5,8 @@ use eframe::egui::Context;
 
 use crate::window::PageAction;
 
+use super::history::{RoutineHistory, RoutineRun};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u8)]
 enum TccCommand {
@@ -14,14 +16,16 @@ enum TccCommand {
 
 pub struct TccControlPage {
     nag: Nag52Diag,
+    history: RoutineHistory,
     status: Arc<RwLock<String>>,
     running: Arc<AtomicBool>
 }
 
 impl TccControlPage {
-    pub fn new(nag: Nag52Diag) -> Self {
+    pub fn new(nag: Nag52Diag, history: RoutineHistory) -> Self {
         Self {
             nag,
+            history,
             running: Arc::new(AtomicBool::new(false)),
             status: Arc::new(RwLock::new(String::new()))
         }
@@ -33,6 +37,7 @@ impl TccControlPage {
         let nag_c = self.nag.clone();
         let status_c = self.status.clone();
         let running_c = self.running.clone();
+        let history = self.history.clone();
         std::thread::spawn(move|| {
             running_c.store(true, Ordering::Relaxed);
             *status_c.write().unwrap() = String::new();
@@ -40,9 +45,18 @@ impl TccControlPage {
 
             let res = nag_c.with_kwp(|kwp| {
                 kwp.kwp_set_session(KwpSessionType::ExtendedDiagnostics.into())?;
-                kwp.send_byte_array_with_response(&[0x31, 0x33, mode as u8])?;
+                let resp = kwp.send_byte_array_with_response(&[0x31, 0x33, mode as u8])?;
                 kwp.kwp_set_session(KwpSessionType::Normal.into())?;
-                Ok(())
+                Ok(resp)
+            });
+
+            let name = match mode {
+                TccCommand::EnableTcc => "Enable TCC",
+                TccCommand::DisableTcc => "Disable TCC",
+            };
+            history.add(match &res {
+                Ok(resp) => RoutineRun::new(0x33, name, true, resp.get(2..).unwrap_or_default().to_vec(), "Completed"),
+                Err(e) => RoutineRun::new(0x33, name, false, vec![], e.to_string()),
             });
 
             *status_c.write().unwrap() = match res {
@@ -84,6 +98,12 @@ impl crate::window::InterfacePage for TccControlPage {
         PageAction::None
     }
 
+    fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
+        if let Some(n) = nag {
+            self.nag = (*n).clone();
+        }
+    }
+
     fn get_title(&self) -> &'static str {
         "TCC control"
     }
@@ -0,0 +1,206 @@
+//! Offline comparison of two settings YAML files (As saved by the settings editor)
+use std::{fs::File, io::Read, path::PathBuf};
+
+use backend::{
+    diag::settings::{AdpSettings, EtsSettings, NagSettings, PrmSettings, SbsSettings, SolSettings, TccSettings, TcuSettings},
+    serde_yaml::{self, Value},
+};
+use eframe::egui::{self, Color32, RichText};
+use egui_extras::{Column, TableBuilder};
+
+use crate::window::{InterfacePage, PageAction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffKind {
+    /// Only in the second file
+    Added,
+    /// Only in the first file
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DiffEntry {
+    /// Field path, nested fields separated by '.'
+    path: String,
+    kind: DiffKind,
+    left: Option<Value>,
+    right: Option<Value>,
+}
+
+fn key_name(k: &Value) -> String {
+    match k {
+        Value::String(s) => s.clone(),
+        other => serde_yaml::to_string(other).unwrap_or_default().trim().to_string(),
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Walks both values, recording every leaf that differs
+fn diff_values(path: &str, left: &Value, right: &Value, out: &mut Vec<DiffEntry>) {
+    match (left, right) {
+        (Value::Mapping(l), Value::Mapping(r)) => {
+            for (k, lv) in l {
+                let p = join_path(path, &key_name(k));
+                match r.get(k) {
+                    Some(rv) => diff_values(&p, lv, rv, out),
+                    None => out.push(DiffEntry { path: p, kind: DiffKind::Removed, left: Some(lv.clone()), right: None }),
+                }
+            }
+            for (k, rv) in r {
+                if !l.contains_key(k) {
+                    out.push(DiffEntry { path: join_path(path, &key_name(k)), kind: DiffKind::Added, left: None, right: Some(rv.clone()) });
+                }
+            }
+        },
+        (l, r) if l != r => out.push(DiffEntry { path: path.to_string(), kind: DiffKind::Changed, left: Some(l.clone()), right: Some(r.clone()) }),
+        _ => {}
+    }
+}
+
+fn parses_as<T: TcuSettings>(v: &Value) -> Option<&'static str> {
+    serde_yaml::from_value::<T>(v.clone()).ok().map(|_| T::setting_name())
+}
+
+/// Works out which settings program a file is for, from which settings struct it parses as
+fn detect_program(v: &Value) -> Option<&'static str> {
+    let detectors: [fn(&Value) -> Option<&'static str>; 7] = [
+        parses_as::<TccSettings>,
+        parses_as::<SolSettings>,
+        parses_as::<SbsSettings>,
+        parses_as::<NagSettings>,
+        parses_as::<PrmSettings>,
+        parses_as::<AdpSettings>,
+        parses_as::<EtsSettings>,
+    ];
+    detectors.iter().find_map(|d| d(v))
+}
+
+struct LoadedFile {
+    path: PathBuf,
+    value: Value,
+    program: Option<&'static str>,
+}
+
+fn load_file() -> Option<Result<LoadedFile, String>> {
+    let path = rfd::FileDialog::new().add_filter("config yml", &["yml"]).pick_file()?;
+    let mut s = String::new();
+    let res = File::open(&path)
+        .and_then(|mut f| f.read_to_string(&mut s))
+        .map_err(|e| format!("Cannot read {:?}: {e}", path))
+        .and_then(|_| serde_yaml::from_str::<Value>(&s).map_err(|e| format!("Cannot load {:?}. Invalid YML: {e}", path)))
+        .map(|value| LoadedFile { program: detect_program(&value), path, value });
+    Some(res)
+}
+
+fn value_text(v: &Option<Value>) -> String {
+    match v {
+        Some(v) => serde_yaml::to_string(v).unwrap_or_default().trim().to_string(),
+        None => "-".into(),
+    }
+}
+
+pub struct SettingsDiffPage {
+    files: [Option<LoadedFile>; 2],
+    diff: Vec<DiffEntry>,
+}
+
+impl SettingsDiffPage {
+    pub fn new() -> Self {
+        Self {
+            files: [None, None],
+            diff: Vec::new(),
+        }
+    }
+
+    fn update_diff(&mut self) {
+        self.diff.clear();
+        if let [Some(a), Some(b)] = &self.files {
+            diff_values("", &a.value, &b.value, &mut self.diff);
+        }
+    }
+}
+
+impl InterfacePage for SettingsDiffPage {
+    fn make_ui(&mut self, ui: &mut egui::Ui, _frame: &eframe::Frame) -> PageAction {
+        ui.heading("Compare settings files");
+        ui.label("Load two settings YML files to see which values differ. No TCU is needed");
+        let mut action = PageAction::None;
+        for (idx, name) in ["First file", "Second file"].into_iter().enumerate() {
+            ui.horizontal(|row| {
+                if row.button(format!("Load {}", name.to_lowercase())).clicked() {
+                    match load_file() {
+                        Some(Ok(f)) => {
+                            self.files[idx] = Some(f);
+                            self.update_diff();
+                        },
+                        Some(Err(e)) => {
+                            action = PageAction::SendNotification { text: e, kind: egui_toast::ToastKind::Error };
+                        },
+                        None => {}
+                    }
+                }
+                match &self.files[idx] {
+                    Some(f) => row.label(format!("{}: {} ({})", name, f.path.display(), f.program.unwrap_or("Unknown program"))),
+                    None => row.label(format!("{name}: Not loaded")),
+                };
+            });
+        }
+        let [a, b] = &self.files;
+        let (a, b) = match (a, b) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return action,
+        };
+        if a.program != b.program {
+            ui.label(RichText::new("WARNING. These files are for different settings programs!").color(Color32::RED));
+        }
+        if self.diff.is_empty() {
+            ui.label("Both files are identical");
+            return action;
+        }
+        ui.label(format!("{} differences", self.diff.len()));
+        TableBuilder::new(ui)
+            .striped(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::auto().at_least(250.0))
+            .column(Column::auto())
+            .column(Column::auto().at_least(100.0))
+            .column(Column::remainder())
+            .header(20.0, |mut header| {
+                header.col(|ui| { ui.strong("Setting"); });
+                header.col(|ui| { ui.strong("Change"); });
+                header.col(|ui| { ui.strong("First file"); });
+                header.col(|ui| { ui.strong("Second file"); });
+            })
+            .body(|body| {
+                body.rows(18.0, self.diff.len(), |idx, mut row| {
+                    let d = &self.diff[idx];
+                    let (text, color) = match d.kind {
+                        DiffKind::Added => ("Added", Color32::from_rgb(0, 180, 0)),
+                        DiffKind::Removed => ("Removed", Color32::RED),
+                        DiffKind::Changed => ("Changed", Color32::from_rgb(255, 165, 0)),
+                    };
+                    row.col(|ui| { ui.label(&d.path); });
+                    row.col(|ui| { ui.label(RichText::new(text).color(color)); });
+                    row.col(|ui| { ui.label(value_text(&d.left)); });
+                    row.col(|ui| { ui.label(value_text(&d.right)); });
+                });
+            });
+        action
+    }
+
+    fn get_title(&self) -> &'static str {
+        "Compare settings files"
+    }
+
+    fn should_show_statusbar(&self) -> bool {
+        true
+    }
+}
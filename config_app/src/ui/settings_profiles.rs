@@ -0,0 +1,146 @@
+//! Named settings profiles (E.g. "street", "track") that are stored locally,
+//! one YML file per profile, in a directory per settings program.
+use std::{fs::File, io::{Read, Write}, path::PathBuf};
+
+use backend::{diag::settings::TcuSettings, serde_yaml};
+
+use crate::app_data::app_data_subdir;
+
+pub type ProfileResult<T> = std::result::Result<T, String>;
+
+fn profile_dir<T: TcuSettings>() -> ProfileResult<PathBuf> {
+    let program: String = T::setting_name()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    app_data_subdir(&format!("settings_profiles/{program}")).map_err(|e| e.to_string())
+}
+
+fn profile_path<T: TcuSettings>(name: &str) -> ProfileResult<PathBuf> {
+    if name.trim().is_empty() || name.contains(['/', '\\', '.']) {
+        return Err(format!("'{name}' is not a valid profile name"));
+    }
+    Ok(profile_dir::<T>()?.join(format!("{}.yml", name.trim())))
+}
+
+pub fn list_profiles<T: TcuSettings>() -> ProfileResult<Vec<String>> {
+    let mut res = Vec::new();
+    for entry in std::fs::read_dir(profile_dir::<T>()?).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().map(|x| x == "yml").unwrap_or(false) {
+            if let Some(name) = path.file_stem().and_then(|x| x.to_str()) {
+                res.push(name.to_string());
+            }
+        }
+    }
+    res.sort();
+    Ok(res)
+}
+
+pub fn save_profile<T: TcuSettings>(name: &str, settings: &T) -> ProfileResult<()> {
+    let s = serde_yaml::to_string(settings).map_err(|e| e.to_string())?;
+    File::create(profile_path::<T>(name)?)
+        .and_then(|mut f| f.write_all(s.as_bytes()))
+        .map_err(|e| e.to_string())
+}
+
+pub fn load_profile<T: TcuSettings>(name: &str) -> ProfileResult<T> {
+    let mut s = String::new();
+    File::open(profile_path::<T>(name)?)
+        .and_then(|mut f| f.read_to_string(&mut s))
+        .map_err(|e| e.to_string())?;
+    serde_yaml::from_str(&s).map_err(|e| format!("Invalid settings YML: {e}"))
+}
+
+pub fn rename_profile<T: TcuSettings>(old_name: &str, new_name: &str) -> ProfileResult<()> {
+    let new_path = profile_path::<T>(new_name)?;
+    if new_path.exists() {
+        return Err(format!("A profile called '{new_name}' already exists"));
+    }
+    std::fs::rename(profile_path::<T>(old_name)?, new_path).map_err(|e| e.to_string())
+}
+
+pub fn delete_profile<T: TcuSettings>(name: &str) -> ProfileResult<()> {
+    std::fs::remove_file(profile_path::<T>(name)?).map_err(|e| e.to_string())
+}
+
+/// Profile selector state for the settings page
+#[derive(Debug, Clone, Default)]
+pub struct ProfileUiState {
+    /// Setting program the profile list was loaded for
+    program: Option<&'static str>,
+    profiles: Vec<String>,
+    selected: Option<String>,
+    name_buffer: String,
+}
+
+impl ProfileUiState {
+    fn refresh<T: TcuSettings>(&mut self) -> ProfileResult<()> {
+        self.program = Some(T::setting_name());
+        self.profiles = list_profiles::<T>()?;
+        if let Some(s) = &self.selected {
+            if !self.profiles.contains(s) {
+                self.selected = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws the profile selector. Returns settings if a profile was loaded, or an error message
+    pub fn make_ui<T: TcuSettings>(&mut self, ui: &mut eframe::egui::Ui, settings: &T) -> ProfileResult<Option<T>> {
+        if self.program != Some(T::setting_name()) {
+            self.selected = None;
+            self.name_buffer.clear();
+            self.refresh::<T>()?;
+        }
+        let mut result = None;
+        ui.horizontal(|row| {
+            row.label("Profiles:");
+            eframe::egui::ComboBox::from_id_source(format!("profiles-{}", T::setting_name()))
+                .width(150.0)
+                .selected_text(self.selected.clone().unwrap_or("None".into()))
+                .show_ui(row, |cb_ui| {
+                    for p in &self.profiles {
+                        cb_ui.selectable_value(&mut self.selected, Some(p.clone()), p);
+                    }
+                });
+            if let Some(selected) = self.selected.clone() {
+                if row.button("Load profile").clicked() {
+                    result = Some(load_profile::<T>(&selected).map(Some));
+                }
+                if row.button("Overwrite profile").clicked() {
+                    result = Some(save_profile::<T>(&selected, settings).map(|_| None));
+                }
+                if row.button("Delete profile").clicked() {
+                    result = Some(delete_profile::<T>(&selected).map(|_| None));
+                }
+            }
+            row.separator();
+            row.label("Name:");
+            row.text_edit_singleline(&mut self.name_buffer);
+            if row.button("Save as new profile").clicked() {
+                let name = self.name_buffer.trim().to_string();
+                result = Some(save_profile::<T>(&name, settings).map(|_| {
+                    self.selected = Some(name);
+                    None
+                }));
+            }
+            if let Some(selected) = self.selected.clone() {
+                if row.button("Rename profile").clicked() {
+                    let name = self.name_buffer.trim().to_string();
+                    result = Some(rename_profile::<T>(&selected, &name).map(|_| {
+                        self.selected = Some(name);
+                        None
+                    }));
+                }
+            }
+        });
+        match result {
+            Some(res) => {
+                self.refresh::<T>()?;
+                res
+            },
+            None => Ok(None),
+        }
+    }
+}
@@ -1,25 +1,83 @@
-use std::{sync::{atomic::AtomicBool, Arc, RwLock}, borrow::Borrow, time::{Instant, Duration}, ops::RangeInclusive, fs::File, io::{Write, Read}, any::Any};
+use std::{sync::{atomic::AtomicBool, Arc, RwLock}, borrow::Borrow, time::{Instant, Duration}, fs::File, io::{Write, Read}, any::Any};
 
-use backend::{diag::{settings::{TcuSettings, TccSettings, unpack_settings, LinearInterpSettings, pack_settings, SolSettings, SbsSettings, NagSettings, PrmSettings, AdpSettings, EtsSettings}, Nag52Diag, DataState}, ecu_diagnostics::{kwp2000::{KwpSessionType, KwpCommand}, DiagServerResult}, serde_yaml::{Value, Mapping, self}};
-use eframe::{egui::{ProgressBar, DragValue, self, CollapsingHeader, plot::{PlotPoints, Line, Plot}, ScrollArea, Window, TextEdit, TextBuffer, Layout, Label, Button, RichText}, epaint::Color32};
+use backend::{diag::{send_with_session_retry, settings_layout::{settings_layout, FieldLayout}, settings::{TcuSettings, TccSettings, unpack_settings, UnPackError, LinearInterpSettings, pack_settings, SolSettings, SbsSettings, NagSettings, PrmSettings, AdpSettings, EtsSettings}, Nag52Diag, DataState}, ecu_diagnostics::{kwp2000::{KwpSessionType, KwpCommand}, DiagServerResult}, serde_yaml::{Value, Mapping, self}};
+use eframe::{egui::{ProgressBar, self, CollapsingHeader, plot::{PlotPoints, Line, Plot}, ScrollArea, Window, TextEdit, TextBuffer, Layout, Label, Button, RichText}, epaint::Color32};
 use egui_extras::{TableBuilder, Column};
 use serde::{Serialize, Deserialize, de::DeserializeOwned};
 
-use crate::window::{InterfacePage, PageLoadState, PageAction};
+use crate::window::{InterfacePage, PageLoadState, PageAction, DEV_MODE_SESSION};
+
+use super::settings_profiles::ProfileUiState;
+use super::widgets::battery_guard::BatteryGuard;
 
 pub const PAGE_LOAD_TIMEOUT: f32 = 10000.0;
+/// Colour of settings that have been changed, but not yet written to the TCU
+const PENDING_COLOR: Color32 = Color32::from_rgb(255, 165, 0);
 
+/// Editor state derived from the current settings, so that the settings
+/// do not have to be re-serialized every frame
 #[derive(Debug, Clone)]
-pub struct TcuSettingsWrapper<T>(Arc<RwLock<DataState<T>>>)
+pub struct SettingsEditCache {
+    value: Value,
+    default_value: Option<Value>,
+    /// Settings last read from or written to the TCU, so edited fields can be marked
+    synced_value: Option<Value>,
+    packed: Vec<u8>,
+}
+
+/// Current settings (0), the editor cache (1), the packed settings last read from
+/// or written to the TCU (2), used to spot unwritten edits, and the TCU's default settings (3),
+/// once fetched
+#[derive(Debug, Clone)]
+pub struct TcuSettingsWrapper<T>(Arc<RwLock<DataState<T>>>, Arc<RwLock<Option<SettingsEditCache>>>, Arc<RwLock<Option<Vec<u8>>>>, Arc<RwLock<Option<T>>>)
 where T: TcuSettings;
 
 impl<T> TcuSettingsWrapper<T>
 where T: TcuSettings {
     pub fn new_pair() -> (Self, Self) {
-        let s = Self(Arc::new(RwLock::new(DataState::Unint)));
+        let s = Self(Arc::new(RwLock::new(DataState::Unint)), Arc::new(RwLock::new(None)), Arc::new(RwLock::new(None)), Arc::new(RwLock::new(None)));
         (s.clone(), s)
     }
 
+    fn build_cache(&self, settings: &T) -> SettingsEditCache {
+        SettingsEditCache {
+            value: serde_yaml::to_value(settings).unwrap(),
+            default_value: self.3.read().unwrap().as_ref().and_then(|d| serde_yaml::to_value(d).ok()),
+            synced_value: self.2.read().unwrap().as_ref()
+                .and_then(|raw| unpack_settings::<T>(T::get_scn_id(), raw).ok())
+                .and_then(|s| serde_yaml::to_value(&s).ok()),
+            packed: pack_settings(T::get_scn_id(), *settings),
+        }
+    }
+
+    /// Marks the editor cache as stale, so it is rebuilt on the next frame
+    pub fn invalidate_cache(&self) {
+        *self.1.write().unwrap() = None;
+    }
+
+    /// Records `settings` as what is now on the TCU
+    fn mark_synced(&self, settings: &T) {
+        *self.2.write().unwrap() = Some(pack_settings(T::get_scn_id(), *settings));
+    }
+
+    /// Records `settings` as the TCU's defaults, which enables the per variable reset
+    fn set_defaults(&self, settings: &T) {
+        *self.3.write().unwrap() = Some(*settings);
+        self.invalidate_cache();
+    }
+
+    pub fn has_defaults(&self) -> bool {
+        self.3.read().unwrap().is_some()
+    }
+
+    /// True if the settings have been edited since they were last read from or written to the TCU
+    pub fn is_dirty(&self) -> bool {
+        match (&*self.0.read().unwrap(), &*self.2.read().unwrap()) {
+            (DataState::LoadOk(s), Some(synced)) => pack_settings(T::get_scn_id(), *s) != *synced,
+            _ => false,
+        }
+    }
+
     pub fn loaded_ok(&self) -> bool {
         self.0.read().unwrap().is_ok()
     }
@@ -56,27 +114,131 @@ pub struct TcuAdvSettingsUi {
     prm_settings: TcuSettingsWrapper<PrmSettings>,
     adp_settings: TcuSettingsWrapper<AdpSettings>,
     ets_settings: TcuSettingsWrapper<EtsSettings>,
-    open_settings: OpenSetting
+    open_settings: OpenSetting,
+    profiles: ProfileUiState,
+    show_reset_all: bool,
+    /// Per program result of the last 'Reset all programs' action
+    reset_all_results: Option<Vec<(&'static str, Result<(), String>)>>,
+    battery: BatteryGuard,
 }
 
-pub fn read_scn_settings<T>(nag: &Nag52Diag, dest: &TcuSettingsWrapper<T>)
+/// Error text for a settings block that could not be unpacked, telling the user
+/// what to do if the firmware and config app are out of sync
+fn unpack_err_msg<T: TcuSettings>(e: &UnPackError) -> String {
+    if e.is_revision_mismatch() {
+        format!(
+            "{}. This config app expects revision {} of these settings. Update your config app (Or TCU firmware) so both match",
+            e.to_string(),
+            T::get_revision_name()
+        )
+    } else {
+        e.to_string()
+    }
+}
+
+/// Reads the current settings of a program from the TCU
+pub fn read_scn<T>(nag: &Nag52Diag) -> Result<T, String>
 where T: TcuSettings {
-    match nag.with_kwp(|kwp| {
+    let res = nag.with_kwp(|kwp| {
         kwp.send_byte_array_with_response(&[0x21, 0xFC, T::get_scn_id()])
-    }) {
-        Ok(res) => {
-            match unpack_settings::<T>(T::get_scn_id(), &res[2..]) {
-                Ok(r) => *dest.0.write().unwrap() = DataState::LoadOk(r),
-                Err(e) => *dest.0.write().unwrap() = DataState::LoadErr(e.to_string()),
-            }
+    }).map_err(|e| e.to_string())?;
+    if res.len() < 2 {
+        return Err(format!("Response too short ({res:02X?})"));
+    }
+    unpack_settings::<T>(T::get_scn_id(), &res[2..]).map_err(|e| unpack_err_msg::<T>(&e))
+}
+
+pub fn read_scn_settings<T>(nag: &Nag52Diag, dest: &TcuSettingsWrapper<T>)
+where T: TcuSettings {
+    *dest.0.write().unwrap() = match read_scn::<T>(nag) {
+        Ok(r) => {
+            dest.mark_synced(&r);
+            DataState::LoadOk(r)
         },
-        Err(e) => {
-            *dest.0.write().unwrap() = DataState::LoadErr(e.to_string());
+        Err(e) => DataState::LoadErr(e),
+    };
+    dest.invalidate_cache();
+}
+
+/// Resets a program to its TCU default, then re-reads it so the editor shows the defaults
+pub fn reset_scn_settings<T>(nag: &Nag52Diag, dest: &TcuSettingsWrapper<T>) -> Result<(), String>
+where T: TcuSettings {
+    nag.with_kwp(|x| {
+        send_with_session_retry(x, DEV_MODE_SESSION.into(), &[KwpCommand::WriteDataByLocalIdentifier.into(), 0xFC, T::get_scn_id(), 0x00])
+    }).map_err(|e| e.to_string())?;
+    read_scn_settings(nag, dest);
+    let state = dest.0.read().unwrap().clone();
+    match state {
+        DataState::LoadOk(s) => {
+            dest.set_defaults(&s);
+            Ok(())
         },
+        _ => Err(format!("Reset OK, but re-reading failed: {}", dest.get_err_msg()))
+    }
+}
+
+/// Fetches the TCU default values of a program. The TCU cannot report its defaults on their own,
+/// so the program is reset to default and read back, then the settings that were on the TCU are
+/// written back again
+pub fn read_scn_default_settings<T>(nag: &Nag52Diag, dest: &TcuSettingsWrapper<T>) -> Result<(), String>
+where T: TcuSettings {
+    let current = read_scn::<T>(nag)?;
+    nag.with_kwp(|x| {
+        send_with_session_retry(x, DEV_MODE_SESSION.into(), &[KwpCommand::WriteDataByLocalIdentifier.into(), 0xFC, T::get_scn_id(), 0x00])
+    }).map_err(|e| e.to_string())?;
+    let defaults = read_scn::<T>(nag);
+    let restore = nag.with_kwp(|x| {
+        let mut req = vec![KwpCommand::WriteDataByLocalIdentifier.into(), 0xFC];
+        req.extend_from_slice(&pack_settings(T::get_scn_id(), current));
+        send_with_session_retry(x, DEV_MODE_SESSION.into(), &req)
+    });
+    if let Err(e) = restore {
+        // The TCU is left on its defaults, so the editor has to show all edits as unwritten
+        if let Ok(d) = &defaults {
+            dest.mark_synced(d);
+            dest.set_defaults(d);
+        }
+        dest.invalidate_cache();
+        return Err(format!("Could not write your settings back ({e}). The TCU is now on its default settings, press 'Write settings' to send yours again"));
+    }
+    dest.mark_synced(&current);
+    match defaults {
+        Ok(d) => {
+            dest.set_defaults(&d);
+            Ok(())
+        },
+        Err(e) => Err(format!("Could not read the defaults: {e}"))
     }
 }
 
 impl TcuAdvSettingsUi {
+    fn reset_all_programs(&self) -> Vec<(&'static str, Result<(), String>)> {
+        let mut res = Vec::new();
+        // Only reset programs the TCU reported on load
+        if self.tcc_settings.loaded_ok() {
+            res.push((self.tcc_settings.get_name(), reset_scn_settings(&self.nag, &self.tcc_settings)));
+        }
+        if self.sol_settings.loaded_ok() {
+            res.push((self.sol_settings.get_name(), reset_scn_settings(&self.nag, &self.sol_settings)));
+        }
+        if self.sbs_settings.loaded_ok() {
+            res.push((self.sbs_settings.get_name(), reset_scn_settings(&self.nag, &self.sbs_settings)));
+        }
+        if self.nag_settings.loaded_ok() {
+            res.push((self.nag_settings.get_name(), reset_scn_settings(&self.nag, &self.nag_settings)));
+        }
+        if self.prm_settings.loaded_ok() {
+            res.push((self.prm_settings.get_name(), reset_scn_settings(&self.nag, &self.prm_settings)));
+        }
+        if self.adp_settings.loaded_ok() {
+            res.push((self.adp_settings.get_name(), reset_scn_settings(&self.nag, &self.adp_settings)));
+        }
+        if self.ets_settings.loaded_ok() {
+            res.push((self.ets_settings.get_name(), reset_scn_settings(&self.nag, &self.ets_settings)));
+        }
+        res
+    }
+
     pub fn new(nag: Nag52Diag) -> Self {
         let is_ready = Arc::new(RwLock::new(PageLoadState::waiting("Initializing")));
         let is_ready_t = is_ready.clone();
@@ -92,7 +254,7 @@ impl TcuAdvSettingsUi {
         std::thread::spawn(move|| {
             let res = nag_c.with_kwp(|x| {
                 *is_ready_t.write().unwrap() = PageLoadState::waiting("Setting TCU diag mode");
-                x.kwp_set_session(0x93.into())
+                x.kwp_set_session(DEV_MODE_SESSION.into())
             });
 
             match res {
@@ -115,6 +277,7 @@ impl TcuAdvSettingsUi {
         });
         Self {
             ready: is_ready,
+            battery: BatteryGuard::new(nag.clone()),
             nag,
             start_time: Instant::now(),
             tcc_settings: tcc,
@@ -124,22 +287,28 @@ impl TcuAdvSettingsUi {
             prm_settings: prm,
             adp_settings: adp,
             ets_settings: ets,
-            open_settings: OpenSetting::None
+            open_settings: OpenSetting::None,
+            profiles: ProfileUiState::default(),
+            show_reset_all: false,
+            reset_all_results: None,
         }
     } 
 }
 
-pub fn make_settings_ui<'de, T: TcuSettings>(nag: &Nag52Diag, settings_ref: &TcuSettingsWrapper<T>, ui: &mut eframe::egui::Ui) -> Option<PageAction>
+pub fn make_settings_ui<'de, T: TcuSettings>(nag: &Nag52Diag, settings_ref: &TcuSettingsWrapper<T>, profiles: &mut ProfileUiState, write_blocked: bool, ui: &mut eframe::egui::Ui) -> Option<PageAction>
 where T: Clone + Copy + Serialize + DeserializeOwned {
     let mut action = None;
     let setting_state = settings_ref.0.read().unwrap().clone();
     if let DataState::LoadOk(mut settings) = setting_state {
+        let mut cache = settings_ref.1.write().unwrap().take().unwrap_or_else(|| settings_ref.build_cache(&settings));
+        // Set when the settings are replaced as a whole (Reset, YML or profile load)
+        let mut replaced = false;
         ui.with_layout(Layout::top_down(eframe::emath::Align::Min), |ui| {
             ui.label(format!("Setting revision name: {}", T::get_revision_name()));
             if let Some(url) = T::wiki_url() {
                 ui.hyperlink_to(format!("Help on {}", T::setting_name()), url);
             }
-            let ba = pack_settings(T::get_scn_id(), settings);
+            let ba = cache.packed.clone();
             ui.add_space(10.0);
             ui.label("Hex SCN coding (Display only)");
             let w = ui.available_width();
@@ -150,14 +319,16 @@ where T: Clone + Copy + Serialize + DeserializeOwned {
             });
             ui.add_space(10.0);
             ui.horizontal(|x| {
-                if x.button("Write settings").clicked() {
+                if x.add_enabled(!write_blocked, Button::new("Write settings")).clicked() {
                     let res = nag.with_kwp(|x| {
                         let mut req = vec![KwpCommand::WriteDataByLocalIdentifier.into(), 0xFC];
                         req.extend_from_slice(&ba);
-                        x.send_byte_array_with_response(&req)
+                        send_with_session_retry(x, DEV_MODE_SESSION.into(), &req)
                     });
                     match res {
                         Ok(_) => {
+                            settings_ref.mark_synced(&settings);
+                            cache.synced_value = Some(cache.value.clone());
                             if T::effect_immediate() {
                                 action = Some(PageAction::SendNotification { 
                                     text: format!("{} write OK!", T::setting_name()), 
@@ -178,9 +349,13 @@ where T: Clone + Copy + Serialize + DeserializeOwned {
                         }
                     }
                 }
-                if x.button("Reset to TCU Default").clicked() {
+                if settings_ref.is_dirty() {
+                    x.label(RichText::new("Unwritten changes").color(PENDING_COLOR))
+                        .on_hover_text("These settings have been changed, but not yet written to the TCU. Changed variables are marked with a dot");
+                }
+                if x.add_enabled(!write_blocked, Button::new("Reset to TCU Default")).clicked() {
                     let res = nag.with_kwp(|x| {
-                        x.send_byte_array_with_response(&[KwpCommand::WriteDataByLocalIdentifier.into(), 0xFC, T::get_scn_id(), 0x00])
+                        send_with_session_retry(x, DEV_MODE_SESSION.into(), &[KwpCommand::WriteDataByLocalIdentifier.into(), 0xFC, T::get_scn_id(), 0x00])
                     });
                     match res {
                         Ok(_) => {
@@ -198,6 +373,9 @@ where T: Clone + Copy + Serialize + DeserializeOwned {
                             if let Ok(x) = nag.with_kwp(|kwp| kwp.send_byte_array_with_response(&[0x21, 0xFC, T::get_scn_id()])) {
                                 if let Ok(res) = unpack_settings(T::get_scn_id(), &x[2..]) {
                                     settings = res;
+                                    settings_ref.mark_synced(&settings);
+                                    settings_ref.set_defaults(&settings);
+                                    replaced = true;
                                 }
                             }
                         },
@@ -209,6 +387,20 @@ where T: Clone + Copy + Serialize + DeserializeOwned {
                         }
                     }
                 }
+                if !settings_ref.has_defaults() {
+                    let btn = x.add_enabled(!write_blocked, Button::new("Load TCU defaults"))
+                        .on_hover_text("Briefly resets this program on the TCU to read its defaults, then writes the current settings back");
+                    if btn.clicked() {
+                        if let Err(e) = read_scn_default_settings(nag, settings_ref) {
+                            action = Some(PageAction::SendNotification { 
+                                text: format!("Error loading {} defaults: {}", T::setting_name(), e), 
+                                kind: egui_toast::ToastKind::Error 
+                            });
+                        }
+                        // Keep the edits, but pick up the defaults and the re-read TCU settings
+                        replaced = true;
+                    }
+                }
                 if x.button("Save to YML").clicked() {
                     // Backup the settings to file
                     if let Some(save_path) = rfd::FileDialog::new()
@@ -232,6 +424,7 @@ where T: Clone + Copy + Serialize + DeserializeOwned {
                         f.read_to_string(&mut s).unwrap();
                         if let Ok(s) = serde_yaml::from_str(&s) {
                             settings = s;
+                            replaced = true;
                             action = Some(PageAction::SendNotification { 
                                 text: format!("{} loaded OK from {:?}!", T::setting_name(), path), 
                                 kind: egui_toast::ToastKind::Success 
@@ -245,20 +438,57 @@ where T: Clone + Copy + Serialize + DeserializeOwned {
                     }
                 }
             });
+            match profiles.make_ui::<T>(ui, &settings) {
+                Ok(Some(s)) => {
+                    settings = s;
+                    replaced = true;
+                    action = Some(PageAction::SendNotification { 
+                        text: format!("Profile loaded. Press 'Write settings' to send it to the TCU"), 
+                        kind: egui_toast::ToastKind::Info 
+                    });
+                },
+                Ok(None) => {},
+                Err(e) => {
+                    action = Some(PageAction::SendNotification { 
+                        text: format!("Profile error: {e}"), 
+                        kind: egui_toast::ToastKind::Error 
+                    });
+                }
+            }
+            if replaced {
+                cache = settings_ref.build_cache(&settings);
+            }
             ui.add_space(10.0);
+            if cache.default_value.is_some() {
+                ui.label("Right click on a variable name to reset it to its default value");
+            }
+            let dev_id = egui::Id::new("settings-developer-view");
+            let mut dev_view = ui.data_mut(|d| d.get_persisted::<bool>(dev_id)).unwrap_or(false);
+            ui.checkbox(&mut dev_view, "Developer view (Show raw bytes of each field)");
+            ui.data_mut(|d| d.insert_persisted(dev_id, dev_view));
+            let mut layout = None;
+            if dev_view {
+                match settings_layout(&settings) {
+                    Ok(l) => layout = Some(l),
+                    Err(e) => { ui.label(format!("Cannot show field layout: {e}")); }
+                }
+            }
             ScrollArea::new([false, true]).show(ui, |ui| {
-                let mut v = serde_yaml::to_value(&settings).unwrap();
-                make_ui_for_value::<T>(T::setting_name(), &mut v, ui);
-                match serde_yaml::from_value::<T>(v) {
-                    Ok(s) => {
-                        settings = s;
-                    },
-                    Err(e) => {
-                        action = Some(PageAction::SendNotification { text: format!("Error setting setting: {}", e.to_string()), kind: egui_toast::ToastKind::Error });
+                let dev = layout.as_deref().map(|l| DeveloperView { layout: l, packed: &cache.packed });
+                if make_ui_for_value::<T>(T::setting_name(), &mut cache.value, cache.default_value.as_ref(), cache.synced_value.as_ref(), dev.as_ref(), ui) {
+                    match serde_yaml::from_value::<T>(cache.value.clone()) {
+                        Ok(s) => {
+                            settings = s;
+                            cache.packed = pack_settings(T::get_scn_id(), settings);
+                        },
+                        Err(e) => {
+                            action = Some(PageAction::SendNotification { text: format!("Error setting setting: {}", e.to_string()), kind: egui_toast::ToastKind::Error });
+                        }
                     }
                 }
             });
         });
+        *settings_ref.1.write().unwrap() = Some(cache);
         *settings_ref.0.write().unwrap() = DataState::LoadOk(settings);
     }
     return action;
@@ -335,16 +565,49 @@ impl InterfacePage for TcuAdvSettingsUi {
                 ui.label(RichText::new(format!("{} - {}", err.0, err.1)).color(Color32::RED));
             }
         }
+        let low_batt = self.battery.make_ui(ui);
+        if ui.add_enabled(!low_batt, Button::new("Reset all programs to TCU default")).clicked() {
+            self.show_reset_all = true;
+            self.reset_all_results = None;
+        }
+        let mut show_reset_all = self.show_reset_all;
+        let mut reset_confirmed = false;
+        Window::new("Reset all programs?")
+            .open(&mut show_reset_all)
+            .collapsible(false)
+            .show(ui.ctx(), |win| {
+                match &self.reset_all_results {
+                    None => {
+                        win.label("This will reset every program on the TCU to its default settings.");
+                        win.label("Any changes you have not saved to a profile or YML file will be lost!");
+                        if win.button("Yes, reset everything").clicked() {
+                            reset_confirmed = true;
+                        }
+                    },
+                    Some(results) => {
+                        for (name, res) in results {
+                            match res {
+                                Ok(_) => win.label(format!("{name} - Reset OK")),
+                                Err(e) => win.label(RichText::new(format!("{name} - {e}")).color(Color32::RED)),
+                            };
+                        }
+                    }
+                }
+            });
+        if reset_confirmed {
+            self.reset_all_results = Some(self.reset_all_programs());
+        }
+        self.show_reset_all = show_reset_all;
         ui.separator();
         let action = match self.open_settings {
             OpenSetting::None => None,
-            OpenSetting::Tcc => make_settings_ui(&self.nag, &self.tcc_settings, ui),
-            OpenSetting::Sol => make_settings_ui(&self.nag, &self.sol_settings, ui),
-            OpenSetting::Sbs => make_settings_ui(&self.nag, &self.sbs_settings, ui),
-            OpenSetting::Nag => make_settings_ui(&self.nag, &self.nag_settings, ui),
-            OpenSetting::Prm => make_settings_ui(&self.nag, &self.prm_settings, ui),
-            OpenSetting::Adp => make_settings_ui(&self.nag, &self.adp_settings, ui),
-            OpenSetting::Ets => make_settings_ui(&self.nag, &self.ets_settings, ui),
+            OpenSetting::Tcc => make_settings_ui(&self.nag, &self.tcc_settings, &mut self.profiles, low_batt, ui),
+            OpenSetting::Sol => make_settings_ui(&self.nag, &self.sol_settings, &mut self.profiles, low_batt, ui),
+            OpenSetting::Sbs => make_settings_ui(&self.nag, &self.sbs_settings, &mut self.profiles, low_batt, ui),
+            OpenSetting::Nag => make_settings_ui(&self.nag, &self.nag_settings, &mut self.profiles, low_batt, ui),
+            OpenSetting::Prm => make_settings_ui(&self.nag, &self.prm_settings, &mut self.profiles, low_batt, ui),
+            OpenSetting::Adp => make_settings_ui(&self.nag, &self.adp_settings, &mut self.profiles, low_batt, ui),
+            OpenSetting::Ets => make_settings_ui(&self.nag, &self.ets_settings, &mut self.profiles, low_batt, ui),
         };
         if let Some(act) = action {
             act
@@ -353,13 +616,33 @@ impl InterfacePage for TcuAdvSettingsUi {
         }
     }
 
+    fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
+        if let Some(n) = nag {
+            self.nag = (*n).clone();
+        }
+    }
+
     fn get_title(&self) -> &'static str {
         "Advanced settings"
     }
 
+    fn has_unsaved_changes(&self) -> bool {
+        self.tcc_settings.is_dirty()
+            || self.sol_settings.is_dirty()
+            || self.sbs_settings.is_dirty()
+            || self.nag_settings.is_dirty()
+            || self.prm_settings.is_dirty()
+            || self.adp_settings.is_dirty()
+            || self.ets_settings.is_dirty()
+    }
+
     fn should_show_statusbar(&self) -> bool {
         true
     }
+
+    fn is_dangerous(&self) -> bool {
+        true
+    }
 }
 
 impl Drop for TcuAdvSettingsUi {
@@ -368,24 +651,185 @@ impl Drop for TcuAdvSettingsUi {
     }
 }
 
-fn make_ui_for_value<T: TcuSettings>(setting_name: &'static str, v: &mut Value, ui: &mut egui::Ui) {
+/// Layout of the packed SCN block, used to show the raw bytes behind each field
+struct DeveloperView<'a> {
+    layout: &'a [FieldLayout],
+    /// Packed settings, including the leading SCN ID byte
+    packed: &'a [u8],
+}
+
+impl<'a> DeveloperView<'a> {
+    fn make_ui(&self, ui: &mut egui::Ui, path: &str) {
+        match self.layout.iter().find(|f| f.path == path) {
+            Some(f) => {
+                let raw = self.packed.get(1 + f.offset..1 + f.offset + f.size).unwrap_or_default();
+                ui.label(RichText::new(format!("+0x{:02X} {:02X?}", f.offset, raw)).code())
+                    .on_hover_text(format!("Byte offset {} within the SCN block (After the SCN ID byte), {} byte(s)", f.offset, f.size));
+            },
+            None => {
+                ui.label("?");
+            }
+        }
+    }
+}
+
+/// Returns true if any value was modified
+fn make_ui_for_value<T: TcuSettings>(setting_name: &'static str, v: &mut Value, default: Option<&Value>, synced: Option<&Value>, dev: Option<&DeveloperView>, ui: &mut egui::Ui) -> bool {
     if v.is_mapping() {
-        make_ui_for_mapping::<T>(setting_name, &mut v.as_mapping_mut().unwrap(), ui)
+        make_ui_for_mapping::<T>(setting_name, "", &mut v.as_mapping_mut().unwrap(), default.and_then(|d| d.as_mapping()), synced.and_then(|s| s.as_mapping()), dev, ui)
+    } else {
+        false
+    }
+}
+
+fn set_value(v: &mut Value, new: Value, changed: &mut bool) {
+    if *v != new {
+        *v = new;
+        *changed = true;
+    }
+}
+
+/// Variable name label. If the TCU default is known, right clicking it
+/// allows for just this variable to be reset to its default value.
+/// For integer fields, `hex_mode` adds a toggle between decimal and hex entry.
+/// The label is marked if the value differs from `synced`, the value on the TCU
+fn make_field_label(ui: &mut egui::Ui, text: String, v: &mut Value, default: Option<&Value>, synced: Option<&Value>, hex_mode: Option<&mut bool>, changed: &mut bool) {
+    let pending = synced.filter(|s| **s != *v);
+    let label = match pending {
+        Some(_) => RichText::new(format!("● {text}")).code().color(PENDING_COLOR),
+        None => RichText::new(text).code(),
+    };
+    let mut resp = ui.add(Label::new(label).sense(egui::Sense::click()));
+    if let Some(s) = pending {
+        let s_str = serde_yaml::to_string(s).unwrap_or_default();
+        resp = resp.on_hover_text(format!("Not yet written. On the TCU: {}", s_str.trim()));
     }
+    if let Some(d) = default {
+        let d_str = serde_yaml::to_string(d).unwrap_or_default();
+        resp = resp.on_hover_text(format!("Default: {}", d_str.trim()));
+    }
+    if default.is_none() && hex_mode.is_none() {
+        return;
+    }
+    resp.context_menu(|ui| {
+        if let Some(d) = default {
+            if ui.add_enabled(*v != *d, Button::new("Reset to default")).clicked() {
+                set_value(v, d.clone(), changed);
+                ui.close_menu();
+            }
+        }
+        if let Some(hex) = hex_mode {
+            let text = if *hex { "Edit as decimal" } else { "Edit as hex" };
+            if ui.button(text).clicked() {
+                *hex = !*hex;
+                ui.close_menu();
+            }
+        }
+    });
 }
 
-fn make_ui_for_mapping<T: TcuSettings>(setting_name: &'static str, v: &mut Mapping, ui: &mut egui::Ui) {
+/// Hex text entry for an integer. The text is kept while the field is being edited,
+/// and reverts to the last valid value once focus is lost
+fn make_hex_edit(ui: &mut egui::Ui, id: egui::Id, value: &mut u64) {
+    let mut buf = ui.data_mut(|d| d.get_temp::<String>(id)).unwrap_or_else(|| format!("{:X}", value));
+    let resp = ui.add(TextEdit::singleline(&mut buf).desired_width(100.0).font(egui::TextStyle::Monospace));
+    if resp.changed() {
+        if let Ok(x) = u64::from_str_radix(buf.trim().trim_start_matches("0x"), 16) {
+            *value = x.min(i32::MAX as u64);
+        }
+    }
+    if resp.has_focus() {
+        ui.data_mut(|d| d.insert_temp(id, buf));
+    } else {
+        ui.data_mut(|d| d.remove::<String>(id));
+    }
+}
+
+/// Unit of a setting, guessed from its name. None if the name does not say
+fn unit_for_key(key: &str) -> Option<&'static str> {
+    if key.contains("multi") || key.contains("step") || key.contains("coefficient") {
+        None
+    } else if key.ends_with("_ms") || key.ends_with("_time") {
+        Some("ms")
+    } else if key.ends_with("rpm") || key.ends_with("stall_speed") {
+        Some("RPM")
+    } else if key.contains("pressure") {
+        Some("mBar")
+    } else if key.ends_with("_temp") {
+        Some("*C")
+    } else if key.contains("torque") {
+        Some("Nm")
+    } else {
+        None
+    }
+}
+
+/// Text entry for a number, so exact values can be typed in. Like [make_hex_edit], the text is
+/// kept while the field is being edited, but the value only changes whilst the text is valid
+fn make_num_edit(ui: &mut egui::Ui, id: egui::Id, value: &mut f64, integer: bool, unit: Option<&str>) {
+    let mut buf = ui.data_mut(|d| d.get_temp::<String>(id)).unwrap_or_else(|| match integer {
+        true => format!("{}", value),
+        // Settings floats are f32, this avoids showing f64 rounding noise
+        false => format!("{}", *value as f32),
+    });
+    let parsed = if integer {
+        buf.trim().parse::<u64>()
+            .map_err(|_| "Must be a whole number, 0 or above".to_string())
+            .and_then(|x| if x > i32::MAX as u64 { Err(format!("Must be {} or below", i32::MAX)) } else { Ok(x as f64) })
+    } else {
+        buf.trim().parse::<f64>()
+            .map_err(|_| "Must be a number".to_string())
+            .and_then(|x| if x.is_finite() { Ok(x) } else { Err("Must be a number".to_string()) })
+    };
+    let mut edit = TextEdit::singleline(&mut buf).desired_width(100.0);
+    if parsed.is_err() {
+        edit = edit.text_color(Color32::RED);
+    }
+    ui.horizontal(|row| {
+        let mut resp = row.add(edit);
+        match &parsed {
+            Ok(x) => {
+                if resp.changed() {
+                    *value = *x;
+                }
+            },
+            Err(e) => resp = resp.on_hover_text(format!("{e}. The value stays at {value} until this is fixed")),
+        }
+        if let Some(u) = unit {
+            row.label(u);
+        }
+        if resp.has_focus() {
+            row.data_mut(|d| d.insert_temp(id, buf.clone()));
+        } else {
+            row.data_mut(|d| d.remove::<String>(id));
+        }
+    });
+}
+
+/// `path` is the dotted path of this mapping within the settings struct (Empty for the top level)
+fn make_ui_for_mapping<T: TcuSettings>(setting_name: &'static str, path: &str, v: &mut Mapping, default: Option<&Mapping>, synced: Option<&Mapping>, dev: Option<&DeveloperView>, ui: &mut egui::Ui) -> bool {
+    let mut changed = false;
     egui::Grid::new(format!("Grid-{}", setting_name))
     .striped(true)
     .min_col_width(100.0)
     .show(ui, |ui| {
         ui.strong("Variable");
         ui.strong("Value");
+        if dev.is_some() {
+            ui.strong("Raw");
+        }
         ui.end_row();
         for (i, v) in v.iter_mut() {
             let key = i.as_str().unwrap();
+            let default_v = default.and_then(|d| d.get(i));
+            let synced_v = synced.and_then(|s| s.get(i));
+            let field_path = if path.is_empty() { key.to_string() } else { format!("{path}.{key}") };
             if v.is_mapping() {
-                CollapsingHeader::new(key).default_open(false).show(ui,|sub| {
+                let header = match synced_v {
+                    Some(s) if *s != *v => RichText::new(format!("● {key}")).color(PENDING_COLOR),
+                    _ => RichText::new(key),
+                };
+                CollapsingHeader::new(header).id_source(key).default_open(false).show(ui,|sub| {
                     if let Ok(lerp) = serde_yaml::from_value::<LinearInterpSettings>(v.clone()) {
                         // Linear interp extra display
                         sub.label("Linear interpolation settings");
@@ -413,49 +857,69 @@ fn make_ui_for_mapping<T: TcuSettings>(setting_name: &'static str, v: &mut Mappi
                                 p.line(line)
                             });
                     }
-                    make_ui_for_mapping::<T>(setting_name,&mut v.as_mapping_mut().unwrap(), sub);
+                    changed |= make_ui_for_mapping::<T>(setting_name, &field_path, &mut v.as_mapping_mut().unwrap(), default_v.and_then(|d| d.as_mapping()), synced_v.and_then(|s| s.as_mapping()), dev, sub);
                 });
                 ui.end_row();
             } else if v.is_bool() {
-                ui.code(format!("{key}"));
+                make_field_label(ui, format!("{key}"), v, default_v, synced_v, None, &mut changed);
                 let mut o = v.as_bool().unwrap();
                 ui.checkbox(&mut o, "");
-                *v = Value::from(o);
+                set_value(v, Value::from(o), &mut changed);
+                if let Some(d) = dev {
+                    d.make_ui(ui, &field_path);
+                }
                 ui.end_row();
             } else if v.is_f64() {
-                ui.code(format!("{key}: "));
+                make_field_label(ui, format!("{key}: "), v, default_v, synced_v, None, &mut changed);
                 let mut o = v.as_f64().unwrap();
-                let d = DragValue::new(&mut o).max_decimals(3).speed(0);
-                ui.add(d);
-                *v = Value::from(o);
+                make_num_edit(ui, ui.id().with(("num", &field_path)), &mut o, false, unit_for_key(key));
+                set_value(v, Value::from(o), &mut changed);
+                if let Some(d) = dev {
+                    d.make_ui(ui, &field_path);
+                }
                 ui.end_row();
             } else if v.is_u64(){
-                ui.code(format!("{key}: "));
+                let hex_id = ui.id().with(("hex_mode", key));
+                let mut hex_mode = ui.data_mut(|d| d.get_persisted::<bool>(hex_id)).unwrap_or(false);
+                make_field_label(ui, format!("{key}: "), v, default_v, synced_v, Some(&mut hex_mode), &mut changed);
+                ui.data_mut(|d| d.insert_persisted(hex_id, hex_mode));
                 let mut o = v.as_u64().unwrap();
-                let d = DragValue::new(&mut o).max_decimals(0).speed(0).clamp_range(RangeInclusive::new(0, i32::MAX));
-                ui.add(d);
-                *v = Value::from(o);
+                if hex_mode {
+                    make_hex_edit(ui, hex_id.with("buffer"), &mut o);
+                } else {
+                    let mut f = o as f64;
+                    make_num_edit(ui, ui.id().with(("num", &field_path)), &mut f, true, unit_for_key(key));
+                    o = f as u64;
+                }
+                set_value(v, Value::from(o), &mut changed);
+                if let Some(d) = dev {
+                    d.make_ui(ui, &field_path);
+                }
                 ui.end_row();
             } else if v.is_string() {
-                let mut s = v.as_str().unwrap().to_string();
                 if let Some(valid_options) = T::get_enum_entries(&key) {
-                    ui.code(format!("{key}: "));
+                    make_field_label(ui, format!("{key}: "), v, default_v, synced_v, None, &mut changed);
+                    let mut s = v.as_str().unwrap().to_string();
+                    let label = |entry: &str| T::get_enum_label(key, entry).map(|l| l.to_string()).unwrap_or(entry.to_string());
                     egui::ComboBox::from_id_source(format!("selector-{key}"))
-                        .selected_text(s.clone())
+                        .selected_text(label(&s))
                         .show_ui(ui, |cb_ui| {
                             for entry in valid_options {
-                                let x = entry.clone();
+                                let text = label(&entry);
                                 cb_ui.selectable_value(
                                     &mut s,
-                                    x,
                                     entry,
+                                    text,
                                 );
                             }
                         });
-                        *v = Value::from(s);
+                        set_value(v, Value::from(s), &mut changed);
                 } else {
                     ui.label(format!("Unknown enum Ty for {i:?}"));
                 }
+                if let Some(d) = dev {
+                    d.make_ui(ui, &field_path);
+                }
                 ui.end_row();
             } else {
                 ui.label(format!("FIXME: {:?} - {:?}", i, v));
@@ -463,4 +927,5 @@ fn make_ui_for_mapping<T: TcuSettings>(setting_name: &'static str, v: &mut Mappi
             }
         }
     });
+    changed
 }
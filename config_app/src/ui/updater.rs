@@ -1,12 +1,57 @@
-use std::{sync::{Arc, RwLock}, time::Instant, path::PathBuf, fs::File, io::{Write, BufReader, Cursor}};
+use std::{sync::{Arc, RwLock, atomic::{AtomicBool, Ordering}}, time::{Instant, Duration}, path::PathBuf, fs::File, io::{Write, BufReader, Cursor}};
 
-use backend::{diag::{Nag52Diag, flash::PartitionInfo, DataState}, hw::firmware::{Firmware, load_binary, FirmwareHeader, load_binary_from_path}};
-use curl::easy::{Easy, List};
+use backend::{diag::{Nag52Diag, flash::{FlashError, FlashProgress, PartitionInfo}, DataState}, hw::firmware::{Firmware, FirmwareHeader, FirmwareLoadError, load_binary_from_path, load_binary_from_url}};
 use eframe::egui::{self, ScrollArea};
 use octocrab::{models::repos::Release, repos::releases::ListReleasesBuilder};
 use tokio::runtime::Runtime;
 
 use crate::window::{InterfacePage, PageAction, get_context};
+use crate::ui::widgets::battery_guard::BatteryGuard;
+
+/// Block level progress of a flash read or write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TransferProgress {
+    pub start_addr: u32,
+    pub current: u32,
+    pub total: u32,
+    pub block: u32,
+    pub total_blocks: u32,
+    /// Running (Smoothed) throughput. 0 until the first block is transferred
+    pub bytes_per_sec: u32,
+}
+
+impl TransferProgress {
+    pub fn new(start_addr: u32, total: u32, block_size: u16) -> Self {
+        let bs = (block_size as u32).max(1);
+        Self {
+            start_addr,
+            current: 0,
+            total,
+            block: 0,
+            total_blocks: (total + bs - 1) / bs,
+            bytes_per_sec: 0,
+        }
+    }
+
+    /// Registers a transferred block of `len` bytes, which took `took` to transfer
+    pub fn on_block(&mut self, len: u32, took: Duration) {
+        self.current += len;
+        self.block += 1;
+        let rate = (len as f32 / took.as_secs_f32().max(0.001)) as u32;
+        self.bytes_per_sec = match self.bytes_per_sec {
+            0 => rate,
+            // Exponential moving average, so the ETA follows changes in link speed without jumping around
+            avg => ((avg as u64 * 7 + rate as u64) / 8) as u32,
+        };
+    }
+
+    pub fn eta_secs(&self) -> Option<u32> {
+        match self.bytes_per_sec {
+            0 => None,
+            spd => Some(self.total.saturating_sub(self.current) / spd)
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum CurrentFlashState {
@@ -14,10 +59,11 @@ pub enum CurrentFlashState {
     Download,
     Unzip,
     Prepare,
-    Read { start_addr: u32, current: u32, total: u32 },
-    Write { start_addr: u32, current: u32, total: u32 },
+    Read(TransferProgress),
+    Write(TransferProgress),
     Verify,
     Completed(String),
+    Cancelled,
     Failed(String)
 }
 
@@ -30,17 +76,13 @@ impl CurrentFlashState {
     }
 
     pub fn is_tx_rx(&self) -> bool {
-        match self {
-            CurrentFlashState::Read { start_addr, current, total } | CurrentFlashState::Write { start_addr, current, total } => true,
-            _ => false
-        }
+        self.get_progress().is_some()
     }
 
-    pub fn get_progress(&self) -> (u32, u32, u32) {
+    pub fn get_progress(&self) -> Option<TransferProgress> {
         match self {
-            CurrentFlashState::Read { start_addr, current, total } => (*start_addr, *current, *total),
-            CurrentFlashState::Write { start_addr, current, total } => (*start_addr, *current, *total),
-            _ => (0,0,0)
+            CurrentFlashState::Read(p) | CurrentFlashState::Write(p) => Some(*p),
+            _ => None
         }
     }
 }
@@ -50,12 +92,18 @@ pub struct UpdatePage {
     nag: Nag52Diag,
     fw: Arc<RwLock<Option<Firmware>>>,
     status: Arc<RwLock<CurrentFlashState>>,
-    flash_start: Option<Instant>,
+    /// Set by the user to stop the running read or write after the current block
+    cancel: Arc<AtomicBool>,
     coredump: Option<PartitionInfo>,
     old_fw: Option<(FirmwareHeader, PartitionInfo)>,
     releases:  Arc<RwLock<DataState<Vec<Release>>>>,
     checked_unstable: bool,
-    selected_release: Option<Release>
+    selected_release: Option<Release>,
+    /// Version of the older firmware the user agreed to downgrade to
+    downgrade_confirmed: Option<String>,
+    /// Firmware URL entered by the user
+    fw_url: String,
+    battery: BatteryGuard,
 }
 
 impl UpdatePage {
@@ -89,15 +137,18 @@ impl UpdatePage {
         });
 
         Self{
+            battery: BatteryGuard::new(nag.clone()).block_when_moving(),
             nag, 
             fw: Arc::new(RwLock::new(None)),
             status: Arc::new(RwLock::new(CurrentFlashState::None)),
-            flash_start: None,
+            cancel: Arc::new(AtomicBool::new(false)),
             coredump: coredump_info,
             old_fw: curr_fw_info,
             releases: fw_list,
             checked_unstable: false,
-            selected_release: None
+            selected_release: None,
+            downgrade_confirmed: None,
+            fw_url: String::new(),
         }
     }
 }
@@ -126,9 +177,37 @@ fn make_fw_info(ui: &mut egui::Ui, id: &str, fw: &FirmwareHeader, part_info: Opt
         ui.end_row();
 
         ui.label("Build time");
-        ui.label(fw.get_build_timestamp().map(|f| f.to_string()).unwrap_or("Unknown".into()));
+        ui.label(fw.get_build_timestamp().map(|f| f.to_string()).unwrap_or(format!("Unknown ({} {})", fw.get_date(), fw.get_time())));
+        ui.end_row();
+
+        ui.label("Secure version");
+        ui.label(fw.get_secure_version().to_string());
         ui.end_row();
     });
+    let invalid = fw.invalid_fields();
+    if !invalid.is_empty() {
+        ui.label(egui::RichText::new(format!(
+            "WARNING. Some header fields are unreadable ({}). Check this is the right firmware file!",
+            invalid.join(", ")
+        )).color(egui::Color32::RED));
+    }
+}
+
+/// Downloads the firmware at `url` on the calling thread, updating the flash state as it goes
+fn download_fw(url: &str, state: &RwLock<CurrentFlashState>, fw: &RwLock<Option<Firmware>>) {
+    *state.write().unwrap() = CurrentFlashState::Download;
+    match load_binary_from_url(url) {
+        Ok(bin) => {
+            *fw.write().unwrap() = Some(bin);
+            *state.write().unwrap() = CurrentFlashState::None;
+        },
+        Err(FirmwareLoadError::Network(e)) => {
+            *state.write().unwrap() = CurrentFlashState::Failed(format!("Firmware download failed: {e}"));
+        },
+        Err(e) => {
+            *state.write().unwrap() = CurrentFlashState::Failed(format!("Firmware was invalid: {:?}", e));
+        }
+    }
 }
 
 impl InterfacePage for UpdatePage {
@@ -211,41 +290,7 @@ impl InterfacePage for UpdatePage {
                         if ui.button("Download firmware").clicked() {
                             let state_c = self.status.clone();
                             let fw_c = self.fw.clone();
-                            std::thread::spawn(move|| {
-                                *state_c.write().unwrap() = CurrentFlashState::Download;
-                                let mut buffer: Vec<u8> = Vec::new();
-                                let mut easy = Easy::new();
-                                let mut list = List::new();
-                                list.append("Accept: application/octet-stream").unwrap();
-                                easy.http_headers(list).unwrap();
-                                easy.useragent("request").unwrap();
-                                easy.follow_location(true).unwrap();
-                                easy.url(&url).unwrap();
-                                {
-                                    let mut transfer = easy.transfer();
-                                    let _ = transfer.write_function(|data| {
-                                        buffer.extend_from_slice(data);
-                                        Ok(data.len())
-                                    });
-                                    let _ = transfer.perform();
-                                }
-                                
-                                let code = easy.response_code().unwrap_or(0);
-                                if code == 200 || code == 302 {
-                                    // Try and load FW from here
-                                    match load_binary(buffer) {
-                                        Ok(bin) => {
-                                            *fw_c.write().unwrap() = Some(bin);
-                                            *state_c.write().unwrap() = CurrentFlashState::None;
-                                        }
-                                        Err(e) => {
-                                            *state_c.write().unwrap() = CurrentFlashState::Failed(format!("Firmware was invalid: {:?}", e));
-                                        }
-                                    }
-                                } else {
-                                    *state_c.write().unwrap() = CurrentFlashState::Failed(format!("Firmware download response code was {code}"));
-                                }
-                            });
+                            std::thread::spawn(move|| download_fw(&url, &state_c, &fw_c));
                         }
                     }
 
@@ -270,6 +315,20 @@ impl InterfacePage for UpdatePage {
         //    ui.hyperlink_to(label, url)
         //}
 
+        ui.horizontal(|row| {
+            row.label("Firmware URL:");
+            row.text_edit_singleline(&mut self.fw_url);
+            if row.add_enabled(!self.fw_url.trim().is_empty(), egui::Button::new("Download"))
+                .on_hover_text("Downloads a firmware .bin over HTTPS, for example from your own build server")
+                .clicked()
+            {
+                let url = self.fw_url.trim().to_string();
+                let state_c = self.status.clone();
+                let fw_c = self.fw.clone();
+                std::thread::spawn(move || download_fw(&url, &state_c, &fw_c));
+            }
+        });
+
         if ui.button("Load FW").clicked() {
             if let Some(bin_path) = rfd::FileDialog::new()
                 .add_filter("Firmware bin", &["bin"])
@@ -285,65 +344,93 @@ impl InterfacePage for UpdatePage {
         }
         let c_fw = self.fw.clone().read().unwrap().clone();
         if let Some(fw) = &c_fw {
+            ui.heading("Loaded firmware");
+            ui.label("Check this is the firmware you want before flashing");
             make_fw_info(ui, "nfw",&fw.header, None);
+            if let Some(warning) = fw.layout_warning() {
+                ui.label(egui::RichText::new(format!(
+                    "WARNING. {warning}. This may not be a firmware file!"
+                )).color(egui::Color32::RED));
+            }
             let mut flash = false;
             let mut disclaimer = false;
-            if let Some(new_ts) = fw.header.get_build_timestamp() {
-                if let Some(old_ts) = self.old_fw.map(|f| f.0.get_build_timestamp()).flatten() {
-                    if old_ts > new_ts {
-                        ui.strong("WARNING. The new firmware is older than the current firmware! This can cause bootloops!");
-                        ui.hyperlink_to("See reverting to old FW versions", "docs.ultiamte-nag52.net");
-                        disclaimer = true;
+            let mut blocked = false;
+            if let Some((old, _)) = &self.old_fw {
+                if fw.header.violates_anti_rollback(old) {
+                    ui.label(egui::RichText::new(format!(
+                        "Cannot flash this firmware. Its secure version ({}) is below the TCU's anti-rollback counter ({}), so the TCU would refuse to boot it!",
+                        fw.header.get_secure_version(),
+                        old.get_secure_version()
+                    )).color(egui::Color32::RED).strong());
+                    blocked = true;
+                } else if fw.header.is_downgrade_from(old) {
+                    ui.strong(format!(
+                        "WARNING. You are about to downgrade from {} to {}! This can cause bootloops!",
+                        old.get_version(),
+                        fw.header.get_version()
+                    ));
+                    ui.hyperlink_to("See reverting to old FW versions", "docs.ultiamte-nag52.net");
+                    let mut confirmed = self.downgrade_confirmed.as_deref() == Some(fw.header.get_version().as_str());
+                    if ui.checkbox(&mut confirmed, "I want to downgrade").changed() {
+                        self.downgrade_confirmed = confirmed.then(|| fw.header.get_version());
                     }
+                    blocked = !confirmed;
+                    disclaimer = true;
                 }
             }
             if (!fw.header.get_version().contains("main") || fw.header.get_version().contains("dirty")) && self.old_fw.map(|x| x.0.get_version().contains("main")).unwrap_or(true) {
                 ui.strong("WARNING. You are about to flash potentially unstable firmware. Proceed with caution!");
                 disclaimer = true;
             }
+            if self.battery.make_ui(ui) {
+                blocked = true;
+            }
             let text = match disclaimer {
                 true => "I have read the warnings. Proceed with flashing",
                 false => "Flash new FW",
             };
-            if ui.button(text).clicked() {
+            if ui.add_enabled(!blocked, egui::Button::new(text)).clicked() {
                 flash = true;
             }
             if flash {
                 let mut ng = self.nag.clone();
                 let fw_c = c_fw.clone().unwrap();
                 let state_c = self.status.clone();
+                let cancel_c = self.cancel.clone();
+                cancel_c.store(false, Ordering::Relaxed);
                 std::thread::spawn(move || {
                     get_context().request_repaint();
                     *state_c.write().unwrap() = CurrentFlashState::Prepare;
-                    let (start_addr, bs) = match ng.begin_ota(fw_c.raw.len() as u32) {
-                        Ok((a, b)) => (a, b),
-                        Err(e) => {
-                            *state_c.write().unwrap() = CurrentFlashState::Failed(format!("Failed to prepare for update. {}", e));
-                            return;
-                        },
-                    };
-                    get_context().request_repaint();
-                    let mut written = 0;
-                    for (bid, block) in fw_c.raw.chunks(bs as usize).enumerate() {
-                        match ng.transfer_data(((bid + 1) & 0xFF) as u8, block) {
-                            Ok(_) => { 
-                                written += block.len() as u32;
-                                *state_c.write().unwrap() = CurrentFlashState::Write { start_addr, current: written, total: fw_c.raw.len() as u32 } 
+                    let mut progress: Option<TransferProgress> = None;
+                    let mut block_start = Instant::now();
+                    let res = ng.flash_ota(&fw_c.raw, true, |p| {
+                        match p {
+                            FlashProgress::Started { start_addr, block_size } => {
+                                progress = Some(TransferProgress::new(start_addr, fw_c.raw.len() as u32, block_size));
+                            },
+                            FlashProgress::Written { bytes_done, .. } => {
+                                if let Some(prog) = progress.as_mut() {
+                                    prog.on_block(bytes_done - prog.current, block_start.elapsed());
+                                }
                             },
-                            Err(e) => {
-                                *state_c.write().unwrap() = CurrentFlashState::Failed(format!("Failed to write to address 0x{:08X?} for update. {}", start_addr + written ,e));
-                                return;
+                            FlashProgress::Verifying => {
+                                *state_c.write().unwrap() = CurrentFlashState::Verify;
+                                get_context().request_repaint();
+                                return true;
                             }
                         }
-                        get_context().request_repaint();
-                    }
-                    *state_c.write().unwrap() = CurrentFlashState::Verify;
-                    match ng.end_ota(true) {
-                        Ok(_) => *state_c.write().unwrap() = CurrentFlashState::Completed("Done!".to_string()),
-                        Err(e) => {
-                            *state_c.write().unwrap() = CurrentFlashState::Failed(format!("Error verification: {}", e));
+                        if let Some(prog) = progress {
+                            *state_c.write().unwrap() = CurrentFlashState::Write(prog);
                         }
-                    }
+                        block_start = Instant::now();
+                        get_context().request_repaint();
+                        !cancel_c.load(Ordering::Relaxed)
+                    });
+                    *state_c.write().unwrap() = match res {
+                        Ok(()) => CurrentFlashState::Completed("Done!".to_string()),
+                        Err(FlashError::Cancelled) => CurrentFlashState::Cancelled,
+                        Err(e) => CurrentFlashState::Failed(e.to_string()),
+                    };
                     get_context().request_repaint();
                 });
             }
@@ -352,6 +439,8 @@ impl InterfacePage for UpdatePage {
         if let Some(read_op) = &read_partition {
             let mut ng = self.nag.clone();
             let state_c = self.status.clone();
+            let cancel_c = self.cancel.clone();
+            cancel_c.store(false, Ordering::Relaxed);
             let mut save_path = None;
             if let Some(f) = rfd::FileDialog::new()
                 .add_filter(".bin", &["bin"])
@@ -371,20 +460,28 @@ impl InterfacePage for UpdatePage {
                         return;
                     },
                 };
-                let mut read = 0;
                 let mut read_buffer: Vec<u8> = vec![];
                 let mut counter = 0u8;
                 let start = read_op_c.address;
+                let mut progress = TransferProgress::new(start, read_op_c.size, bs);
+                *state_c.write().unwrap() = CurrentFlashState::Read(progress);
                 while read_buffer.len() < read_op_c.size as usize {
+                    if cancel_c.load(Ordering::Relaxed) {
+                        let _ = ng.abort_transfer();
+                        *state_c.write().unwrap() = CurrentFlashState::Cancelled;
+                        get_context().request_repaint();
+                        return;
+                    }
                     counter = counter.wrapping_add(1);
+                    let block_start = Instant::now();
                     match ng.read_data(counter) {
                         Ok(data) => { 
-                            read += data.len();
+                            progress.on_block(data.len() as u32, block_start.elapsed());
                             read_buffer.extend_from_slice(&data);
-                            *state_c.write().unwrap() = CurrentFlashState::Read { start_addr: start, current: read as u32, total: read_op_c.size };
+                            *state_c.write().unwrap() = CurrentFlashState::Read(progress);
                         },
                         Err(e) => {
-                            *state_c.write().unwrap() = CurrentFlashState::Failed(format!("Failed to read address 0x{:08X?}. {}", start as usize + read ,e));
+                            *state_c.write().unwrap() = CurrentFlashState::Failed(format!("Failed to read address 0x{:08X?}. {}", start + progress.current ,e));
                             return;
                         }
                     }
@@ -403,16 +500,13 @@ impl InterfacePage for UpdatePage {
         }
 
 
-        if state.is_idle() {
-            self.flash_start = None;
-        }
         if !state.is_idle() {
             let (progress_percent, text) = match state.clone() {
-                CurrentFlashState::Read { start_addr, current, total } => {
-                    (current as f32 / total as f32, format!("Reading address 0x{:02X?}", start_addr + current))
+                CurrentFlashState::Read(p) => {
+                    (p.current as f32 / p.total as f32, format!("Reading address 0x{:02X?}", p.start_addr + p.current))
                 },
-                CurrentFlashState::Write { start_addr, current, total } => {
-                    (current as f32 / total as f32, format!("Writing address 0x{:02X?}", start_addr + current))
+                CurrentFlashState::Write(p) => {
+                    (p.current as f32 / p.total as f32, format!("Writing address 0x{:02X?}", p.start_addr + p.current))
                 },
                 CurrentFlashState::Prepare => {
                     (0.0, "Preparing".to_string())
@@ -420,6 +514,7 @@ impl InterfacePage for UpdatePage {
                 CurrentFlashState::None => (1.0, "Idle".to_string()),
                 CurrentFlashState::Verify => (1.0, "Verifying".to_string()),
                 CurrentFlashState::Completed(s) => (1.0, s),
+                CurrentFlashState::Cancelled => (1.0, "Cancelled by user".to_string()),
                 CurrentFlashState::Failed(s) => (1.0, s),
                 CurrentFlashState::Download => {
                     (0.0, format!("Downloading firmware"))
@@ -429,24 +524,34 @@ impl InterfacePage for UpdatePage {
                 },
             };
             ui.add(egui::widgets::ProgressBar::new(progress_percent).animate(true).show_percentage());
-            if state.is_tx_rx() {
-                if self.flash_start.is_none() {
-                    self.flash_start = Some(Instant::now())
+            if let Some(p) = state.get_progress() {
+                ui.label(format!("Block {}/{} ({:.1}/{:.1}Kb)", p.block, p.total_blocks, p.current as f32 / 1024.0, p.total as f32 / 1024.0));
+                match p.eta_secs() {
+                    Some(eta) => {
+                        ui.label(format!("{:.0} bytes/sec", p.bytes_per_sec));
+                        ui.label(format!("ETA: {:02}:{:02} remaining", eta/60, eta % 60));
+                    },
+                    None => {
+                        ui.label("ETA: Calculating...");
+                    }
+                }
+                if self.cancel.load(Ordering::Relaxed) {
+                    ui.label("Cancelling after the current block...");
+                } else if ui.button("Cancel").clicked() {
+                    self.cancel.store(true, Ordering::Relaxed);
                 }
-                let f_start = self.flash_start.unwrap();
-                let (start_address, current, total) = state.get_progress();
-                let spd = (1000.0 * current as f32
-                    / f_start.elapsed().as_millis() as f32)
-                    as u32;
-                let eta = (total - current) / spd;
-                ui.label(format!("Avg {:.0} bytes/sec", spd));
-                ui.label(format!("ETA: {:02}:{:02} seconds remaining", eta/60, eta % 60));
             }
             ui.label(text);
         }
         crate::window::PageAction::None
     }
 
+    fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>) {
+        if let Some(n) = nag {
+            self.nag = (*n).clone();
+        }
+    }
+
     fn get_title(&self) -> &'static str {
         "Flash updater"
     }
@@ -454,4 +559,8 @@ impl InterfacePage for UpdatePage {
     fn should_show_statusbar(&self) -> bool {
         true
     }
+
+    fn is_dangerous(&self) -> bool {
+        true
+    }
 }
\ No newline at end of file
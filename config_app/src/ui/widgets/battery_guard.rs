@@ -0,0 +1,105 @@
+//! Battery voltage check shown before writing to the TCU.
+//!
+//! A brown-out part way through a write can leave the TCU with corrupt settings or firmware,
+//! so writes are blocked whilst the battery is low, unless the user accepts the risk.
+//! Reprogramming can also be blocked whilst the car is moving, which cannot be overridden.
+use std::sync::{Arc, Mutex};
+
+use backend::diag::{
+    records::{LocalRecordData, RecordIdents, Sentinel, VehicleState, MIN_WRITE_VBATT_MV},
+    Nag52Diag,
+};
+use eframe::egui::{self, Color32, RichText};
+
+use crate::{i18n::{tr, tr_fmt}, vehicle_state, window::get_context};
+
+pub struct BatteryGuard {
+    nag: Nag52Diag,
+    /// Battery voltage in mV, or why it is not known. None whilst reading
+    reading: Arc<Mutex<Option<Result<u16, String>>>>,
+    /// Vehicle state read alongside the battery voltage, if the TCU knows it
+    vehicle: Arc<Mutex<Option<VehicleState>>>,
+    /// User chose to write anyway
+    overridden: bool,
+    block_when_moving: bool,
+}
+
+impl BatteryGuard {
+    pub fn new(nag: Nag52Diag) -> Self {
+        let mut s = Self {
+            nag,
+            reading: Arc::new(Mutex::new(None)),
+            vehicle: Arc::new(Mutex::new(None)),
+            overridden: false,
+            block_when_moving: false,
+        };
+        s.recheck();
+        s
+    }
+
+    /// Also blocks writes whilst the car is moving
+    pub fn block_when_moving(mut self) -> Self {
+        self.block_when_moving = true;
+        self
+    }
+
+    /// Reads the battery voltage again in the background
+    pub fn recheck(&mut self) {
+        self.overridden = false;
+        *self.reading.lock().unwrap() = None;
+        let reading = self.reading.clone();
+        let vehicle = self.vehicle.clone();
+        let nag = self.nag.clone();
+        std::thread::spawn(move || {
+            *vehicle.lock().unwrap() = vehicle_state::read(&nag).ok();
+            let res = match nag.read_record(RecordIdents::GearboxSensors) {
+                Ok(LocalRecordData::Sensors(s)) => s.v_batt.available().ok_or(tr("battery.unknown")),
+                Ok(_) => unreachable!(),
+                Err(e) => Err(e.to_string()),
+            };
+            *reading.lock().unwrap() = Some(res);
+            get_context().request_repaint();
+        });
+    }
+
+    /// Shows the battery voltage, with a warning if it is too low to write.
+    /// Returns true if writes should be blocked
+    pub fn make_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let reading = self.reading.lock().unwrap().clone();
+        let mut blocked = false;
+        // Prefer live data, if a page is reading it
+        let vehicle = vehicle_state::latest().or(*self.vehicle.lock().unwrap());
+        if self.block_when_moving && vehicle.map_or(false, |v| v.moving == Some(true)) {
+            ui.label(RichText::new(tr("battery.moving")).color(Color32::RED));
+            blocked = true;
+        }
+        ui.horizontal(|row| {
+            match &reading {
+                None => {
+                    row.spinner();
+                    row.label(tr("battery.checking"));
+                    blocked = true;
+                },
+                Some(Ok(mv)) if *mv >= MIN_WRITE_VBATT_MV => {
+                    row.label(tr_fmt("battery.ok", &[("volts", &format!("{:.1}", *mv as f32 / 1000.0))]));
+                },
+                Some(res) => {
+                    let text = match res {
+                        Ok(mv) => tr_fmt("battery.low", &[
+                            ("volts", &format!("{:.1}", *mv as f32 / 1000.0)),
+                            ("min", &format!("{:.1}", MIN_WRITE_VBATT_MV as f32 / 1000.0)),
+                        ]),
+                        Err(e) => tr_fmt("battery.read_failed", &[("error", e)]),
+                    };
+                    row.label(RichText::new(text).color(Color32::RED));
+                    row.checkbox(&mut self.overridden, tr("battery.override"));
+                    blocked |= !self.overridden;
+                }
+            }
+            if row.add_enabled(reading.is_some(), egui::Button::new(tr("battery.recheck"))).clicked() {
+                self.recheck();
+            }
+        });
+        blocked
+    }
+}
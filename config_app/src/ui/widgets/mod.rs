@@ -1,4 +1,5 @@
 use eframe::{egui::{TextBuffer, WidgetText, Button, RichText, Label, Response}, epaint::{Vec2, Rounding}};
 
 pub mod range_display;
-pub mod number_input;
\ No newline at end of file
+pub mod number_input;
+pub mod battery_guard;
\ No newline at end of file
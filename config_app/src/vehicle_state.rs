@@ -0,0 +1,52 @@
+//! Latest known engine and movement state of the car, shared by every page.
+//!
+//! Pages that read the CAN data record anyway report it here, and safety interlocks
+//! read it from here (Or query the TCU if nothing recent is known), so they all agree.
+use std::{sync::RwLock, time::{Duration, Instant}};
+
+use backend::diag::{
+    records::{DataCanDump, LocalRecordData, RecordIdents, VehicleState},
+    Nag52Diag,
+};
+use eframe::egui::{self, Color32, RichText};
+
+/// A state older than this is not shown or used, as the car may have set off since
+const STALE_AFTER: Duration = Duration::from_secs(3);
+
+static LATEST: RwLock<Option<(Instant, VehicleState)>> = RwLock::new(None);
+
+/// Called with every CAN data sample read from the TCU
+pub fn update(can: &DataCanDump) -> VehicleState {
+    let state = can.vehicle_state();
+    *LATEST.write().unwrap() = Some((Instant::now(), state));
+    state
+}
+
+/// Vehicle state, if it was seen recently
+pub fn latest() -> Option<VehicleState> {
+    (*LATEST.read().unwrap()).filter(|(at, _)| at.elapsed() < STALE_AFTER).map(|(_, s)| s)
+}
+
+/// Reads the vehicle state from the TCU now
+pub fn read(nag: &Nag52Diag) -> Result<VehicleState, String> {
+    match nag.read_record(RecordIdents::CanDataDump).map_err(|e| e.to_string())? {
+        LocalRecordData::Canbus(c) => Ok(update(&c)),
+        _ => unreachable!(),
+    }
+}
+
+fn describe(value: Option<bool>, yes: &str, no: &str, unknown: &str) -> RichText {
+    match value {
+        Some(true) => RichText::new(yes).color(Color32::from_rgb(230, 150, 0)),
+        Some(false) => RichText::new(no),
+        None => RichText::new(unknown).weak(),
+    }
+}
+
+/// Compact engine / movement badge, for the status bar
+pub fn badge_ui(ui: &mut egui::Ui, state: &VehicleState) {
+    ui.label(describe(state.engine_running, "Engine running", "Engine off", "Engine: No signal"))
+        .on_hover_text("From the engine speed the TCU receives over CAN");
+    ui.label(describe(state.moving, "Moving", "Stationary", "Speed: No signal"))
+        .on_hover_text("From the rear wheel speeds the TCU receives over CAN");
+}
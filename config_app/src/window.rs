@@ -1,17 +1,18 @@
 use std::{
     collections::VecDeque,
     ops::Add,
-    time::{Duration, Instant}, sync::Arc, borrow::BorrowMut, fs::OpenOptions, io::Write,
+    time::{Duration, Instant}, sync::Arc, borrow::BorrowMut,
 };
 
-use backend::{diag::Nag52Diag, ecu_diagnostics::{DiagError, dynamic_diag::ServerEvent}, hw::usb::{EspLogMessage, EspLogLevel}};
+use backend::{diag::Nag52Diag, ecu_diagnostics::{DiagError, DiagServerResult, dynamic_diag::{DynamicDiagSession, ServerEvent}, kwp2000::{KwpSessionType, KwpSessionTypeByte}}};
 use eframe::{
     egui::{self, Direction, RichText, WidgetText, Sense, Button, ScrollArea, Context},
-    epaint::{Pos2, Vec2, Color32, Rect, Rounding, FontId}, emath::Align2,
+    epaint::{Pos2, Vec2, Color32, Rect, Rounding, FontId, Stroke}, emath::Align2,
 };
-use egui_extras::{TableBuilder, Column};
 use egui_toast::{Toast, ToastKind, ToastOptions, Toasts, ERROR_COLOR};
 
+use crate::{app_data::open_in_file_manager, file_log, i18n, repaint::{self, RepaintMode}, vehicle_state, ui::{launcher::adapter_name, log_view::LogView}};
+
 static mut GLOBAL_EGUI_CONTEXT: Option<Context> = None;
 
 pub fn get_context() -> &'static Context {
@@ -38,14 +39,18 @@ pub struct MainWindow {
     pages: VecDeque<Box<dyn InterfacePage>>,
     show_sbar: bool,
     show_back: bool,
+    /// Back was pressed on a page with unsaved changes, waiting for the user to confirm
+    confirm_discard: bool,
     last_repaint_time: Instant,
-    logs: VecDeque<EspLogMessage>,
+    log_view: LogView,
     trace: VecDeque<String>,
     show_logger: bool,
     show_tracer: bool,
     last_data_query_time: Instant,
     last_tx_rate: u32,
-    last_rx_rate: u32
+    last_rx_rate: u32,
+    /// Diagnostic payload throughput (Tx, Rx) in bytes/sec
+    payload_rate: (u32, u32),
 }
 
 impl MainWindow {
@@ -54,15 +59,17 @@ impl MainWindow {
             pages: VecDeque::new(),
             show_sbar: false,
             show_back: true,
+            confirm_discard: false,
             nag: None,
             last_repaint_time: Instant::now(),
-            logs: VecDeque::new(),
+            log_view: LogView::new(),
             trace: VecDeque::new(),
             show_logger: false,
             show_tracer: false,
             last_data_query_time: Instant::now(),
             last_tx_rate: 0,
-            last_rx_rate: 0
+            last_rx_rate: 0,
+            payload_rate: (0, 0),
         }
     }
     pub fn add_new_page(&mut self, p: Box<dyn InterfacePage>) {
@@ -71,6 +78,20 @@ impl MainWindow {
         self.pages[0].on_load(self.nag.clone());
     }
 
+    /// Reconnects to the TCU, so the user stays on the page they were on. Every clone of the
+    /// handle (Including ones held by page threads) shares the connection, so they all switch to
+    /// the new one. Open pages are then reloaded
+    pub fn reconnect(&mut self) -> DiagServerResult<()> {
+        match &self.nag {
+            Some(n) => n.try_reconnect()?,
+            None => return Err(DiagError::ServerNotRunning),
+        };
+        for pg in self.pages.iter_mut() {
+            pg.on_load(self.nag.clone());
+        }
+        Ok(())
+    }
+
     pub fn pop_page(&mut self) {
         self.pages.pop_front();
         if let Some(pg) = self.pages.get_mut(0) {
@@ -84,6 +105,25 @@ impl MainWindow {
 }
 
 pub const MAX_BANDWIDTH: f32 = 155200.0 / 4.0;
+/// Session ID of the firmware's developer mode (UN52DevMode)
+pub(crate) const DEV_MODE_SESSION: u8 = 0x93;
+/// Session ID of the KWP default (Normal) session
+const NORMAL_SESSION: u8 = 0x81;
+
+/// Sends StartDiagnosticSession(Normal) directly to the TCU, bypassing the diag server's
+/// record of the current session, then syncs the diag server's record with it
+fn force_normal_session(f: &DynamicDiagSession) {
+    if let Err(e) = f.send_byte_array_with_response(&[0x10, NORMAL_SESSION]) {
+        log::error!("Could not force the normal session: {e}");
+        return;
+    }
+    if let Err(e) = f.kwp_set_session(KwpSessionTypeByte::Standard(KwpSessionType::Normal)) {
+        log::error!("TCU is in the normal session, but the diag server could not be updated: {e}");
+    }
+}
+
+/// Border and banner colour of pages that are [InterfacePage::is_dangerous]
+const DANGER_COLOR: Color32 = Color32::from_rgb(139, 0, 0);
 
 impl eframe::App for MainWindow {
     fn update(&mut self, ctx: &eframe::egui::Context, frame: &mut eframe::Frame) {
@@ -96,34 +136,97 @@ impl eframe::App for MainWindow {
         let mut s_bar_height = 0.0;
         if stack_size > 0 {
             let mut pop_page = false;
+            let mut reconnect = false;
             if self.show_sbar {
                 egui::TopBottomPanel::bottom("NAV").show(ctx, |nav| {
                     nav.horizontal(|row| {
                         egui::widgets::global_dark_light_mode_buttons(row);
+                        let lang = i18n::current_language();
+                        egui::ComboBox::from_id_source("language")
+                            .selected_text(lang.native_name())
+                            .show_ui(row, |cb_ui| {
+                                for l in i18n::Language::ALL {
+                                    if cb_ui.selectable_label(l == lang, l.native_name()).clicked() && l != lang {
+                                        i18n::set_language(l);
+                                    }
+                                }
+                            }).response.on_hover_text(i18n::tr("window.language"));
+                        let mut on_data = repaint::mode() == RepaintMode::OnData;
+                        if row.checkbox(&mut on_data, "Low power")
+                            .on_hover_text("Only redraw live pages when new data arrives from the TCU, instead of at 60 FPS. Charts scroll less smoothly")
+                            .changed()
+                        {
+                            repaint::set_mode(if on_data { RepaintMode::OnData } else { RepaintMode::Continuous });
+                        }
                         if stack_size > 1 {
                             if row.add_enabled(self.show_back, Button::new("Back")).clicked() {
-                                pop_page = true;
+                                if self.pages[0].has_unsaved_changes() {
+                                    self.confirm_discard = true;
+                                } else {
+                                    pop_page = true;
+                                }
                             }
                         }
                         if let Some(nag) = &self.nag {
-
+                            row.label(format!("[{}] {}", adapter_name(nag.get_adapter_type()), nag.get_device_desc()))
+                                .on_hover_text("Adapter the app is connected to the TCU with");
+                            if let Some(state) = vehicle_state::latest() {
+                                vehicle_state::badge_ui(row, &state);
+                            }
                             let _ = nag.with_kwp(|f| {
                                 if f.is_ecu_connected() {
-                                    if let Some(mode) = f.get_current_diag_mode() {
-                                        row.label(format!("Mode: {}(0x{:02X?})", mode.name, mode.id));
-                                    } 
+                                    match f.get_current_diag_mode() {
+                                        Some(mode) => {
+                                            let label = RichText::new(format!("Mode: {}(0x{:02X?})", mode.name, mode.id));
+                                            row.label(if mode.id == DEV_MODE_SESSION { label.color(ERROR_COLOR) } else { label })
+                                                .on_hover_text("Session the app last put the TCU into");
+                                            if mode.id != NORMAL_SESSION && row.button("Force normal session").clicked() {
+                                                force_normal_session(f);
+                                            }
+                                        },
+                                        None => {
+                                            row.label(RichText::new("Mode: Unknown").color(ERROR_COLOR));
+                                            if row.button("Force normal session").clicked() {
+                                                force_normal_session(f);
+                                            }
+                                        }
+                                    }
+                                    row.menu_button("Session", |menu| {
+                                        menu.label(RichText::new("Developer mode unlocks untested firmware functions. Only use it if you know what you are doing!").color(ERROR_COLOR));
+                                        let sessions = [
+                                            ("Normal", KwpSessionTypeByte::Standard(KwpSessionType::Normal)),
+                                            ("Extended diagnostics", KwpSessionTypeByte::Standard(KwpSessionType::ExtendedDiagnostics)),
+                                            ("UN52 developer mode", KwpSessionTypeByte::Extended(DEV_MODE_SESSION)),
+                                        ];
+                                        for (name, session) in sessions {
+                                            if menu.button(name).clicked() {
+                                                if let Err(e) = f.kwp_set_session(session) {
+                                                    log::error!("Could not enter {name} session: {e}");
+                                                }
+                                                menu.close_menu();
+                                            }
+                                        }
+                                        menu.separator();
+                                        if menu.button("Force normal session")
+                                            .on_hover_text("Returns the TCU to the normal session even if the app thinks it is already there. Use this if a page left the TCU in the wrong session")
+                                            .clicked()
+                                        {
+                                            force_normal_session(f);
+                                            menu.close_menu();
+                                        }
+                                    });
                                 } else {
                                     row.label(RichText::new("Disconnected").color(ERROR_COLOR));
+                                    if row.button("Reconnect").clicked() {
+                                        reconnect = true;
+                                    }
                                 }
                                 Ok(())
                             });
 
                             if nag.has_logger() {
                                 while let Some(msg) = nag.read_log_msg() {
-                                    self.logs.push_back(msg);
-                                    if self.logs.len() > 1000 {
-                                        self.logs.pop_front();
-                                    }
+                                    self.log_view.push(msg);
                                 }
                                 if row.button("Show Log view").clicked() {
                                     self.show_logger = true;
@@ -135,6 +238,11 @@ impl eframe::App for MainWindow {
                             if row.button("Show packet trace").clicked() {
                                 self.show_tracer = true;
                             }
+                            if row.button("Open log folder").clicked() {
+                                if let Err(e) = file_log::log_dir().and_then(|d| open_in_file_manager(&d)) {
+                                    log::error!("Could not open log folder: {e}");
+                                }
+                            }
                             if let Some(evt) = nag.get_server_event() {
 
                                 let fmt_str = match evt {
@@ -184,6 +292,7 @@ impl eframe::App for MainWindow {
                                     self.last_tx_rate = tx;
                                     self.last_rx_rate = rx;
                                 }
+                                self.payload_rate = nag.get_payload_rate();
                                 self.last_data_query_time = Instant::now();
                             }
 
@@ -216,6 +325,9 @@ impl eframe::App for MainWindow {
                             s_rx_resp.on_hover_ui(|h| {
                                 h.label(format!("{} B/s", self.last_rx_rate));
                             });
+
+                            row.label(format!("Tx {} Rx {}", fmt_rate(self.payload_rate.0), fmt_rate(self.payload_rate.1)))
+                                .on_hover_text("Diagnostic data throughput");
                         }
                         let elapsed = self.last_repaint_time.elapsed().as_micros() as u64;
                         self.last_repaint_time = Instant::now();
@@ -224,6 +336,29 @@ impl eframe::App for MainWindow {
                     s_bar_height = nav.available_height()
                 });
             }
+            if self.confirm_discard {
+                let mut open = true;
+                egui::Window::new("Discard unsaved changes?")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+                    .open(&mut open)
+                    .show(ctx, |win| {
+                        win.label("Some changes on this page have not been written to the TCU, and will be lost if you leave.");
+                        win.horizontal(|row| {
+                            if row.button("Discard changes").clicked() {
+                                pop_page = true;
+                                self.confirm_discard = false;
+                            }
+                            if row.button("Keep editing").clicked() {
+                                self.confirm_discard = false;
+                            }
+                        });
+                    });
+                if !open {
+                    self.confirm_discard = false;
+                }
+            }
             if pop_page {
                 self.pop_page();
             }
@@ -235,8 +370,34 @@ impl eframe::App for MainWindow {
                 ))
                 .align_to_end(false)
                 .direction(Direction::BottomUp);
+            if reconnect {
+                let (kind, text) = match self.reconnect() {
+                    Ok(_) => (ToastKind::Success, "Reconnected".to_string()),
+                    Err(e) => (ToastKind::Error, format!("Reconnect failed: {e}")),
+                };
+                toasts.add(Toast {
+                    kind,
+                    text: WidgetText::RichText(RichText::new(text)),
+                    options: ToastOptions {
+                        show_icon: true,
+                        expires_at: Some(Instant::now().add(Duration::from_secs(5))),
+                    },
+                });
+            }
             self.show_back = true;
-            egui::CentralPanel::default().show(ctx, |main_win_ui| {
+            let dangerous = self.pages[0].is_dangerous();
+            let mut panel_frame = egui::Frame::central_panel(&ctx.style());
+            if dangerous {
+                panel_frame = panel_frame.stroke(Stroke::new(3.0, DANGER_COLOR));
+            }
+            egui::CentralPanel::default().frame(panel_frame).show(ctx, |main_win_ui| {
+                if dangerous {
+                    egui::Frame::none().fill(DANGER_COLOR).inner_margin(4.0).show(main_win_ui, |banner| {
+                        banner.set_width(banner.available_width());
+                        banner.label(RichText::new("DANGER ZONE - Actions on this page can reset or brick the TCU").color(Color32::WHITE).strong());
+                    });
+                    main_win_ui.add_space(5.0);
+                }
                 match self.pages[0].make_ui(main_win_ui, frame) {
                     PageAction::None => {}
                     PageAction::Destroy => {
@@ -257,7 +418,7 @@ impl eframe::App for MainWindow {
                         self.show_back = false;
                     }
                     PageAction::SendNotification { text, kind } => {
-                        println!("Pushing notification {}", text);
+                        log::info!("Notification: {}", text);
                         toasts.add(Toast {
                             kind,
                             text: WidgetText::RichText(RichText::new(text)),
@@ -277,83 +438,7 @@ impl eframe::App for MainWindow {
             // Show Log viewer
             if self.show_logger {
                 egui::Window::new("Log view").open(&mut self.show_logger).show(ctx, |ui| {
-                    let is_dark = ctx.style().visuals.dark_mode;
-                    let table = TableBuilder::new(ui)
-                        .striped(false)
-                        .resizable(true)
-                        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-                        .column(Column::auto()) // Level
-                        .column(Column::initial(100.0).at_least(40.0)) // Timestamp
-                        .column(Column::initial(100.0).range(40.0..=300.0).clip(true)) // Module
-                        .column(Column::remainder()) // Message
-                        .stick_to_bottom(true)
-                        .max_scroll_height(400.0)
-                        .min_scrolled_height(100.0);
-
-                    table.header(20.0, |mut header| {
-                        header.col(|ui| {
-                            ui.strong("Level");
-                        });
-                        header.col(|ui| {
-                            ui.strong("Since boot");
-                        });
-                        header.col(|ui| {
-                            ui.strong("Module");
-                        });
-                        header.col(|ui| {
-                            ui.strong("Message");
-                        });
-                    }).body(|mut body| {
-                        body.rows(10.0, self.logs.len(), |row_index, mut row| {
-                            let msg = &self.logs[row_index];
-                            let c = match msg.lvl {
-                                EspLogLevel::Debug => Color32::DEBUG_COLOR,
-                                EspLogLevel::Info => if is_dark { Color32::GREEN } else { Color32::DARK_GREEN },
-                                EspLogLevel::Warn => if is_dark { Color32::YELLOW } else { Color32::GOLD },
-                                EspLogLevel::Error => if is_dark { Color32::RED } else { Color32::DARK_RED },
-                            };
-                            row.col(|ui| {
-                                let l_txt = match &msg.lvl {
-                                    EspLogLevel::Debug => "DEBUG",
-                                    EspLogLevel::Info => "INFO",
-                                    EspLogLevel::Warn => "WARN",
-                                    EspLogLevel::Error => "ERROR",
-                                };
-                                ui.label(RichText::new(l_txt).color(c));
-                            });
-                            row.col(|ui| {
-                                ui.label(RichText::new(format!("{} Ms", msg.timestamp)).color(c));
-                            });
-                            row.col(|ui| {
-                                ui.label(RichText::new(&msg.tag).color(c));
-                            });
-                            row.col(|ui| {
-                                ui.label(RichText::new(&msg.msg).color(c));
-                            });
-                        })
-                    });
-                    ui.horizontal(|ui| {
-                        if ui.button("Clear logs").clicked() {
-                            self.logs.clear();
-                        }
-                        if ui.button("Save logs to disk").clicked() {
-                            if let Some(p) = rfd::FileDialog::new().add_filter("log file", &["log"]).save_file() {
-                                let mut f = OpenOptions::new().write(true).append(false).create(true).open(p).unwrap();
-                                let mut s = String::new();
-                                for msg in &self.logs {
-                                    let li = match msg.lvl {
-                                        EspLogLevel::Debug => "DD",
-                                        EspLogLevel::Info => "II",
-                                        EspLogLevel::Warn => "WW",
-                                        EspLogLevel::Error => "EE",
-                                    };
-                                    s.push_str(&format!("{} {} - ({}) {}\n", msg.timestamp, li, msg.tag, msg.msg));
-                                }
-                                f.write_all(s.as_bytes()).unwrap();
-
-                            }
-                        }
-                    });
+                    self.log_view.make_ui(ui);
                 });
             }
 
@@ -370,6 +455,14 @@ impl eframe::App for MainWindow {
     }
 }
 
+fn fmt_rate(bytes_per_sec: u32) -> String {
+    if bytes_per_sec >= 1024 {
+        format!("{:.1} KB/s", bytes_per_sec as f32 / 1024.0)
+    } else {
+        format!("{} B/s", bytes_per_sec)
+    }
+}
+
 pub enum PageAction {
     None,
     Destroy,
@@ -387,10 +480,21 @@ pub trait InterfacePage {
     fn destroy_nag(&self) -> bool {
         false
     }
+    /// Called when the page is shown, and on every open page after a reconnect.
+    /// Pages holding a [Nag52Diag] should switch to `nag` here, so they keep working after a reconnect
     fn on_load(&mut self, nag: Option<Arc<Nag52Diag>>){}
     fn nag_destroy_before_load(&self) -> bool {
         false
     }
+    /// Pages with edits that have not been written to the TCU. Leaving such a page with
+    /// the back button asks the user to confirm first
+    fn has_unsaved_changes(&self) -> bool {
+        false
+    }
+    /// Pages that can brick or reset the TCU. These are drawn with a red border and warning banner
+    fn is_dangerous(&self) -> bool {
+        false
+    }
 }
 
 pub trait StatusBar {